@@ -10,10 +10,10 @@ const PIXEL_SIZE: vec2<f32> = vec2<f32>(4.0, 4.0); // Pixel size for pixelation
 
 struct PostProcessSettings {
     intensity: f32,
-#ifdef SIXTEEN_BYTE_ALIGNMENT
-    // WebGL2 structs must be 16-byte aligned.
-    _webgl2_padding: vec3<f32>
-#endif
+    // Ambient tint from the sun's colour, already blended towards white on the CPU.
+    tint: vec3<f32>,
+    // Extra red/blue split while a rocket is being hit.
+    aberration: f32,
 }
 @group(0) @binding(2) var<uniform> settings: PostProcessSettings;
 
@@ -47,11 +47,16 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
 
     let edge = edgeMask(pixelUV);
 
-    let redChannel = textureSample(screen_texture, texture_sampler, (pixelUV - vec2<f32>(0.5)) * CA_AMT + vec2<f32>(0.5)).r;
+    // Split outwards from the centre of the screen, clamped so the shifted samples stay in the texture
+    let split = (pixelUV - vec2<f32>(0.5)) * settings.aberration;
+    let redUV = clamp((pixelUV - vec2<f32>(0.5)) * CA_AMT + vec2<f32>(0.5) + split, vec2<f32>(0.0), vec2<f32>(1.0));
+    let blueUV = clamp((pixelUV - vec2<f32>(0.5)) / CA_AMT + vec2<f32>(0.5) - split, vec2<f32>(0.0), vec2<f32>(1.0));
+
+    let redChannel = textureSample(screen_texture, texture_sampler, redUV).r;
     let greenChannel = textureSample(screen_texture, texture_sampler, pixelUV).g;
-    let blueChannel = textureSample(screen_texture, texture_sampler, (pixelUV - vec2<f32>(0.5)) / CA_AMT + vec2<f32>(0.5)).b;
+    let blueChannel = textureSample(screen_texture, texture_sampler, blueUV).b;
 
-    var color = vec3<f32>(redChannel, greenChannel, blueChannel) * edge;
+    var color = vec3<f32>(redChannel, greenChannel, blueChannel) * edge * settings.tint;
 
     let fragCoord = in.position.xy;  
     if (fragCoord.y % 2.0 < 1.0) {
@@ -2,6 +2,13 @@
 
 @group(2) @binding(0) var<uniform> material_color: vec4<f32>;
 @group(2) @binding(1) var<uniform> time: f32;
+@group(2) @binding(2) var<uniform> star_density: f32;
+@group(2) @binding(3) var<uniform> twinkle_speed: f32;
+@group(2) @binding(4) var<uniform> scroll_velocity: vec2<f32>;
+@group(2) @binding(5) var<uniform> star_scale: f32;
+@group(2) @binding(6) var<uniform> parallax: f32;
+@group(2) @binding(7) var<uniform> view_offset: vec2<f32>;
+@group(2) @binding(8) var<uniform> backdrop: f32;
 
 fn random2(p: vec2<f32>) -> f32 {
     let dot_product = dot(p, vec2<f32>(12.9898, 78.233));
@@ -10,14 +17,23 @@ fn random2(p: vec2<f32>) -> f32 {
 
 @fragment
 fn fragment(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    let uv = vertex.position.xy;
+    // Pixels run downwards while the world's y axis points up
+    let view = vec2<f32>(view_offset.x, -view_offset.y) * parallax;
+    // Snap the drifted position to whole pixels so every star keeps its hash while it moves
+    let uv = floor(vertex.position.xy + scroll_velocity * time + view) + vec2<f32>(0.5);
 
     let motion_offset = vec2<f32>(0.01, 0.0);
 
-    let scaled_uv = uv * 0.2; // Further decrease the scale for much larger stars
+    let scaled_uv = uv * star_scale;
 
-    let brightness = step(0.995, random2(scaled_uv + motion_offset));
-    let density_factor = step(0.05, fract(scaled_uv.x) * fract(scaled_uv.y)); 
+    let brightness = step(1.0 - star_density, random2(scaled_uv + motion_offset));
+    let density_factor = step(0.05, fract(scaled_uv.x) * fract(scaled_uv.y));
 
-    return material_color * vec4<f32>(brightness * density_factor, brightness * density_factor, brightness * density_factor, 0.8);
+    // Each star pulses on its own phase
+    let phase = random2(scaled_uv + vec2<f32>(17.0, 31.0)) * 6.2831;
+    let twinkle = 0.75 + 0.25 * sin(time * twinkle_speed + phase);
+
+    let star = brightness * density_factor * twinkle;
+    let colour = material_color.rgb * star;
+    return vec4<f32>(colour, max(backdrop, star * 0.8));
 }
@@ -0,0 +1,41 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0) var<uniform> material_color: vec4<f32>;
+@group(2) @binding(1) var<uniform> time: f32;
+// Share of the mesh radius covered by the sun's surface, the rest is corona
+@group(2) @binding(2) var<uniform> core_radius: f32;
+@group(2) @binding(3) var<uniform> pulse_speed: f32;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+// Value noise, smooth enough for slowly drifting flares
+fn noise(p: vec2<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    let bottom = mix(hash(i), hash(i + vec2<f32>(1.0, 0.0)), u.x);
+    let top = mix(hash(i + vec2<f32>(0.0, 1.0)), hash(i + vec2<f32>(1.0, 1.0)), u.x);
+    return mix(bottom, top, u.y);
+}
+
+@fragment
+fn fragment(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let offset = (vertex.uv - vec2<f32>(0.5)) * 2.0;
+    let distance = length(offset);
+    // Stays above 1 so the sun keeps blooming at the bottom of the pulse
+    let pulse = 1.0 + 0.15 * sin(time * pulse_speed);
+
+    if distance <= core_radius {
+        let churn = noise(offset * 6.0 + vec2<f32>(time * 0.3, -time * 0.2));
+        return vec4<f32>(material_color.rgb * pulse * (0.85 + 0.3 * churn), 1.0);
+    }
+
+    // Sampled along the direction rather than the angle, so there is no seam
+    let direction = offset / distance;
+    let flare = noise(direction * 3.0 + vec2<f32>(time * 0.5, time * 0.3));
+    let reach = (1.0 - core_radius) * (0.35 + 0.65 * flare);
+    let glow = 1.0 - clamp((distance - core_radius) / reach, 0.0, 1.0);
+    return vec4<f32>(material_color.rgb * pulse, glow * glow * 0.8);
+}
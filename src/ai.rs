@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+
+use crate::check_sun_collision;
+use crate::game_state::{in_match, not_paused, GameState};
+use crate::rng::{random_seed, GameRng};
+use crate::rocket::{read_player_input, Rocket, RocketInput, MAX_PLAYERS};
+use crate::sun::{kill_distance, nearest_sun, Sun};
+
+// Extra distance past the kill distance the AI keeps from the sun
+const SUN_SAFETY_MARGIN: f32 = 120.;
+// How far ahead, in seconds, the AI checks where thrusting would take it
+const THRUST_LOOKAHEAD: f32 = 0.6;
+// Headings within these angles count as aligned
+const THRUST_ANGLE: f32 = 0.5;
+const FIRE_ANGLE: f32 = 0.12;
+const FIRE_RANGE: f32 = 450.;
+// Closes in on the target until it is this far away
+const PREFERRED_RANGE: f32 = 220.;
+// Turn rate asked for per radian of heading error, and the slack around it
+const TURN_GAIN: f32 = 2.;
+const TURN_TOLERANCE: f32 = 0.05;
+// Distance from the sun's centre the AI circles at while it has nobody to chase,
+// and the speed it keeps up there
+const ORBIT_RADIUS: f32 = 300.;
+const ORBIT_SPEED: f32 = 100.;
+const DIFFICULTY_TEXT_COLOR: Color = Color::srgba(1., 1., 1., 0.8);
+
+/// Lets the AI fly the second rocket, started with `--vs-ai`. The difficulty
+/// comes from `--ai-difficulty easy|normal|hard` and can be changed on the menu.
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        let mut opponents = AiOpponents::default();
+        if std::env::args().any(|arg| arg == "--vs-ai") {
+            // The second rocket, every match has at least two
+            opponents.0[1] = true;
+        }
+        app.insert_resource(opponents)
+            .insert_resource(AiDifficulty::from_args())
+            .add_systems(Startup, add_difficulty_text)
+            .add_systems(
+                Update,
+                (
+                    cycle_ai_difficulty.run_if(in_state(GameState::Menu)),
+                    update_difficulty_text,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (attach_ai_controllers, ai_rocket)
+                    .chain()
+                    .run_if(in_match)
+                    .run_if(not_paused)
+                    .after(read_player_input),
+            );
+    }
+}
+
+/// How well the AI flies, chosen before a match
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AiDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl AiDifficulty {
+    fn from_args() -> Self {
+        let mut args = std::env::args().skip_while(|arg| arg != "--ai-difficulty");
+        let name = args.nth(1);
+        match name.as_deref() {
+            Some("easy") => AiDifficulty::Easy,
+            Some("hard") => AiDifficulty::Hard,
+            Some("normal") | None => AiDifficulty::Normal,
+            Some(name) => {
+                warn!("Unknown AI difficulty {name:?}, playing on normal");
+                AiDifficulty::Normal
+            }
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            AiDifficulty::Easy => AiDifficulty::Normal,
+            AiDifficulty::Normal => AiDifficulty::Hard,
+            AiDifficulty::Hard => AiDifficulty::Easy,
+        }
+    }
+
+    /// Seconds between two looks at the target, in between the AI goes by
+    /// where it last saw it heading
+    pub fn reaction_time(self) -> f32 {
+        match self {
+            AiDifficulty::Easy => 0.6,
+            AiDifficulty::Normal => 0.25,
+            AiDifficulty::Hard => 0.08,
+        }
+    }
+
+    /// Largest angle in radians the AI's shots go off target by
+    pub fn aim_error(self) -> f32 {
+        match self {
+            AiDifficulty::Easy => 0.25,
+            AiDifficulty::Normal => 0.08,
+            AiDifficulty::Hard => 0.02,
+        }
+    }
+
+    /// Fraction of the full intercept the AI aims ahead of its target
+    pub fn lead(self) -> f32 {
+        match self {
+            AiDifficulty::Easy => 0.,
+            AiDifficulty::Normal => 0.4,
+            AiDifficulty::Hard => 0.9,
+        }
+    }
+}
+
+/// Players flown by the AI instead of the keyboard or a gamepad
+#[derive(Resource, Default)]
+pub struct AiOpponents(pub [bool; MAX_PLAYERS]);
+
+/// Replaces the player's controls, the AI fills the rocket's `RocketInput` so
+/// it flies through the same movement code as a human
+#[derive(Component)]
+pub struct AiController {
+    difficulty: AiDifficulty,
+    // Position and velocity of the target at the last look
+    seen: Option<(Vec2, Vec2)>,
+    reaction: Timer,
+    // Angle the shots go off target by, rolled again at every look
+    aim_offset: f32,
+    // Apart from `GameRng`, so the AI's guesses don't change how the match plays out
+    rng: GameRng,
+}
+
+impl AiController {
+    pub fn new(difficulty: AiDifficulty) -> Self {
+        Self {
+            difficulty,
+            seen: None,
+            reaction: Timer::from_seconds(difficulty.reaction_time(), TimerMode::Repeating),
+            aim_offset: 0.,
+            rng: GameRng::new(random_seed()),
+        }
+    }
+}
+
+/// Hands every new rocket of an AI player, including respawned ones, its controller
+fn attach_ai_controllers(
+    mut commands: Commands,
+    opponents: Res<AiOpponents>,
+    difficulty: Res<AiDifficulty>,
+    rockets: Query<(Entity, &Rocket), Added<Rocket>>,
+) {
+    for (entity, rocket) in rockets.iter() {
+        if opponents.0[rocket.player] {
+            commands.entity(entity).insert(AiController::new(*difficulty));
+        }
+    }
+}
+
+fn ai_rocket(
+    time: Res<Time>,
+    suns: Query<(&Sun, &Transform), Without<Rocket>>,
+    targets: Query<(&Rocket, &Transform)>,
+    mut rockets: Query<(&Rocket, &Transform, &mut AiController, &mut RocketInput)>,
+) {
+    for (rocket, transform, mut ai, mut input) in rockets.iter_mut() {
+        let position = transform.translation.truncate();
+        let facing = transform.rotation.mul_vec3(Vec3::Y).truncate();
+
+        let sun = nearest_sun(suns.iter(), position);
+        let danger = sun.and_then(|(sun, sun_transform)| {
+            let away = position - sun_transform.translation.truncate();
+            (away.length() < kill_distance(sun, rocket) + SUN_SAFETY_MARGIN)
+                .then_some(away.normalize_or_zero())
+        });
+
+        if ai.reaction.tick(time.delta()).just_finished() {
+            let closest = targets
+                .iter()
+                .filter(|(other, _)| other.player != rocket.player)
+                .min_by(|(_, a), (_, b)| {
+                    let a = a.translation.truncate().distance_squared(position);
+                    let b = b.translation.truncate().distance_squared(position);
+                    a.total_cmp(&b)
+                });
+            ai.seen = closest.map(|(other, other_transform)| {
+                (other_transform.translation.truncate(), other.velocity)
+            });
+            let error = ai.difficulty.aim_error();
+            ai.aim_offset = ai.rng.range(-error, error);
+        }
+        // Where the target should be by now if it kept going the same way
+        let target = ai
+            .seen
+            .map(|(seen, velocity)| seen + velocity * ai.reaction.elapsed_secs());
+
+        let mut next = RocketInput::default();
+        let (heading, wants_thrust) = if let Some(away) = danger {
+            // Climb out of the well at an angle, along the current motion, rather
+            // than fighting the pull head on
+            let side = if away.perp().dot(rocket.velocity) >= 0. { 1. } else { -1. };
+            ((away + away.perp() * side).normalize(), true)
+        } else if let (Some(target), Some((_, velocity))) = (target, ai.seen) {
+            let offset = target - position;
+            let flight_time = offset.length() / rocket.weapon.bullet_speed();
+            let intercept = offset + velocity * flight_time * ai.difficulty.lead();
+            let aim = Vec2::from_angle(ai.aim_offset).rotate(intercept);
+            let error = facing.angle_between(aim).abs();
+            next.fire = error < FIRE_ANGLE && offset.length() < FIRE_RANGE && rocket.ammo > 0;
+            (aim, offset.length() > PREFERRED_RANGE)
+        } else if let Some((_, sun_transform)) = sun {
+            // Circle the sun with nobody to chase, steering back towards the
+            // orbit when drifting off it
+            let away = (position - sun_transform.translation.truncate()).normalize_or_zero();
+            let drift = (ORBIT_RADIUS - position.distance(sun_transform.translation.truncate()))
+                / ORBIT_RADIUS;
+            let side = if away.perp().dot(rocket.velocity) >= 0. { 1. } else { -1. };
+            let heading = (away.perp() * side + away * drift).normalize_or_zero();
+            (heading, rocket.velocity.length() < ORBIT_SPEED)
+        } else {
+            (facing, false)
+        };
+
+        let error = facing.angle_between(heading);
+        let turn =
+            (error * TURN_GAIN).clamp(-rocket.max_rotation_speed, rocket.max_rotation_speed);
+        next.rotate_left = rocket.rotation_speed < turn - TURN_TOLERANCE;
+        next.rotate_right = rocket.rotation_speed > turn + TURN_TOLERANCE;
+
+        // Never thrust towards a spot that would burn
+        let ahead = Transform::from_translation(
+            (position + (rocket.velocity + facing * rocket.top_speed()) * THRUST_LOOKAHEAD)
+                .extend(0.),
+        );
+        let burns = suns.iter().any(|(sun, sun_transform)| {
+            check_sun_collision(&ahead, sun_transform, kill_distance(sun, rocket))
+        });
+        next.thrust = wants_thrust && error.abs() < THRUST_ANGLE && !burns;
+
+        next.reload = rocket.ammo == 0;
+        // Held until a fixed step has seen it, like a key press
+        next.fire |= input.fire;
+        *input = next;
+    }
+}
+
+#[derive(Component)]
+struct DifficultyText;
+
+fn add_difficulty_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.,
+                color: DIFFICULTY_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(64.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+        DifficultyText,
+    ));
+}
+
+/// Tab cycles the AI difficulty on the menu, it holds for the next match
+fn cycle_ai_difficulty(keys: Res<ButtonInput<KeyCode>>, mut difficulty: ResMut<AiDifficulty>) {
+    if keys.just_pressed(KeyCode::Tab) {
+        *difficulty = difficulty.next();
+        info!("AI difficulty now {:?}", *difficulty);
+    }
+}
+
+/// Shown on the menu while an AI opponent is playing
+fn update_difficulty_text(
+    state: Res<State<GameState>>,
+    opponents: Res<AiOpponents>,
+    difficulty: Res<AiDifficulty>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<DifficultyText>>,
+) {
+    for (mut text, mut visibility) in &mut texts {
+        let shown = *state.get() == GameState::Menu && opponents.0.contains(&true);
+        *visibility = if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        text.sections[0].value = format!("AI difficulty: {:?}  [Tab to change]", *difficulty);
+    }
+}
@@ -0,0 +1,241 @@
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+
+use crate::bullet::Bullet;
+use crate::collision::{Collider, CollisionEvent, CollisionKind};
+use crate::effects::spawn_explosion;
+use crate::respawn::safe_spawn_position;
+use crate::rng::GameRng;
+use crate::rocket::{Arena, BoundaryMode, Rocket, ScreenWrap};
+use crate::sun::{gravity_step, PhysicsConfig, Sun};
+use crate::time_scale::TimeScale;
+
+const ASTEROID_COLOR: Color = Color::srgb(0.45, 0.42, 0.4);
+// Share of the suns' pull on rockets that asteroids feel, and their speed cap
+const ASTEROID_GRAVITY_SCALE: f64 = 0.4;
+const ASTEROID_MAX_SPEED: f32 = 120.;
+// Size of each fragment relative to the asteroid it broke off, and how fast
+// the fragments fly apart
+const FRAGMENT_SCALE: f32 = 0.6;
+const FRAGMENT_SPEED: f32 = 40.;
+
+#[derive(Component)]
+pub struct Asteroid {
+    pub velocity: Vec2,
+    pub radius: f32,
+    // Worn down by bullets, the asteroid breaks up at zero
+    pub health: f32,
+}
+
+#[derive(Resource)]
+pub struct AsteroidSettings {
+    // Asteroids spawned at the start of each match
+    pub count: usize,
+    pub min_radius: f32,
+    pub max_radius: f32,
+    pub max_drift: f32,
+    pub health: f32,
+    // Pieces a destroyed asteroid breaks into, none once they would be
+    // smaller than `min_fragment_radius`
+    pub fragments: usize,
+    pub min_fragment_radius: f32,
+}
+
+impl Default for AsteroidSettings {
+    fn default() -> Self {
+        Self {
+            count: 4,
+            min_radius: 14.,
+            max_radius: 26.,
+            max_drift: 40.,
+            health: 75.,
+            fragments: 2,
+            min_fragment_radius: 9.,
+        }
+    }
+}
+
+/// Scatters the asteroids clear of the suns and rockets, each drifting in a
+/// random direction
+pub fn add_asteroids(
+    mut commands: Commands,
+    settings: Res<AsteroidSettings>,
+    mut rng: ResMut<GameRng>,
+    arena: Res<Arena>,
+    rockets: Query<&Transform, With<Rocket>>,
+    suns: Query<&Transform, With<Sun>>,
+) {
+    let mut occupied: Vec<Vec2> = rockets
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let suns: Vec<Vec2> = suns
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    for _ in 0..settings.count {
+        let position = safe_spawn_position(&mut rng, &arena, &occupied, &suns);
+        occupied.push(position);
+        let radius = rng.range(settings.min_radius, settings.max_radius);
+        let angle = rng.range(0., std::f32::consts::TAU);
+        let speed = rng.range(0., settings.max_drift);
+        let asteroid = Asteroid {
+            velocity: Vec2::from_angle(angle) * speed,
+            radius,
+            health: settings.health,
+        };
+        spawn_asteroid(&mut commands, position, asteroid);
+    }
+}
+
+fn spawn_asteroid(commands: &mut Commands, position: Vec2, asteroid: Asteroid) {
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(position.extend(0.5))),
+        Collider {
+            radius: asteroid.radius,
+        },
+        asteroid,
+        ScreenWrap,
+    ));
+}
+
+/// Breaks a destroyed asteroid into smaller pieces flying apart across the
+/// path of the bullet that finished it, keeping the asteroid's own drift
+fn spawn_fragments(
+    commands: &mut Commands,
+    settings: &AsteroidSettings,
+    asteroid: &Asteroid,
+    position: Vec2,
+    bullet_velocity: Vec2,
+) {
+    let radius = asteroid.radius * FRAGMENT_SCALE;
+    if settings.fragments == 0 || radius < settings.min_fragment_radius {
+        return;
+    }
+    let across = bullet_velocity.try_normalize().unwrap_or(Vec2::Y).perp();
+    for index in 0..settings.fragments {
+        // Spread evenly over a half circle centred on the bullet's path
+        let share = (index as f32 + 0.5) / settings.fragments as f32;
+        let angle = (share - 0.5) * std::f32::consts::PI;
+        let direction = Vec2::from_angle(angle).rotate(across);
+        let fragment = Asteroid {
+            velocity: asteroid.velocity + direction * FRAGMENT_SPEED,
+            radius,
+            health: settings.health * FRAGMENT_SCALE,
+        };
+        spawn_asteroid(commands, position + direction * radius, fragment);
+    }
+}
+
+/// Gives every new asteroid a disc of its radius, all sharing one material
+pub fn attach_asteroid_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut material: Local<Option<Handle<ColorMaterial>>>,
+    asteroids: Query<(Entity, &Asteroid), Added<Asteroid>>,
+) {
+    for (entity, asteroid) in asteroids.iter() {
+        let material = material
+            .get_or_insert_with(|| materials.add(ColorMaterial::from(ASTEROID_COLOR)))
+            .clone();
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(Mesh::from(Circle::new(asteroid.radius)))),
+            material,
+        ));
+    }
+}
+
+/// Drifts asteroids along their velocity, pulled a little by the suns, and
+/// bounces them off the suns.
+/// `clip_to_arena` keeps them in the arena like rockets, when it clamps they
+/// also bounce off the window edges instead of sticking to them.
+pub fn move_asteroids(
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    mode: Res<BoundaryMode>,
+    arena: Res<Arena>,
+    config: Res<PhysicsConfig>,
+    suns: Query<(&Sun, &Transform), Without<Asteroid>>,
+    mut asteroids: Query<(&mut Asteroid, &mut Transform)>,
+) {
+    let sun_positions: Vec<Vec2> = suns
+        .iter()
+        .map(|(_, transform)| transform.translation.truncate())
+        .collect();
+    let asteroid_config = PhysicsConfig {
+        g_force: config.g_force * ASTEROID_GRAVITY_SCALE,
+        ..*config
+    };
+    let delta = time_scale.delta(&time);
+
+    for (mut asteroid, mut transform) in asteroids.iter_mut() {
+        let position = transform.translation.truncate();
+        for (sun, sun_transform) in suns.iter() {
+            let offset = position - sun_transform.translation.truncate();
+            let Some(normal) = offset.try_normalize() else {
+                continue;
+            };
+            let touching = offset.length() < sun.radius + asteroid.radius;
+            if touching && asteroid.velocity.dot(normal) < 0. {
+                let velocity = asteroid.velocity;
+                asteroid.velocity = velocity - 2. * velocity.dot(normal) * normal;
+            }
+        }
+        if *mode == BoundaryMode::Clamp {
+            let bounds = Vec2::new(arena.half_width, arena.half_height) - asteroid.radius;
+            if position.x.abs() >= bounds.x && position.x * asteroid.velocity.x > 0. {
+                asteroid.velocity.x = -asteroid.velocity.x;
+            }
+            if position.y.abs() >= bounds.y && position.y * asteroid.velocity.y > 0. {
+                asteroid.velocity.y = -asteroid.velocity.y;
+            }
+        }
+        let (next, velocity) = gravity_step(
+            position,
+            asteroid.velocity,
+            &sun_positions,
+            &asteroid_config,
+            ASTEROID_MAX_SPEED,
+            delta,
+        );
+        asteroid.velocity = velocity;
+        transform.translation.x = next.x;
+        transform.translation.y = next.y;
+    }
+}
+
+/// Bullets chip away at the asteroids they hit and break them up once their
+/// health runs out. Bullets already used up on a rocket are gone by now.
+pub fn check_asteroid_bullet_coll(
+    mut commands: Commands,
+    settings: Res<AsteroidSettings>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut asteroids: Query<(&mut Asteroid, &Transform)>,
+    bullets: Query<&Bullet>,
+) {
+    let mut spent: Vec<Entity> = Vec::new();
+    for collision in collisions.read() {
+        let (bullet_entity, entity) = (collision.a, collision.b);
+        if collision.kind != CollisionKind::BulletAsteroid || spent.contains(&bullet_entity) {
+            continue;
+        }
+        let (Ok(bullet), Ok((mut asteroid, transform))) =
+            (bullets.get(bullet_entity), asteroids.get_mut(entity))
+        else {
+            continue;
+        };
+        if asteroid.health <= 0. {
+            continue;
+        }
+        spent.push(bullet_entity);
+        commands.entity(bullet_entity).despawn();
+
+        asteroid.health -= bullet.damage;
+        if asteroid.health <= 0. {
+            let position = transform.translation.truncate();
+            spawn_explosion(&mut commands, transform.translation, ASTEROID_COLOR);
+            spawn_fragments(&mut commands, &settings, &asteroid, position, bullet.velocity);
+            commands.entity(entity).despawn();
+        }
+    }
+}
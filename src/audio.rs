@@ -0,0 +1,153 @@
+use bevy::{
+    audio::{AudioSinkPlayback, Volume},
+    prelude::*,
+};
+
+use crate::bullet::Bullet;
+use crate::effects::Explosion;
+use crate::game_state::{in_match, GameState, Paused};
+use crate::rocket::{Rocket, RocketInput};
+use crate::settings::GameSettings;
+use crate::sun::Sun;
+
+// An explosion this close to a sun's surface is a rocket burning up in it
+const INCINERATE_MARGIN: f32 = 40.;
+const THRUST_VOLUME: f32 = 0.4;
+const FIRE_VOLUME: f32 = 0.5;
+
+/// Sound effects for thrust, shots, explosions and the sun, and the music
+/// looping underneath. The master and music volume come from `game.ron`.
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (load_sounds, apply_volume_setting, play_music).chain())
+            .add_systems(
+                Update,
+                (
+                    attach_thrust_sounds,
+                    update_thrust_sounds,
+                    play_fire_sounds,
+                    play_explosion_sounds,
+                ),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct Sounds {
+    thrust: Handle<AudioSource>,
+    fire: Handle<AudioSource>,
+    explosion: Handle<AudioSource>,
+    incinerate: Handle<AudioSource>,
+    music: Handle<AudioSource>,
+}
+
+/// The looping engine noise of the rocket it is a child of
+#[derive(Component)]
+pub struct ThrustSound;
+
+fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Sounds {
+        thrust: asset_server.load("sounds/thrust.wav"),
+        fire: asset_server.load("sounds/fire.wav"),
+        explosion: asset_server.load("sounds/explosion.wav"),
+        incinerate: asset_server.load("sounds/incinerate.wav"),
+        music: asset_server.load("sounds/music.wav"),
+    });
+}
+
+fn apply_volume_setting(settings: Res<GameSettings>, mut volume: ResMut<GlobalVolume>) {
+    *volume = GlobalVolume::new(settings.volume);
+}
+
+fn play_music(mut commands: Commands, sounds: Res<Sounds>, settings: Res<GameSettings>) {
+    commands.spawn(AudioBundle {
+        source: sounds.music.clone(),
+        settings: PlaybackSettings::LOOP.with_volume(Volume::new(settings.music_volume)),
+    });
+}
+
+/// Gives every new rocket its engine loop, silent until it thrusts
+fn attach_thrust_sounds(
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    rockets: Query<Entity, Added<Rocket>>,
+) {
+    for entity in rockets.iter() {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                AudioBundle {
+                    source: sounds.thrust.clone(),
+                    settings: PlaybackSettings {
+                        paused: true,
+                        volume: Volume::new(THRUST_VOLUME),
+                        ..PlaybackSettings::LOOP
+                    },
+                },
+                ThrustSound,
+            ));
+        });
+    }
+}
+
+/// Plays each engine loop while its rocket accelerates, and silences them all
+/// while the match is paused or over
+fn update_thrust_sounds(
+    paused: Res<Paused>,
+    state: Res<State<GameState>>,
+    rockets: Query<&RocketInput>,
+    sinks: Query<(&Parent, &AudioSink), With<ThrustSound>>,
+) {
+    let running = in_match(state) && !paused.0;
+    for (parent, sink) in sinks.iter() {
+        let thrusting = rockets.get(parent.get()).is_ok_and(|input| input.thrust);
+        if running && thrusting {
+            sink.play();
+        } else {
+            sink.pause();
+        }
+    }
+}
+
+/// One shot sound per frame however many bullets were fired, a spread would
+/// otherwise play three times as loud
+fn play_fire_sounds(
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    bullets: Query<(), Added<Bullet>>,
+) {
+    if bullets.is_empty() {
+        return;
+    }
+    commands.spawn(AudioBundle {
+        source: sounds.fire.clone(),
+        settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(FIRE_VOLUME)),
+    });
+}
+
+/// Every rocket or asteroid blowing up goes bang, one burning up in a sun
+/// sizzles instead
+fn play_explosion_sounds(
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    explosions: Query<&Transform, Added<Explosion>>,
+    suns: Query<(&Sun, &Transform)>,
+) {
+    for transform in explosions.iter() {
+        let position = transform.translation.truncate();
+        let in_sun = suns.iter().any(|(sun, sun_transform)| {
+            sun_transform.translation.truncate().distance(position)
+                < sun.radius + INCINERATE_MARGIN
+        });
+        let source = if in_sun {
+            sounds.incinerate.clone()
+        } else {
+            sounds.explosion.clone()
+        };
+        commands.spawn(AudioBundle {
+            source,
+            settings: PlaybackSettings::DESPAWN,
+        });
+    }
+}
@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::rocket::MAX_PLAYERS;
+use crate::ron_file::load_ron;
+
+const CONTROLS_PATH: &str = "controls.ron";
+
+/// Keyboard keys of one player
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct PlayerKeys {
+    pub accelerate: KeyCode,
+    pub rotate_left: KeyCode,
+    pub rotate_right: KeyCode,
+    pub fire: KeyCode,
+    pub reload: KeyCode,
+    pub cycle_weapon: KeyCode,
+    pub boost: KeyCode,
+    // Missing from files saved before it existed, leaving the player without a brake
+    #[serde(default)]
+    pub brake: Option<KeyCode>,
+    #[serde(default)]
+    pub hyperspace: Option<KeyCode>,
+    #[serde(default)]
+    pub shield: Option<KeyCode>,
+}
+
+impl PlayerKeys {
+    pub fn default_for(player: usize) -> Self {
+        match player {
+            0 => Self {
+                accelerate: KeyCode::KeyS,
+                rotate_left: KeyCode::KeyA,
+                rotate_right: KeyCode::KeyD,
+                fire: KeyCode::KeyW,
+                reload: KeyCode::KeyE,
+                cycle_weapon: KeyCode::KeyQ,
+                boost: KeyCode::ShiftLeft,
+                brake: Some(KeyCode::KeyX),
+                hyperspace: Some(KeyCode::KeyZ),
+                shield: Some(KeyCode::KeyC),
+            },
+            1 => Self {
+                accelerate: KeyCode::KeyK,
+                rotate_left: KeyCode::KeyJ,
+                rotate_right: KeyCode::KeyL,
+                fire: KeyCode::KeyI,
+                reload: KeyCode::KeyO,
+                cycle_weapon: KeyCode::KeyU,
+                boost: KeyCode::ShiftRight,
+                brake: Some(KeyCode::Comma),
+                hyperspace: Some(KeyCode::Period),
+                shield: Some(KeyCode::KeyM),
+            },
+            2 => Self {
+                accelerate: KeyCode::ArrowDown,
+                rotate_left: KeyCode::ArrowLeft,
+                rotate_right: KeyCode::ArrowRight,
+                fire: KeyCode::ArrowUp,
+                reload: KeyCode::PageDown,
+                cycle_weapon: KeyCode::Delete,
+                boost: KeyCode::ControlRight,
+                brake: Some(KeyCode::End),
+                hyperspace: Some(KeyCode::Insert),
+                shield: Some(KeyCode::Home),
+            },
+            _ => Self {
+                accelerate: KeyCode::Numpad5,
+                rotate_left: KeyCode::Numpad4,
+                rotate_right: KeyCode::Numpad6,
+                fire: KeyCode::Numpad8,
+                reload: KeyCode::Numpad9,
+                cycle_weapon: KeyCode::Numpad7,
+                boost: KeyCode::Numpad0,
+                brake: Some(KeyCode::Numpad2),
+                hyperspace: Some(KeyCode::Numpad1),
+                shield: Some(KeyCode::Numpad3),
+            },
+        }
+    }
+}
+
+/// Keys of every player, indexed by `Rocket::player`, read from `controls.ron`.
+/// Players missing from the file, e.g. one written for two players, get their
+/// default keys.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct KeyBindings {
+    pub players: Vec<PlayerKeys>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            players: (0..MAX_PLAYERS).map(PlayerKeys::default_for).collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, player: usize) -> PlayerKeys {
+        self.players
+            .get(player)
+            .copied()
+            .unwrap_or_else(|| PlayerKeys::default_for(player))
+    }
+
+    /// Reads the bindings from disk, falling back to the default keys if the file is missing or invalid
+    pub fn load() -> Self {
+        load_ron(CONTROLS_PATH).unwrap_or_else(|| {
+            warn!("Could not read {CONTROLS_PATH}, using the default keys");
+            Self::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ron_file::parse_ron;
+
+    /// Reads the contents of a `controls.ron`, the default keys if they are invalid
+    fn parse(contents: &str) -> KeyBindings {
+        parse_ron(CONTROLS_PATH, contents)
+    }
+
+    #[test]
+    fn reads_the_keys_from_the_file() {
+        let contents = "(players: [(
+            accelerate: ArrowUp,
+            rotate_left: ArrowLeft,
+            rotate_right: ArrowRight,
+            fire: Space,
+            reload: KeyR,
+            cycle_weapon: Tab,
+            boost: ShiftLeft,
+            brake: Some(ArrowDown),
+        )])";
+        let bindings = parse(contents);
+        let keys = bindings.keys(0);
+        assert_eq!(keys.accelerate, KeyCode::ArrowUp);
+        assert_eq!(keys.fire, KeyCode::Space);
+        assert_eq!(keys.brake, Some(KeyCode::ArrowDown));
+        // Left out of the file
+        assert_eq!(keys.hyperspace, None);
+        // Players past the file get their defaults
+        assert_eq!(bindings.keys(1), PlayerKeys::default_for(1));
+    }
+
+    #[test]
+    fn falls_back_to_the_default_keys() {
+        assert_eq!(parse("(players: [(fire: Nope)])"), KeyBindings::default());
+        assert_eq!(parse(""), KeyBindings::default());
+    }
+
+    #[test]
+    fn default_keys_round_trip() {
+        let text = ron::to_string(&KeyBindings::default()).unwrap();
+        assert_eq!(parse(&text), KeyBindings::default());
+    }
+}
@@ -1,101 +1,486 @@
 use bevy::prelude::*;
-use crate::rocket::Rocket;
-use bevy_hanabi::prelude::*;
+use crate::collision::{Collider, CollisionEvent, CollisionKind};
+use crate::effects::spawn_impact;
+use crate::powerup::{TripleShot, TRIPLE_SHOT_ANGLES};
+use crate::respawn::SpawnProtection;
+use crate::rocket::{
+    Arena, BoundaryMode, DestroyCause, Rocket, RocketDamaged, RocketDestroyed, RocketInput,
+    ScreenWrap,
+};
+use crate::shield::SHIELD_BUBBLE_COLOR;
+use crate::sun::{gravity_step, PhysicsConfig, Sun};
+use crate::time_scale::TimeScale;
 
-#[derive(Component)]
+// With circle collisions a bullet hits a rocket from 30 units out
+const BULLET_RADIUS: f32 = 5.;
+
+// Bullets are pulled a little harder than rockets, at their speed the paths
+// still only bend rather than fall straight in
+const BULLET_GRAVITY_SCALE: f64 = 1.6;
+const BULLET_MAX_SPEED: f32 = 500.;
+// Share of the muzzle speed a shot always keeps over its shooter along the
+// barrel, so flying backwards can't turn it around and the shooter can't catch it
+const MIN_MUZZLE_FRACTION: f32 = 0.5;
+// Seconds a missile keeps steering, it flies on straight for the rest of its lifetime
+const MISSILE_FUEL: f32 = 3.;
+// Seconds a shot passes through the rocket that fired it
+const OWNER_GRACE: f32 = 0.25;
+const BULLET_TEXTURE: &str = "Bullet.png";
+
+#[derive(Component, Clone)]
 pub struct Bullet {
-    pub speed: f32,
+    pub velocity: Vec2,
+    pub damage: f32,
+    pub lifetime: Timer,
+    pub bounces_left: u32,
+    // Player whose rocket fired it
+    pub owner: usize,
+    // Distance flown so far, and the distance over which the damage falls off
+    pub travelled: f32,
+    pub falloff_range: Option<f32>,
+}
+
+impl Bullet {
+    /// Damage dealt by a hit right now, lowered by range for weapons with falloff
+    pub fn damage_at_range(&self, falloff_floor: f32) -> f32 {
+        let Some(range) = self.falloff_range else {
+            return self.damage;
+        };
+        let falloff = (self.travelled / range).min(1.);
+        self.damage * (1. - falloff * (1. - falloff_floor))
+    }
+}
+
+/// What a bullet does to the rocket it hits besides the damage
+#[derive(Resource)]
+pub struct BulletImpact {
+    // Speed a hit adds along the bullet's path, enough shots push a rocket into a sun
+    pub knockback: f32,
+    // Share of the damage left at the end of a falloff range
+    pub falloff_floor: f32,
+}
+
+impl Default for BulletImpact {
+    fn default() -> Self {
+        Self {
+            knockback: 40.,
+            falloff_floor: 0.4,
+        }
+    }
+}
+
+/// A bullet that turns towards the closest enemy rocket while it has fuel,
+/// never its own
+#[derive(Component, Clone)]
+pub struct Missile {
+    // Radians per second, slow enough that a missile can be dodged
+    pub turn_rate: f32,
+    pub fuel: f32,
 }
 
 pub fn spawn_bullet(
     time: Res<Time>,
+    time_scale: Res<TimeScale>,
     mut commands: Commands,
-    keys: Res<ButtonInput<KeyCode>>,
-    asset_server: Res<AssetServer>,
-    mut query: Query<(&mut Transform, &mut Rocket)>,
+    mut query: Query<(&mut Transform, &mut Rocket, &RocketInput, Has<TripleShot>)>,
 ) {
-    let bullet_sprite: Handle<Image> = asset_server.load("Bullet.png");
-
-    for (transform, mut rocket) in query.iter_mut() {
-        let current_time = time.elapsed();
-
-        if keys.just_pressed(rocket.spawn_key)
-           && current_time - rocket.last_shot_time >= rocket.cooldown
-        {
-            let direction = transform.rotation.mul_vec3(Vec3::Y); // Rocket's forward direction
-            let bullet_spawn_pos = transform.translation + direction * 50.0; // Padding of 50 units
-
-            commands.spawn((
-                SpriteBundle {
-                    texture: bullet_sprite.clone(),
-                    transform: Transform {
-                        translation: bullet_spawn_pos,
-                        scale: Vec3::new(2., 2., 1.0),
-                        rotation: transform.rotation,
+    // Slow motion slows the trigger and the reload as much as the bullets
+    let delta = time_scale.delta(&time);
+    for (transform, mut rocket, input, triple_shot) in query.iter_mut() {
+        if input.cycle_weapon {
+            rocket.weapon = rocket.weapon.next();
+        }
+
+        // Holding reload takes priority, a shot pressed mid-reload is dropped
+        // rather than cancelling the reload
+        let reloading = rocket.update_reload(delta, input.reload);
+        let aim = aim_rotation(&rocket, &transform, input);
+        let wants_fire = input.fire && !reloading && rocket.ammo > 0 && aim.is_some();
+
+        if rocket.try_fire(delta, wants_fire) {
+            rocket.ammo -= 1;
+            let aim = aim.unwrap_or(transform.rotation);
+            let weapon = rocket.weapon;
+            let angles = match weapon.angles() {
+                [_] if triple_shot => TRIPLE_SHOT_ANGLES,
+                angles => angles,
+            };
+            for angle in angles {
+                let rotation = aim * Quat::from_rotation_z(angle.to_radians());
+                let direction = rotation.mul_vec3(Vec3::Y); // Bullet's forward direction
+                let bullet_spawn_pos = transform.translation + direction * 50.0; // Padding of 50 units
+
+                let mut bullet = commands.spawn((
+                    SpriteBundle {
+                        transform: Transform {
+                            translation: bullet_spawn_pos,
+                            scale: Vec3::new(2., 2., 1.0),
+                            rotation,
+                        },
                         ..default()
                     },
-                    ..default()
-                },
-                Bullet {
-                    speed: 300.0, // Example bullet speed
-                },
-            ));
-
-            // Update the last shot time
-            rocket.last_shot_time = current_time;
+                    Bullet {
+                        velocity: launch_velocity(
+                            direction.truncate(),
+                            weapon.bullet_speed(),
+                            rocket.velocity,
+                        ),
+                        damage: weapon.damage(),
+                        lifetime: Timer::from_seconds(weapon.bullet_lifetime(), TimerMode::Once),
+                        bounces_left: weapon.bounces(),
+                        owner: rocket.player,
+                        travelled: 0.,
+                        falloff_range: weapon.falloff_range(),
+                    },
+                    Collider {
+                        radius: BULLET_RADIUS,
+                    },
+                    ScreenWrap,
+                ));
+                if let Some(turn_rate) = weapon.homing_turn_rate() {
+                    bullet.insert(Missile {
+                        turn_rate,
+                        fuel: MISSILE_FUEL,
+                    });
+                }
+            }
         }
     }
 }
 
+/// Loads the sprite of every new bullet, `spawn_bullet` leaves it out so the
+/// simulation also runs without an asset server
+pub fn attach_bullet_textures(
+    asset_server: Res<AssetServer>,
+    mut bullets: Query<&mut Handle<Image>, Added<Bullet>>,
+) {
+    for mut texture in bullets.iter_mut() {
+        *texture = asset_server.load(BULLET_TEXTURE);
+    }
+}
+
+/// Direction shots leave the rocket in: straight ahead, or towards the cursor
+/// for a mouse aimed rocket. `None` while the cursor sits right on the rocket,
+/// where there is no direction to shoot in.
+fn aim_rotation(rocket: &Rocket, transform: &Transform, input: &RocketInput) -> Option<Quat> {
+    let Some(target) = input.aim.filter(|_| rocket.mouse_aim) else {
+        return Some(transform.rotation);
+    };
+    let direction = (target - transform.translation.truncate()).try_normalize()?;
+    Some(Quat::from_rotation_z(Vec2::Y.angle_between(direction)))
+}
+
+/// Muzzle velocity plus the shooter's momentum, capped so shots fired while
+/// boosting stay dodgeable
+fn launch_velocity(direction: Vec2, muzzle_speed: f32, rocket_velocity: Vec2) -> Vec2 {
+    let mut velocity =
+        (direction * muzzle_speed + rocket_velocity).clamp_length_max(BULLET_MAX_SPEED);
+    // The cap gives way here, a shot slower than its shooter would hit it
+    let min_forward =
+        rocket_velocity.dot(direction).max(0.) + muzzle_speed * MIN_MUZZLE_FRACTION;
+    let forward = velocity.dot(direction);
+    if forward < min_forward {
+        velocity += direction * (min_forward - forward);
+    }
+    velocity
+}
+
+/// Turns missiles towards the closest living enemy rocket, at most by their turn
+/// rate. A missile without a target or fuel left keeps its heading.
+pub fn steer_missiles(
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    rockets: Query<(&Rocket, &Transform)>,
+    mut missiles: Query<(&Transform, &mut Bullet, &mut Missile)>,
+) {
+    let delta = time_scale.delta(&time);
+    for (transform, mut bullet, mut missile) in missiles.iter_mut() {
+        if missile.fuel <= 0. {
+            continue;
+        }
+        missile.fuel -= delta;
+        let position = transform.translation.truncate();
+        let target = rockets
+            .iter()
+            .filter(|(rocket, _)| rocket.player != bullet.owner && rocket.health > 0.)
+            .map(|(_, transform)| transform.translation.truncate())
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            });
+        let Some(target) = target else {
+            continue;
+        };
+        let max_turn = missile.turn_rate * delta;
+        let turn = bullet.velocity.angle_between(target - position).clamp(-max_turn, max_turn);
+        // NaN while the missile sits on its target or has stopped, it hits either way
+        if turn.is_finite() {
+            bullet.velocity = Vec2::from_angle(turn).rotate(bullet.velocity);
+        }
+    }
+}
 
+/// Moves bullets along their velocity, bent by the suns. Bouncing bullets
+/// reflect off the arena edges.
 pub fn handle_bullet_movement(
     time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    config: Res<PhysicsConfig>,
+    arena: Res<Arena>,
+    suns: Query<&Transform, (With<Sun>, Without<Bullet>)>,
+    mut query: Query<(&mut Transform, &mut Bullet)>,
+) {
+    let sun_positions: Vec<Vec2> = suns
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let bullet_config = PhysicsConfig {
+        g_force: config.g_force * BULLET_GRAVITY_SCALE,
+        ..*config
+    };
+
+    for (mut transform, mut bullet) in query.iter_mut() {
+        let start = transform.translation.truncate();
+        let (mut position, mut velocity) = gravity_step(
+            start,
+            bullet.velocity,
+            &sun_positions,
+            &bullet_config,
+            BULLET_MAX_SPEED,
+            time_scale.delta(&time),
+        );
+        if bullet.bounces_left > 0 {
+            // Both axes are checked, a bullet flying into a corner reflects off both walls
+            let hits = bounce(&mut position.x, &mut velocity.x, arena.half_width) as u32
+                + bounce(&mut position.y, &mut velocity.y, arena.half_height) as u32;
+            // Once out of bounces the bullet leaves the arena and expires
+            if hits > 0 {
+                bullet.bounces_left -= 1;
+            }
+        }
+        bullet.velocity = velocity;
+        bullet.travelled += start.distance(position);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        // Keep the sprite pointing along the curve
+        if let Some(direction) = velocity.try_normalize() {
+            transform.rotation = Quat::from_rotation_arc_2d(Vec2::Y, direction);
+        }
+    }
+}
+
+/// Reflects one axis off the wall at `±limit`. Only a bullet heading out of the
+/// arena is reflected, so one that is still past the wall after bouncing
+/// can't flip back and forth in place.
+fn bounce(position: &mut f32, velocity: &mut f32, limit: f32) -> bool {
+    if position.abs() <= limit || *position * *velocity <= 0. {
+        return false;
+    }
+    *velocity = -*velocity;
+    // Mirror the overshoot back inside the arena
+    *position = position.signum() * (2. * limit - position.abs()).max(-limit);
+    true
+}
+
+/// Removes bullets once their lifetime runs out or they leave the arena, so
+/// the number in flight stays bounded however fast the rockets fire
+pub fn expire_bullets(
+    time: Res<Time>,
+    mode: Res<BoundaryMode>,
+    arena: Res<Arena>,
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Transform, &mut Bullet)>,
+    mut query: Query<(Entity, &Transform, &mut Bullet)>,
 ) {
-    for (entity, mut transform, bullet) in query.iter_mut() {
-        let forward = transform.rotation.mul_vec3(Vec3::Y).normalize();
-        transform.translation += forward * bullet.speed * time.delta_seconds();
-        if transform.translation.y > 510.0 || transform.translation.y < -380.0 {
+    for (entity, transform, mut bullet) in query.iter_mut() {
+        let position = transform.translation;
+        let expired = bullet.lifetime.tick(time.delta()).finished();
+        // Wrapping bullets are carried over by `clip_to_arena` on the next step
+        let outside = !arena.contains(position.truncate());
+        if expired || (outside && *mode != BoundaryMode::Wrap) {
             commands.entity(entity).despawn();
         }
     }
 }
 
+/// Bullets burn up in the suns and hurt the rockets they hit, shields stop
+/// them without taking damage
 pub fn check_bullet_coll(
     mut commands: Commands,
-    rocket_query: Query<(Entity, &Transform), (With<Rocket>, Without<EffectProperties>)>,
-    bullet_query: Query<&Transform, (With<Bullet>, Without<EffectProperties>)>,
-    mut effect: Query<(
-        &mut EffectProperties,
-        &mut EffectInitializers,
-        &mut Transform,
-    )>,
+    impact: Res<BulletImpact>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut damaged: EventWriter<RocketDamaged>,
+    mut destroyed: EventWriter<RocketDestroyed>,
+    mut rocket_query: Query<(&mut Rocket, &Transform), Without<SpawnProtection>>,
+    bullet_query: Query<(&Bullet, &Transform)>,
 ) {
-    let Ok((mut properties, mut initializers, mut effect_transform)) = effect.get_single_mut()
-    else {
-        return;
-    };
+    // A bullet is used up by whatever it touched first
+    let mut spent: Vec<Entity> = Vec::new();
+    for collision in collisions.read() {
+        let bullet_entity = collision.a;
+        if spent.contains(&bullet_entity) {
+            continue;
+        }
+        let Ok((bullet, bullet_transform)) = bullet_query.get(bullet_entity) else {
+            continue;
+        };
+        match collision.kind {
+            CollisionKind::BulletSun => {
+                spent.push(bullet_entity);
+                commands.entity(bullet_entity).despawn();
+            }
+            CollisionKind::BulletShip => {
+                let entity = collision.b;
+                let Ok((mut rocket, enemy_transform)) = rocket_query.get_mut(entity) else {
+                    continue;
+                };
+                if rocket.health <= 0.0 {
+                    continue;
+                }
+                // Leaving the muzzle the shot still overlaps its own rocket
+                let leaving = bullet.lifetime.elapsed_secs() < OWNER_GRACE;
+                if leaving && rocket.player == bullet.owner {
+                    continue;
+                }
+                spent.push(bullet_entity);
+                commands.entity(bullet_entity).despawn();
+                if rocket.shield_up {
+                    spawn_impact(&mut commands, bullet_transform.translation, SHIELD_BUBBLE_COLOR);
+                    continue;
+                }
 
-    for (entity, enemy_transform) in rocket_query.iter() {
-        for bullet_transform in bullet_query.iter() {            
-            let distance = enemy_transform
-            .translation
-            .truncate()
-            .distance(bullet_transform.translation.truncate());
-            if distance < 30.
-            {
-                effect_transform.translation = enemy_transform.translation;
-
-                let r = 255.;
-                let g = 165.;
-                let b = 0.;
-                let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-                properties.set("spawn_color", color.into());
-                initializers.reset();
-    
-                commands.entity(entity).despawn();
+                rocket.last_hit_by = Some(bullet.owner);
+                rocket.apply_knockback(bullet.velocity.normalize_or_zero() * impact.knockback);
+                if rocket.take_damage(bullet.damage_at_range(impact.falloff_floor)) {
+                    // `destroy_rockets` blows it up and reports the kill
+                    destroyed.send(RocketDestroyed {
+                        entity,
+                        player: rocket.player,
+                        position: enemy_transform.translation,
+                        velocity: rocket.velocity,
+                        color: rocket.color,
+                        cause: DestroyCause::Bullet,
+                        killer: Some(bullet.owner),
+                    });
+                } else {
+                    damaged.send(RocketDamaged {
+                        player: rocket.player,
+                        destroyed: false,
+                        attacker: Some(bullet.owner),
+                    });
+                    spawn_impact(&mut commands, bullet_transform.translation, rocket.color);
+                }
             }
+            // Asteroids are shot by `check_asteroid_bullet_coll`
+            _ => {}
         }
     }
-}
\ No newline at end of file
+}
+
+/// A plain shot of the second player's, for tests that move or hit it by hand
+#[cfg(test)]
+pub fn test_bullet(velocity: Vec2) -> Bullet {
+    Bullet {
+        velocity,
+        damage: 10.,
+        lifetime: Timer::from_seconds(1., TimerMode::Once),
+        bounces_left: 0,
+        owner: 1,
+        travelled: 0.,
+        falloff_range: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    use crate::rocket::{test_rocket, MAX_HEALTH};
+
+    /// The rocket after `hits` bullets flying at `velocity` have hit it
+    fn hit(rocket: Rocket, velocity: Vec2, hits: usize) -> Rocket {
+        let mut world = World::new();
+        world.init_resource::<BulletImpact>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.init_resource::<Events<RocketDamaged>>();
+        world.init_resource::<Events<RocketDestroyed>>();
+        let target = world.spawn((Transform::default(), rocket)).id();
+        for _ in 0..hits {
+            let shot = world.spawn((Transform::default(), test_bullet(velocity))).id();
+            world.send_event(CollisionEvent {
+                kind: CollisionKind::BulletShip,
+                a: shot,
+                b: target,
+            });
+            world.run_system_once(check_bullet_coll);
+            world.flush();
+        }
+        world.get::<Rocket>(target).cloned().unwrap()
+    }
+
+    /// Bullets fired by a rocket holding the trigger for `steps` steps of
+    /// `delta` seconds
+    fn shots(steps: u32, delta: f32) -> usize {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<TimeScale>();
+        let mut rocket = test_rocket(0);
+        rocket.fire_cooldown = 0.5;
+        let input = RocketInput {
+            fire: true,
+            ..default()
+        };
+        world.spawn((Transform::default(), rocket, input));
+        for _ in 0..steps {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(delta));
+            world.run_system_once(spawn_bullet);
+        }
+        world.query::<&Bullet>().iter(&world).count()
+    }
+
+    #[test]
+    fn fires_once_per_cooldown() {
+        // Straight away, then after every half second held
+        assert_eq!(shots(1, 0.125), 1);
+        assert_eq!(shots(4, 0.125), 1);
+        assert_eq!(shots(5, 0.125), 2);
+        assert_eq!(shots(16, 0.125), 4);
+        // The same time held in longer frames fires as often
+        assert_eq!(shots(8, 0.25), 4);
+    }
+
+    #[test]
+    fn knockback_pushes_along_the_bullet() {
+        let velocity = Vec2::new(3., -4.) * 100.;
+        let rocket = hit(test_rocket(0), velocity, 1);
+        let push = rocket.velocity.normalize();
+        assert!(push.distance(velocity.normalize()) < 1e-5);
+        assert_eq!(rocket.velocity, rocket.knockback);
+        assert!(rocket.health < MAX_HEALTH);
+    }
+
+    #[test]
+    fn knockback_never_goes_past_max_speed() {
+        let mut rocket = test_rocket(0);
+        rocket.max_speed = 60.;
+        rocket.health = 1000.;
+        let rocket = hit(rocket, Vec2::new(0., 500.), 20);
+        assert!(rocket.velocity.length() <= 60. + 1e-3);
+        assert!(rocket.velocity.y > 0.);
+    }
+
+    #[test]
+    fn damage_falls_off_over_the_range() {
+        let mut shot = test_bullet(Vec2::X);
+        shot.falloff_range = Some(200.);
+        assert_eq!(shot.damage_at_range(0.4), 10.);
+        shot.travelled = 100.;
+        assert!((shot.damage_at_range(0.4) - 7.).abs() < 1e-5);
+        // Past the range the floor is kept
+        shot.travelled = 1000.;
+        assert!((shot.damage_at_range(0.4) - 4.).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+
+use crate::effects::{Explosion, Impact};
+use crate::rocket::Rocket;
+use crate::sun::{kill_distance, nearest_sun, Sun};
+use crate::starfield::Background;
+use crate::{ARENA_HEIGHT, ARENA_WIDTH};
+
+// Rocket whose distance to the sun drives the gravity rumble
+const LOCAL_PLAYER: usize = 0;
+
+/// Camera shake built from two channels: `trauma` for discrete hits such as
+/// explosions, decaying over time, and `rumble`, a continuous level that is
+/// recomputed every frame. Their sum is capped so the camera never moves
+/// further than `max_offset`.
+#[derive(Resource)]
+pub struct ScreenShake {
+    pub trauma: f32,
+    pub rumble: f32,
+    pub max_offset: f32,
+    // Trauma lost per second
+    pub decay: f32,
+    // Trauma added by an explosion at the centre of the screen, fading out
+    // to nothing at `explosion_falloff` units away
+    pub explosion_trauma: f32,
+    pub explosion_falloff: f32,
+    // Trauma added by a bullet hit that didn't destroy the rocket, with the same falloff
+    pub impact_trauma: f32,
+}
+
+impl Default for ScreenShake {
+    fn default() -> Self {
+        Self {
+            trauma: 0.,
+            rumble: 0.,
+            max_offset: 8.,
+            decay: 1.5,
+            explosion_trauma: 0.6,
+            explosion_falloff: 700.,
+            impact_trauma: 0.2,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct GravityRumble {
+    // Distance from the sun where the rumble starts
+    pub onset_distance: f32,
+    // Rumble level reached at the kill distance, from 0 to 1
+    pub max_intensity: f32,
+}
+
+impl Default for GravityRumble {
+    fn default() -> Self {
+        Self {
+            onset_distance: 260.,
+            max_intensity: 0.35,
+        }
+    }
+}
+
+/// Keeps every rocket and sun in view, zooming out as they spread and back in
+/// as they close up. A scale of 1 shows the whole arena.
+#[derive(Resource)]
+pub struct CameraFollow {
+    // World units kept free around the outermost rocket
+    pub padding: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    // How quickly the view catches up, higher is snappier
+    pub smoothing: f32,
+    // Where the view currently is, the shake is added on top
+    pub centre: Vec2,
+    pub scale: f32,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        Self {
+            padding: 120.,
+            min_scale: 0.75,
+            max_scale: 1.3,
+            smoothing: 2.,
+            centre: Vec2::ZERO,
+            scale: 1.,
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn camera_follow(
+    time: Res<Time>,
+    mut follow: ResMut<CameraFollow>,
+    rockets: Query<&Transform, With<Rocket>>,
+    suns: Query<&Transform, With<Sun>>,
+    mut projections: Query<&mut OrthographicProjection, With<Camera2d>>,
+    mut backgrounds: Query<&mut Transform, (With<Background>, Without<Rocket>, Without<Sun>)>,
+) {
+    let positions: Vec<Vec2> = rockets
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let (target_centre, target_scale) = match positions.as_slice() {
+        [] => (Vec2::ZERO, 1.),
+        // The last rocket flying gets the stage to itself
+        [survivor] => (*survivor, 1.),
+        _ => {
+            let points = positions
+                .iter()
+                .copied()
+                .chain(suns.iter().map(|transform| transform.translation.truncate()));
+            let (min, max) = points.fold(
+                (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                |(min, max), point| (min.min(point), max.max(point)),
+            );
+            let size = max - min + Vec2::splat(2. * follow.padding);
+            let scale = (size.x / ARENA_WIDTH).max(size.y / ARENA_HEIGHT);
+            ((min + max) / 2., scale.clamp(follow.min_scale, follow.max_scale))
+        }
+    };
+
+    // Exponential so the easing doesn't depend on the frame rate
+    let blend = 1. - (-follow.smoothing * time.delta_seconds()).exp();
+    follow.centre = follow.centre.lerp(target_centre, blend);
+    follow.scale += (target_scale - follow.scale) * blend;
+
+    for mut projection in &mut projections {
+        projection.scale = follow.scale;
+    }
+    // The starfield is drawn in screen space, moving and scaling the quads
+    // along with the view only keeps them covering the screen, the layers
+    // scroll for the parallax themselves
+    for mut transform in &mut backgrounds {
+        transform.translation.x = follow.centre.x;
+        transform.translation.y = follow.centre.y;
+        transform.scale = Vec3::new(follow.scale, follow.scale, 1.);
+    }
+}
+
+pub fn gravity_rumble(
+    settings: Res<GravityRumble>,
+    mut shake: ResMut<ScreenShake>,
+    rockets: Query<(&Rocket, &Transform)>,
+    suns: Query<(&Sun, &Transform)>,
+) {
+    let local = rockets
+        .iter()
+        .find(|(rocket, _)| rocket.player == LOCAL_PLAYER);
+    let Some((rocket, transform)) = local else {
+        shake.rumble = 0.;
+        return;
+    };
+    let Some((sun, sun_transform)) = nearest_sun(suns.iter(), transform.translation.truncate())
+    else {
+        shake.rumble = 0.;
+        return;
+    };
+
+    let distance = transform
+        .translation
+        .truncate()
+        .distance(sun_transform.translation.truncate());
+    let kill = kill_distance(sun, rocket);
+    let onset = settings.onset_distance.max(kill + 1.);
+    let proximity = ((onset - distance) / (onset - kill)).clamp(0., 1.);
+
+    // Squared so it stays faint on the outskirts and ramps up close to the sun
+    shake.rumble = settings.max_intensity * proximity * proximity;
+}
+
+/// Adds trauma for every new explosion and bullet hit, less the further from
+/// the centre of the view it is
+pub fn shake_on_explosions(
+    mut shake: ResMut<ScreenShake>,
+    follow: Res<CameraFollow>,
+    explosions: Query<&Transform, Added<Explosion>>,
+    impacts: Query<&Transform, Added<Impact>>,
+) {
+    let (explosion_trauma, impact_trauma) = (shake.explosion_trauma, shake.impact_trauma);
+    let hits = explosions
+        .iter()
+        .map(|transform| (transform, explosion_trauma))
+        .chain(impacts.iter().map(|transform| (transform, impact_trauma)));
+    for (transform, trauma) in hits {
+        let distance = transform.translation.truncate().distance(follow.centre);
+        let closeness = 1. - (distance / shake.explosion_falloff).min(1.);
+        shake.trauma = (shake.trauma + trauma * closeness).min(1.);
+    }
+}
+
+pub fn apply_screen_shake(
+    time: Res<Time>,
+    mut shake: ResMut<ScreenShake>,
+    follow: Res<CameraFollow>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    shake.trauma = (shake.trauma - shake.decay * time.delta_seconds()).max(0.);
+
+    let amount = (shake.trauma * shake.trauma + shake.rumble).min(1.);
+    let t = time.elapsed_seconds();
+    // Cheap smooth noise, the camera shake must not consume the gameplay rng
+    let jitter = Vec2::new(
+        (t * 47.).sin() * (t * 13.).cos(),
+        (t * 53.).cos() * (t * 17.).sin(),
+    );
+    let position = follow.centre + jitter * amount * shake.max_offset;
+
+    for mut transform in &mut cameras {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
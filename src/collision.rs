@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+
+use crate::asteroid::Asteroid;
+use crate::bullet::Bullet;
+use crate::grid::CollisionGrid;
+use crate::hitbox::{obb_vs_circle, obb_vs_obb, HitboxSettings, Obb};
+use crate::powerup::Powerup;
+use crate::rocket::Rocket;
+use crate::sun::Sun;
+
+/// Circle an entity touches others with. Rockets swap theirs for the
+/// oriented hitbox while `HitboxSettings::use_obb` is on.
+#[derive(Component, Clone, Copy)]
+pub struct Collider {
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollisionKind {
+    ShipShip,
+    ShipSun,
+    ShipAsteroid,
+    ShipPowerup,
+    BulletShip,
+    BulletSun,
+    BulletAsteroid,
+}
+
+/// Two colliders touching at the start of a step. `a` is the body named first
+/// in the kind, the bullet of a `BulletShip` and the rocket of a `ShipSun`.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CollisionEvent {
+    pub kind: CollisionKind,
+    pub a: Entity,
+    pub b: Entity,
+}
+
+// Declared in the order bodies are named in a `CollisionKind`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    Bullet,
+    Ship,
+    Sun,
+    Asteroid,
+    Powerup,
+}
+
+struct Body {
+    entity: Entity,
+    role: Role,
+    position: Vec2,
+    radius: f32,
+    // Only for rockets while oriented hitboxes are on
+    obb: Option<Obb>,
+}
+
+impl Body {
+    /// Furthest from its centre the body can touch another one
+    fn reach(&self) -> f32 {
+        self.obb
+            .map_or(self.radius, |obb| obb.half_extents.length())
+    }
+}
+
+fn kind(a: Role, b: Role) -> Option<CollisionKind> {
+    match (a, b) {
+        (Role::Ship, Role::Ship) => Some(CollisionKind::ShipShip),
+        (Role::Ship, Role::Sun) => Some(CollisionKind::ShipSun),
+        (Role::Ship, Role::Asteroid) => Some(CollisionKind::ShipAsteroid),
+        (Role::Ship, Role::Powerup) => Some(CollisionKind::ShipPowerup),
+        (Role::Bullet, Role::Ship) => Some(CollisionKind::BulletShip),
+        (Role::Bullet, Role::Sun) => Some(CollisionKind::BulletSun),
+        (Role::Bullet, Role::Asteroid) => Some(CollisionKind::BulletAsteroid),
+        _ => None,
+    }
+}
+
+fn touching(kind: CollisionKind, a: &Body, b: &Body) -> bool {
+    match (kind, a.obb, b.obb) {
+        (_, Some(a), Some(b)) => obb_vs_obb(&a, &b),
+        (_, None, Some(obb)) => obb_vs_circle(&obb, a.position, a.radius),
+        (CollisionKind::ShipAsteroid, Some(obb), None) => {
+            obb_vs_circle(&obb, b.position, b.radius)
+        }
+        // A rocket meets the sun with its circle even with boxes on, the sun's
+        // collider is sized for `kill_distance` which is measured from its centre.
+        // Power-ups are picked up within the circle as well.
+        _ => a.position.distance(b.position) < a.radius + b.radius,
+    }
+}
+
+/// Broadphases every collider through `CollisionGrid` and reports each touching
+/// pair once. Nothing is resolved here, `check_bullet_coll`,
+/// `update_rocket_status` and the asteroid and power-up checks decide what a
+/// collision does.
+#[allow(clippy::type_complexity)]
+pub fn detect_collisions(
+    hitbox_settings: Res<HitboxSettings>,
+    mut grid: ResMut<CollisionGrid>,
+    mut collisions: EventWriter<CollisionEvent>,
+    colliders: Query<(
+        Entity,
+        &Collider,
+        &Transform,
+        Option<&Rocket>,
+        Has<Bullet>,
+        Has<Sun>,
+        Has<Asteroid>,
+        Has<Powerup>,
+    )>,
+) {
+    let bodies: Vec<Body> = colliders
+        .iter()
+        .filter_map(|(entity, collider, transform, rocket, bullet, sun, asteroid, powerup)| {
+            let role = match (rocket, bullet, sun, asteroid, powerup) {
+                (Some(_), ..) => Role::Ship,
+                (_, true, ..) => Role::Bullet,
+                (_, _, true, ..) => Role::Sun,
+                (_, _, _, true, _) => Role::Asteroid,
+                (.., true) => Role::Powerup,
+                _ => return None,
+            };
+            let obb = rocket
+                .filter(|_| hitbox_settings.use_obb)
+                .map(|rocket| Obb::from_transform(transform, &rocket.hitbox));
+            Some(Body {
+                entity,
+                role,
+                position: transform.translation.truncate(),
+                radius: collider.radius,
+                obb,
+            })
+        })
+        .collect();
+
+    // Cells as large as the furthest apart two bodies can be and still touch
+    let reach = bodies.iter().map(Body::reach).fold(1., f32::max);
+    grid.reset(2. * reach);
+    for (index, body) in bodies.iter().enumerate() {
+        grid.insert(index, body.position);
+    }
+
+    // Each pair is reported once and in query order, so a bullet touching two
+    // rockets always reports the same one first
+    for (index, body) in bodies.iter().enumerate() {
+        for other_index in grid.query_nearby(body.position) {
+            if other_index <= index {
+                continue;
+            }
+            let other = &bodies[other_index];
+            let (a, b) = if body.role <= other.role {
+                (body, other)
+            } else {
+                (other, body)
+            };
+            let Some(kind) = kind(a.role, b.role) else {
+                continue;
+            };
+            if touching(kind, a, b) {
+                collisions.send(CollisionEvent {
+                    kind,
+                    a: a.entity,
+                    b: b.entity,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::bullet::test_bullet;
+    use crate::rocket::test_rocket;
+    use crate::sun::test_sun;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<HitboxSettings>();
+        world.init_resource::<CollisionGrid>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world
+    }
+
+    fn at(x: f32, y: f32) -> Transform {
+        Transform::from_xyz(x, y, 0.)
+    }
+
+    fn collisions(world: &mut World) -> Vec<(CollisionKind, Entity, Entity)> {
+        world.run_system_once(detect_collisions);
+        let events = world.resource::<Events<CollisionEvent>>();
+        let mut reader = events.get_reader();
+        reader
+            .read(events)
+            .map(|event| (event.kind, event.a, event.b))
+            .collect()
+    }
+
+    #[test]
+    fn reports_each_touching_pair_once() {
+        let mut world = world();
+        let collider = |radius| Collider { radius };
+        let rocket = world.spawn((at(0., 0.), collider(25.), test_rocket(0))).id();
+        let other = world.spawn((at(45., 0.), collider(25.), test_rocket(1))).id();
+        let far = world.spawn((at(400., 0.), collider(25.), test_rocket(2))).id();
+        let sun = world.spawn((at(-60., 0.), collider(40.), test_sun(40.))).id();
+        // Spawned after the rocket it hits, but still named first in its kind
+        let bullet = world.spawn((at(420., 0.), collider(3.), test_bullet(Vec2::X))).id();
+
+        let mut reported = collisions(&mut world);
+        reported.sort_by_key(|(kind, ..)| *kind as u8);
+        assert_eq!(
+            reported,
+            [
+                (CollisionKind::ShipShip, rocket, other),
+                (CollisionKind::ShipSun, rocket, sun),
+                (CollisionKind::BulletShip, bullet, far),
+            ]
+        );
+    }
+
+    #[test]
+    fn bodies_apart_report_nothing() {
+        let mut world = world();
+        world.spawn((at(0., 0.), Collider { radius: 25. }, test_rocket(0)));
+        world.spawn((at(51., 0.), Collider { radius: 25. }, test_rocket(1)));
+        world.spawn((at(0., 80.), Collider { radius: 3. }, test_bullet(Vec2::X)));
+        assert!(collisions(&mut world).is_empty());
+    }
+}
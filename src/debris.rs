@@ -0,0 +1,158 @@
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use crate::rng::{random_seed, GameRng};
+use crate::sun::{gravity_step, PhysicsConfig, Sun};
+use crate::time_scale::TimeScale;
+
+// Pieces a destroyed rocket breaks into, at least the first and fewer than the second
+const DEBRIS_PIECES: (u64, u64) = (4, 7);
+// Speed the pieces fly apart at, on top of the rocket's own velocity
+const DEBRIS_SPEED: (f32, f32) = (30., 90.);
+// Radians per second a piece tumbles at, either way round
+const DEBRIS_SPIN: f32 = 6.;
+// Seconds a piece takes to fade out
+const DEBRIS_LIFETIME: (f32, f32) = (2.5, 4.);
+// Size of a piece in world units
+const DEBRIS_SIZE: (f32, f32) = (4., 9.);
+// The pieces fall towards the suns harder than a rocket, so the wreckage is
+// seen to burn up
+const DEBRIS_GRAVITY_SCALE: f64 = 4.;
+const DEBRIS_MAX_SPEED: f32 = 400.;
+
+/// A piece of a destroyed rocket tumbling away from where it blew up. Only
+/// for show, it passes through rockets and bullets and burns up in the suns.
+/// The mesh is added separately by `attach_debris_meshes`, like a `TimedEffect`.
+#[derive(Component)]
+pub struct Debris {
+    velocity: Vec2,
+    spin: f32,
+    size: f32,
+    color: Color,
+    lifetime: Timer,
+}
+
+/// Kept apart from `GameRng` so the wreckage doesn't change the match
+#[derive(Resource)]
+pub struct DebrisRng(pub GameRng);
+
+impl Default for DebrisRng {
+    fn default() -> Self {
+        Self(GameRng::new(random_seed()))
+    }
+}
+
+/// Breaks a rocket destroyed at `position` into a handful of pieces in its
+/// colour, each carrying on with the rocket's `velocity` plus a push outwards
+pub fn spawn_debris(
+    commands: &mut Commands,
+    rng: &mut GameRng,
+    position: Vec3,
+    velocity: Vec2,
+    color: Color,
+) {
+    let (min_pieces, max_pieces) = DEBRIS_PIECES;
+    let pieces = min_pieces + rng.next_u64() % (max_pieces - min_pieces);
+    for _ in 0..pieces {
+        let direction = Vec2::from_angle(rng.range(0., TAU));
+        let speed = rng.range(DEBRIS_SPEED.0, DEBRIS_SPEED.1);
+        let size = rng.range(DEBRIS_SIZE.0, DEBRIS_SIZE.1);
+        let translation = position + (direction * size).extend(0.);
+        commands.spawn((
+            SpatialBundle::from_transform(
+                Transform::from_translation(translation)
+                    .with_rotation(Quat::from_rotation_z(rng.range(0., TAU))),
+            ),
+            Debris {
+                velocity: velocity + direction * speed,
+                spin: rng.range(-DEBRIS_SPIN, DEBRIS_SPIN),
+                size,
+                color,
+                lifetime: Timer::from_seconds(
+                    rng.range(DEBRIS_LIFETIME.0, DEBRIS_LIFETIME.1),
+                    TimerMode::Once,
+                ),
+            },
+        ));
+    }
+}
+
+/// Flies and spins the pieces, pulled by the suns, and removes them once they
+/// have faded out or fallen into a sun
+pub fn move_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    config: Res<PhysicsConfig>,
+    suns: Query<(&Sun, &Transform), Without<Debris>>,
+    mut debris: Query<(Entity, &mut Debris, &mut Transform)>,
+) {
+    let sun_positions: Vec<Vec2> = suns
+        .iter()
+        .map(|(_, transform)| transform.translation.truncate())
+        .collect();
+    let debris_config = PhysicsConfig {
+        g_force: config.g_force * DEBRIS_GRAVITY_SCALE,
+        ..*config
+    };
+    let delta = time_scale.delta(&time);
+
+    for (entity, mut piece, mut transform) in debris.iter_mut() {
+        let (position, velocity) = gravity_step(
+            transform.translation.truncate(),
+            piece.velocity,
+            &sun_positions,
+            &debris_config,
+            DEBRIS_MAX_SPEED,
+            delta,
+        );
+        piece.velocity = velocity;
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        transform.rotate_z(piece.spin * delta);
+
+        let burnt = suns.iter().any(|(sun, sun_transform)| {
+            sun_transform.translation.truncate().distance(position) < sun.radius
+        });
+        // Scaled like the motion, so hit-stop and slow motion hold the pieces too
+        let finished = piece.lifetime.tick(Duration::from_secs_f32(delta)).finished();
+        if burnt || finished {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Gives every new piece a small jagged triangle in the rocket's colour
+pub fn attach_debris_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    debris: Query<(Entity, &Debris), Added<Debris>>,
+) {
+    for (entity, piece) in debris.iter() {
+        let size = piece.size;
+        let shard = Triangle2d::new(
+            Vec2::new(0., size),
+            Vec2::new(-0.6 * size, -0.5 * size),
+            Vec2::new(0.8 * size, -0.3 * size),
+        );
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(shard)),
+            materials.add(ColorMaterial::from(piece.color)),
+        ));
+    }
+}
+
+/// Fades each piece out over its lifetime
+pub fn fade_debris(
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    debris: Query<(&Debris, &Handle<ColorMaterial>)>,
+) {
+    for (piece, handle) in debris.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.color = piece.color.with_alpha(1. - piece.lifetime.fraction());
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+
+use crate::rocket::{Rocket, RocketInput};
+
+// Distance in world units from the rocket's centre to its nozzle
+const NOZZLE_OFFSET: f32 = 46.;
+const BOOST_TRAIL_SCALE: f32 = 2.;
+// Share of the full plume a rocket thrusting from a standstill gets
+const MIN_TRAIL_SCALE: f32 = 0.4;
+// Lifetime of the explosion particles, after which the emitter is removed
+const EXPLOSION_LIFETIME: f32 = 5.;
+// Lifetime of the sparks a bullet throws off when it hits
+const IMPACT_LIFETIME: f32 = 0.6;
+// Lifetime of the ring left where a rocket jumps out of or into hyperspace
+const WARP_LIFETIME: f32 = 0.8;
+// Size of the thruster flame at full throttle, in world units
+const FLAME_SIZE: Vec2 = Vec2::new(10., 36.);
+const FLAME_COLOR: Color = Color::srgb(1., 0.7, 0.3);
+// How quickly the flame follows the throttle, per second
+const FLAME_RESPONSE: f32 = 12.;
+// Length of the flame while thrusting from a standstill, relative to full throttle
+const FLAME_IDLE_LEVEL: f32 = 0.3;
+
+// Particle effects the simulation spawns without a renderer to build them, the
+// assets are added under these handles by `PresentationPlugin`
+pub const EXPLOSION_EFFECT: Handle<EffectAsset> =
+    Handle::weak_from_u128(0xd403672687bc4ceea0f493c8093c134f);
+pub const IMPACT_EFFECT: Handle<EffectAsset> =
+    Handle::weak_from_u128(0xef36ab1b10ad4168bf46fbc928ced220);
+pub const WARP_EFFECT: Handle<EffectAsset> =
+    Handle::weak_from_u128(0xc12e45fb6f704912a0c967da7162b3c6);
+
+/// A burst of particles played once where something happened, and removed
+/// when its lifetime is over. The emitter is added separately by
+/// `attach_timed_effects`, so the simulation can run without a renderer.
+#[derive(Component)]
+pub struct TimedEffect {
+    effect: Handle<EffectAsset>,
+    lifetime: Timer,
+    color: Color,
+}
+
+/// Where a rocket or asteroid blew up
+#[derive(Component)]
+pub struct Explosion;
+
+/// Where a bullet hit a rocket that survived it
+#[derive(Component)]
+pub struct Impact;
+
+/// Where a rocket left or came out of hyperspace
+#[derive(Component)]
+pub struct Warp;
+
+/// Packs a color into the `0xAABBGGRR` layout of the `spawn_color` property
+pub fn pack_color(color: Color) -> u32 {
+    let [r, g, b, _] = color.to_srgba().to_u8_array();
+    0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32)
+}
+
+fn spawn_timed_effect(
+    commands: &mut Commands,
+    position: Vec3,
+    color: Color,
+    effect: Handle<EffectAsset>,
+    lifetime: f32,
+    marker: impl Bundle,
+    name: &'static str,
+) {
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(position)),
+        TimedEffect {
+            effect,
+            lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+            color,
+        },
+        marker,
+        Name::new(name),
+    ));
+}
+
+/// Bursts a new explosion at `position`, every death gets its own emitter so
+/// rockets destroyed in the same frame all explode
+pub fn spawn_explosion(commands: &mut Commands, position: Vec3, color: Color) {
+    spawn_timed_effect(
+        commands,
+        position,
+        color,
+        EXPLOSION_EFFECT,
+        EXPLOSION_LIFETIME,
+        Explosion,
+        "effect:meteor_explosion",
+    );
+}
+
+pub fn spawn_impact(commands: &mut Commands, position: Vec3, color: Color) {
+    spawn_timed_effect(
+        commands,
+        position,
+        color,
+        IMPACT_EFFECT,
+        IMPACT_LIFETIME,
+        Impact,
+        "effect:bullet_impact",
+    );
+}
+
+pub fn spawn_warp(commands: &mut Commands, position: Vec3, color: Color) {
+    spawn_timed_effect(
+        commands,
+        position,
+        color,
+        WARP_EFFECT,
+        WARP_LIFETIME,
+        Warp,
+        "effect:hyperspace_warp",
+    );
+}
+
+/// A short burst of fast sparks, far smaller than an explosion
+pub fn add_impact_effect(mut effects: ResMut<Assets<EffectAsset>>) {
+    let writer = ExprWriter::new();
+
+    let init_age = SetAttributeModifier::new(Attribute::AGE, writer.lit(0.).expr());
+    let lifetime = (writer.lit(0.2) + writer.lit(0.3) * writer.rand(ScalarType::Float)).expr();
+    let init_lifetime = SetAttributeModifier::new(Attribute::LIFETIME, lifetime);
+
+    let init_pos = SetPositionCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Z).expr(),
+        radius: writer.lit(2.).expr(),
+        dimension: ShapeDimension::Volume,
+    };
+    let init_vel = SetVelocityCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Z).expr(),
+        speed: (writer.lit(60.) + writer.lit(120.) * writer.rand(ScalarType::Float)).expr(),
+    };
+    let prop = writer.add_property("spawn_color", 0xFFFFFFFFu32.into());
+    let init_color = SetAttributeModifier::new(Attribute::COLOR, writer.prop(prop).expr());
+    let update_drag = LinearDragModifier::new(writer.lit(4.).expr());
+
+    let module = writer.finish();
+    let spawner = Spawner::once(16.0.into(), true);
+
+    effects.insert(
+        &IMPACT_EFFECT,
+        EffectAsset::new(1024, spawner, module)
+            .with_name("bullet_impact")
+            .init(init_pos)
+            .init(init_vel)
+            .init(init_age)
+            .init(init_lifetime)
+            .init(init_color)
+            .update(update_drag)
+            .render(SetSizeModifier {
+                size: Vec3::splat(2.).into(),
+            }),
+    );
+}
+
+/// A ring of particles collapsing onto its centre
+pub fn add_warp_effect(mut effects: ResMut<Assets<EffectAsset>>) {
+    let writer = ExprWriter::new();
+
+    let init_age = SetAttributeModifier::new(Attribute::AGE, writer.lit(0.).expr());
+    let lifetime = (writer.lit(0.4) + writer.lit(0.2) * writer.rand(ScalarType::Float)).expr();
+    let init_lifetime = SetAttributeModifier::new(Attribute::LIFETIME, lifetime);
+
+    let init_pos = SetPositionCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Z).expr(),
+        radius: writer.lit(45.).expr(),
+        dimension: ShapeDimension::Surface,
+    };
+    // Negative so the particles fly inwards, reaching the centre as they die
+    let init_vel = SetVelocityCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Z).expr(),
+        speed: writer.lit(-90.).expr(),
+    };
+    let prop = writer.add_property("spawn_color", 0xFFFFFFFFu32.into());
+    let init_color = SetAttributeModifier::new(Attribute::COLOR, writer.prop(prop).expr());
+
+    let module = writer.finish();
+    let spawner = Spawner::once(48.0.into(), true);
+
+    effects.insert(
+        &WARP_EFFECT,
+        EffectAsset::new(1024, spawner, module)
+            .with_name("hyperspace_warp")
+            .init(init_pos)
+            .init(init_vel)
+            .init(init_age)
+            .init(init_lifetime)
+            .init(init_color)
+            .render(SetSizeModifier {
+                size: Vec3::splat(3.).into(),
+            }),
+    );
+}
+
+/// Gives every new effect its particle emitter, in the colour it was spawned with
+pub fn attach_timed_effects(
+    mut commands: Commands,
+    effects: Query<(Entity, &TimedEffect, &Transform), Added<TimedEffect>>,
+) {
+    for (entity, effect, transform) in effects.iter() {
+        commands.entity(entity).insert((
+            ParticleEffectBundle {
+                effect: ParticleEffect::new(effect.effect.clone()).with_z_layer_2d(Some(0.1)),
+                transform: *transform,
+                ..default()
+            },
+            EffectProperties::default().with_properties([(
+                "spawn_color".to_string(),
+                pack_color(effect.color).into(),
+            )]),
+        ));
+    }
+}
+
+/// Removes explosions, impact sparks and warps once their particles are gone
+pub fn despawn_timed_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effects: Query<(Entity, &mut TimedEffect)>,
+) {
+    for (entity, mut effect) in effects.iter_mut() {
+        if effect.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Exhaust emitter attached as a child of every rocket
+#[derive(Component)]
+pub struct ThrustTrail;
+
+#[derive(Resource)]
+pub struct ThrustTrailEffect(pub Handle<EffectAsset>);
+
+pub fn add_thrust_trail_effect(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
+    let mut gradient = Gradient::new();
+    gradient.add_key(0.0, Vec4::new(1.0, 0.65, 0.1, 1.0));
+    gradient.add_key(1.0, Vec4::new(1.0, 0.2, 0.0, 0.0));
+
+    let writer = ExprWriter::new();
+
+    let age = writer.lit(0.).expr();
+    let init_age = SetAttributeModifier::new(Attribute::AGE, age);
+
+    let lifetime = (writer.lit(0.25) + writer.lit(0.2) * writer.rand(ScalarType::Float)).expr();
+    let init_lifetime = SetAttributeModifier::new(Attribute::LIFETIME, lifetime);
+
+    let init_pos = SetPositionCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Z).expr(),
+        radius: writer.lit(3.).expr(),
+        dimension: ShapeDimension::Volume,
+    };
+
+    // A narrow cone pointing out of the back of the rocket, the emitter's
+    // rotation turns it with the rocket
+    let spread = (writer.rand(VectorType::VEC3F) - writer.lit(Vec3::splat(0.5)))
+        * writer.lit(Vec3::new(70., 30., 0.));
+    // Scaled up while boosting for a longer, faster plume
+    let thrust_scale = writer.add_property("thrust_scale", 1.0f32.into());
+    let velocity =
+        ((spread + writer.lit(Vec3::new(0., -140., 0.))) * writer.prop(thrust_scale)).expr();
+    let init_vel = SetAttributeModifier::new(Attribute::VELOCITY, velocity);
+
+    let module = writer.finish();
+
+    let spawner = Spawner::rate(80.0.into()).with_starts_active(false);
+
+    let effect = effects.add(
+        EffectAsset::new(512, spawner, module)
+            .with_name("thrust_trail")
+            .init(init_pos)
+            .init(init_vel)
+            .init(init_age)
+            .init(init_lifetime)
+            .render(ColorOverLifetimeModifier { gradient })
+            .render(SetSizeModifier {
+                size: Vec3::splat(2.).into(),
+            }),
+    );
+    commands.insert_resource(ThrustTrailEffect(effect));
+}
+
+/// Gives every new rocket, including respawned ones, its exhaust emitter
+pub fn attach_thrust_trails(
+    mut commands: Commands,
+    trail: Res<ThrustTrailEffect>,
+    rockets: Query<(Entity, &Rocket), Added<Rocket>>,
+) {
+    for (entity, rocket) in rockets.iter() {
+        // Undo the sprite scale so the particles keep their size in world units
+        let scale = 1.0 / rocket.base_scale;
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                ParticleEffectBundle {
+                    effect: ParticleEffect::new(trail.0.clone()).with_z_layer_2d(Some(0.1)),
+                    transform: Transform {
+                        translation: Vec3::new(0., -NOZZLE_OFFSET * scale, 0.),
+                        scale: Vec3::splat(scale),
+                        ..default()
+                    },
+                    ..default()
+                },
+                ThrustTrail,
+                Name::new("effect:thrust_trail"),
+            ))
+            .insert(EffectProperties::default());
+        });
+    }
+}
+
+/// Emits exhaust only while the rocket's thrust input is held. The plume
+/// lengthens as the rocket picks up speed, and more so while the afterburner is on.
+pub fn update_thrust_trails(
+    rockets: Query<(&Rocket, &RocketInput, &Children)>,
+    mut trails: Query<(&mut EffectInitializers, &mut EffectProperties), With<ThrustTrail>>,
+) {
+    for (rocket, input, children) in rockets.iter() {
+        for child in children.iter() {
+            if let Ok((mut initializers, mut properties)) = trails.get_mut(*child) {
+                initializers.set_active(input.thrust);
+                let throttle = (rocket.speed / rocket.max_speed).clamp(0., 1.);
+                let boost = if rocket.boosting { BOOST_TRAIL_SCALE } else { 1. };
+                let scale = (MIN_TRAIL_SCALE + (1. - MIN_TRAIL_SCALE) * throttle) * boost;
+                properties.set("thrust_scale", scale.into());
+            }
+        }
+    }
+}
+
+/// Flame sprite behind the nozzle of every rocket, `level` runs from 0 (out)
+/// to 1 (full throttle)
+#[derive(Component, Default)]
+pub struct ThrusterFlame {
+    level: f32,
+}
+
+/// Spawns the flame as a child of a rocket drawn at `rocket_scale`, starting
+/// out unlit. It sits just behind the rocket body so the sprite covers its base.
+pub fn spawn_thruster_flame(parent: &mut ChildBuilder, rocket_scale: f32) {
+    // Undo the rocket's scale so the flame is sized in world units
+    let scale = 1.0 / rocket_scale;
+    parent.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: FLAME_COLOR.with_alpha(0.),
+                custom_size: Some(FLAME_SIZE),
+                // Grows out of the nozzle rather than around it
+                anchor: bevy::sprite::Anchor::TopCenter,
+                ..default()
+            },
+            transform: Transform {
+                translation: Vec3::new(0., -NOZZLE_OFFSET * scale, -0.5),
+                scale: Vec3::new(scale, 0., 1.),
+                ..default()
+            },
+            ..default()
+        },
+        ThrusterFlame::default(),
+        Name::new("sprite:thruster_flame"),
+    ));
+}
+
+/// Lengthens and brightens the flame with the throttle while thrust is held,
+/// and lets it die down while braking or coasting
+pub fn update_thruster_flames(
+    time: Res<Time>,
+    rockets: Query<(&Rocket, &RocketInput, &Children)>,
+    mut flames: Query<(&mut ThrusterFlame, &mut Sprite, &mut Transform)>,
+) {
+    let blend = 1. - (-FLAME_RESPONSE * time.delta_seconds()).exp();
+    for (rocket, input, children) in rockets.iter() {
+        let target = if input.thrust {
+            let throttle = (rocket.speed / rocket.max_speed).clamp(0., 1.);
+            FLAME_IDLE_LEVEL + (1. - FLAME_IDLE_LEVEL) * throttle
+        } else {
+            0.
+        };
+        for child in children.iter() {
+            if let Ok((mut flame, mut sprite, mut transform)) = flames.get_mut(*child) {
+                flame.level += (target - flame.level) * blend;
+                transform.scale.y = transform.scale.x * flame.level;
+                sprite.color.set_alpha(flame.level);
+            }
+        }
+    }
+}
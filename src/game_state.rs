@@ -0,0 +1,293 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::asteroid::Asteroid;
+use crate::bullet::Bullet;
+use crate::powerup::Powerup;
+use crate::respawn::{Lives, RespawnQueue};
+use crate::rocket::Rocket;
+use crate::score::MatchState;
+use crate::seed::SeedInput;
+use crate::settings::GameSettings;
+use crate::sun::{PhysicsConfig, Sun, SUN_RADIUS};
+
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameState {
+    #[default]
+    Menu,
+    Playing,
+    /// Sudden death after a tied match, the sun grows until someone is left
+    Overtime,
+    GameOver,
+}
+
+#[derive(Resource, Clone)]
+pub struct MatchTimer(pub Timer);
+
+impl Default for MatchTimer {
+    fn default() -> Self {
+        Self(Timer::new(Duration::from_secs(180), TimerMode::Once))
+    }
+}
+
+#[derive(Resource)]
+pub struct OvertimeSettings {
+    // Radius the sun gains per second
+    pub sun_growth: f32,
+    pub max_sun_radius: f32,
+    // Fraction of the current gravity gained per second
+    pub gravity_growth: f32,
+}
+
+impl Default for OvertimeSettings {
+    fn default() -> Self {
+        Self {
+            sun_growth: 6.,
+            max_sun_radius: 4. * SUN_RADIUS,
+            gravity_growth: 0.15,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Banner;
+
+// Distance of the banners from the top of the window, the pause banner sits
+// lower so it doesn't cover the overtime one
+const BANNER_TOP: f32 = 80.;
+const PAUSE_BANNER_TOP: f32 = 320.;
+
+/// Whether a running match is paused. Pausing also stops virtual time, so
+/// nothing integrates the paused frames once the game resumes.
+#[derive(Resource, Default)]
+pub struct Paused(pub bool);
+
+#[derive(Component)]
+pub struct PauseBanner;
+
+pub fn not_paused(paused: Res<Paused>) -> bool {
+    !paused.0
+}
+
+/// Escape pauses and resumes a match, unless it is closing the seed input
+pub fn toggle_pause(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    seed_input: Res<SeedInput>,
+    mut paused: ResMut<Paused>,
+    mut time: ResMut<Time<Virtual>>,
+    banners: Query<Entity, With<PauseBanner>>,
+) {
+    if !keys.just_pressed(KeyCode::Escape) || seed_input.active {
+        return;
+    }
+    if paused.0 {
+        resume(&mut commands, &mut paused, &mut time, &banners);
+    } else {
+        paused.0 = true;
+        time.pause();
+        let banner = spawn_banner(&mut commands, "PAUSED".into(), PAUSE_BANNER_TOP);
+        commands.entity(banner).insert(PauseBanner);
+    }
+}
+
+fn resume(
+    commands: &mut Commands,
+    paused: &mut Paused,
+    time: &mut Time<Virtual>,
+    banners: &Query<Entity, With<PauseBanner>>,
+) {
+    paused.0 = false;
+    time.unpause();
+    for entity in banners.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Run condition for the gameplay systems, true from the start of a match
+/// until it is decided
+pub fn in_match(state: Res<State<GameState>>) -> bool {
+    matches!(state.get(), GameState::Playing | GameState::Overtime)
+}
+
+pub fn show_menu_banner(mut commands: Commands) {
+    spawn_banner(&mut commands, "PRESS ENTER TO START".into(), BANNER_TOP);
+}
+
+/// Enter starts a match from the menu, and goes back to the menu once it is over
+pub fn advance_on_enter(
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keys.just_pressed(KeyCode::Enter) {
+        return;
+    }
+    match state.get() {
+        GameState::Menu => next_state.set(GameState::Playing),
+        GameState::GameOver => next_state.set(GameState::Menu),
+        GameState::Playing | GameState::Overtime => {}
+    }
+}
+
+/// Puts back everything a previous match may have changed
+#[allow(clippy::too_many_arguments)]
+pub fn reset_match(
+    mut commands: Commands,
+    mut paused: ResMut<Paused>,
+    mut time: ResMut<Time<Virtual>>,
+    pause_banners: Query<Entity, With<PauseBanner>>,
+    mut timer: ResMut<MatchTimer>,
+    mut match_state: ResMut<MatchState>,
+    mut lives: ResMut<Lives>,
+    mut queue: ResMut<RespawnQueue>,
+    mut config: ResMut<PhysicsConfig>,
+    settings: Res<GameSettings>,
+) {
+    // A match restarted with a new seed from the pause screen starts running
+    if paused.0 {
+        resume(&mut commands, &mut paused, &mut time, &pause_banners);
+    }
+    timer.0.reset();
+    // The kill target comes from the command line and holds for every match
+    let kills_to_win = match_state.kills_to_win;
+    *match_state = MatchState::default();
+    match_state.kills_to_win = kills_to_win;
+    *lives = Lives::default();
+    queue.clear();
+    // Overtime grows the sun and its pull
+    *config = PhysicsConfig {
+        g_force: settings.gravity,
+        ..default()
+    };
+}
+
+/// Removes everything the last match spawned so the next one starts fresh
+#[allow(clippy::type_complexity)]
+pub fn cleanup_match(
+    mut commands: Commands,
+    entities: Query<
+        Entity,
+        Or<(
+            With<Rocket>,
+            With<Bullet>,
+            With<Sun>,
+            With<Powerup>,
+            With<Asteroid>,
+        )>,
+    >,
+) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Ends the match when the time runs out. With a single rocket left it wins
+/// outright, otherwise the rocket ahead on the scores does and only a real tie
+/// goes to sudden death.
+pub fn tick_match_timer(
+    time: Res<Time>,
+    mut timer: ResMut<MatchTimer>,
+    mut match_state: ResMut<MatchState>,
+    lives: Res<Lives>,
+    rockets: Query<&Rocket>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // The clock stops between rounds
+    if match_state.between_rounds() || !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let alive: Vec<usize> = rockets.iter().map(|rocket| rocket.player).collect();
+    if alive.len() < 2 {
+        next_state.set(GameState::GameOver);
+        return;
+    }
+    match match_state.leader(&alive, &lives) {
+        Some(leader) => {
+            match_state.time_winner = Some(leader);
+            next_state.set(GameState::GameOver);
+        }
+        None => next_state.set(GameState::Overtime),
+    }
+}
+
+pub fn escalate_overtime(
+    time: Res<Time>,
+    settings: Res<OvertimeSettings>,
+    mut config: ResMut<PhysicsConfig>,
+) {
+    config.g_force *= 1.0 + (settings.gravity_growth * time.delta_seconds()) as f64;
+    config.sun_radius =
+        (config.sun_radius + settings.sun_growth * time.delta_seconds()).min(settings.max_sun_radius);
+}
+
+/// Respawns are off during overtime, so the match ends once one rocket is left
+pub fn check_overtime_winner(
+    rockets: Query<&Rocket>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if rockets.iter().count() <= 1 {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+pub fn show_overtime_banner(mut commands: Commands) {
+    spawn_banner(&mut commands, "SUDDEN DEATH".into(), BANNER_TOP);
+}
+
+pub fn show_game_over_banner(
+    mut commands: Commands,
+    match_state: Res<MatchState>,
+    rockets: Query<&Rocket>,
+) {
+    let mut alive = rockets.iter();
+    // A match cut short by the timer goes to the last rocket flying
+    let winner = match_state.match_winner().or(match (alive.next(), alive.next()) {
+        (Some(winner), None) => Some(winner.player),
+        _ => None,
+    });
+    let text = match winner {
+        Some(winner) => format!("PLAYER {} WINS", winner + 1),
+        None => "DRAW".into(),
+    };
+    info!("Match over: {text}");
+    spawn_banner(&mut commands, format!("{text}\nPRESS ENTER"), BANNER_TOP);
+}
+
+pub fn clear_banner(mut commands: Commands, banners: Query<Entity, With<Banner>>) {
+    for entity in banners.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_banner(commands: &mut Commands, text: String, top: f32) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(top),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            Banner,
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    text,
+                    TextStyle {
+                        font_size: 48.,
+                        color: Color::srgb(1., 0.6, 0.1),
+                        ..default()
+                    },
+                )
+                .with_text_justify(JustifyText::Center),
+            );
+        })
+        .id()
+}
@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use bevy::{
+    input::gamepad::{GamepadConnectionEvent, GamepadRumbleIntensity, GamepadRumbleRequest},
+    prelude::*,
+};
+
+use crate::rocket::{Rocket, RocketDamaged, RocketInput, MAX_PLAYERS};
+
+// Sticks and triggers count as pushed past this point, the rest of their
+// travel is scaled to the full range
+const DEADZONE: f32 = 0.15;
+const HIT_RUMBLE: Rumble = Rumble {
+    strength: 0.3,
+    seconds: 0.12,
+};
+const DESTROYED_RUMBLE: Rumble = Rumble {
+    strength: 1.,
+    seconds: 0.45,
+};
+
+/// Which buttons and axes drive a rocket when it is bound to a gamepad
+#[derive(Clone, Copy)]
+pub struct PadBindings {
+    pub thrust_button: GamepadButtonType,
+    pub fire_button: GamepadButtonType,
+    pub reload_button: GamepadButtonType,
+    pub cycle_weapon_button: GamepadButtonType,
+    pub boost_button: GamepadButtonType,
+    pub brake_button: GamepadButtonType,
+    pub hyperspace_button: GamepadButtonType,
+    pub shield_button: GamepadButtonType,
+    pub thrust_axis: GamepadAxisType,
+    pub rotate_axis: GamepadAxisType,
+}
+
+impl Default for PadBindings {
+    fn default() -> Self {
+        Self {
+            thrust_button: GamepadButtonType::RightTrigger2,
+            fire_button: GamepadButtonType::South,
+            reload_button: GamepadButtonType::West,
+            cycle_weapon_button: GamepadButtonType::North,
+            boost_button: GamepadButtonType::RightTrigger,
+            brake_button: GamepadButtonType::LeftTrigger2,
+            hyperspace_button: GamepadButtonType::East,
+            shield_button: GamepadButtonType::LeftTrigger,
+            thrust_axis: GamepadAxisType::LeftStickY,
+            rotate_axis: GamepadAxisType::LeftStickX,
+        }
+    }
+}
+
+/// Gamepad bound to each player, kept outside the rocket so it survives respawns
+#[derive(Resource, Default)]
+pub struct GamepadAssignments(pub [Option<Gamepad>; MAX_PLAYERS]);
+
+/// Hands newly connected pads to the first player without one, including
+/// pads plugged in mid-match, and frees the slot again on disconnect
+pub fn assign_gamepads(
+    mut events: EventReader<GamepadConnectionEvent>,
+    mut assignments: ResMut<GamepadAssignments>,
+    mut rockets: Query<&mut Rocket>,
+) {
+    for event in events.read() {
+        if event.connected() {
+            if assignments.0.contains(&Some(event.gamepad)) {
+                continue;
+            }
+            if let Some(slot) = assignments.0.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(event.gamepad);
+            }
+        } else {
+            for slot in assignments.0.iter_mut() {
+                if *slot == Some(event.gamepad) {
+                    *slot = None;
+                }
+            }
+        }
+    }
+
+    // Also catches rockets that were just respawned with an unbound controller
+    for mut rocket in rockets.iter_mut() {
+        let gamepad = assignments.0[rocket.player];
+        if rocket.controls.gamepad != gamepad {
+            rocket.controls.gamepad = gamepad;
+        }
+    }
+}
+
+/// How far past the deadzone `value` is, from 0 to 1 either way round
+fn past_deadzone(value: f32) -> f32 {
+    let pushed = ((value.abs() - DEADZONE) / (1. - DEADZONE)).clamp(0., 1.);
+    pushed.copysign(value)
+}
+
+/// Reads a rocket's input from its pad, the thrust trigger and stick and the
+/// rotation stick also set how hard it thrusts and turns through `analog`
+pub fn gamepad_input(
+    gamepad: Gamepad,
+    bindings: &PadBindings,
+    buttons: &ButtonInput<GamepadButton>,
+    button_axes: &Axis<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+) -> RocketInput {
+    let axis = |axis_type| axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.0);
+    let button = |button_type| GamepadButton::new(gamepad, button_type);
+
+    // Digital triggers only report being pressed
+    let trigger = button_axes
+        .get(button(bindings.thrust_button))
+        .unwrap_or(if buttons.pressed(button(bindings.thrust_button)) { 1. } else { 0. });
+    let throttle = past_deadzone(trigger).max(past_deadzone(axis(bindings.thrust_axis)));
+    let rotation = past_deadzone(axis(bindings.rotate_axis));
+    RocketInput {
+        thrust: throttle > 0.,
+        rotate_left: rotation < 0.,
+        rotate_right: rotation > 0.,
+        fire: buttons.just_pressed(button(bindings.fire_button)),
+        reload: buttons.pressed(button(bindings.reload_button)),
+        cycle_weapon: buttons.just_pressed(button(bindings.cycle_weapon_button)),
+        boost: buttons.pressed(button(bindings.boost_button)),
+        brake: buttons.pressed(button(bindings.brake_button)),
+        hyperspace: buttons.just_pressed(button(bindings.hyperspace_button)),
+        shield: buttons.pressed(button(bindings.shield_button)),
+        aim: None,
+        analog: Some(Vec2::new(rotation, throttle)),
+    }
+}
+
+/// Force feedback for one kind of hit, `strength` goes from 0 to 1
+#[derive(Clone, Copy)]
+struct Rumble {
+    strength: f32,
+    seconds: f32,
+}
+
+/// Rumble each player's pad is playing and the time, in real seconds, it ends
+#[derive(Resource, Default)]
+pub struct PadRumbles([Option<(Rumble, f32)>; MAX_PLAYERS]);
+
+/// Shakes the pad of a player whose rocket got hit, harder when it was destroyed.
+/// A new rumble replaces the one playing instead of adding to it, so rapid hits
+/// never build up to a constant vibration.
+pub fn rumble_on_damage(
+    time: Res<Time<Real>>,
+    mut events: EventReader<RocketDamaged>,
+    assignments: Res<GamepadAssignments>,
+    mut rumbles: ResMut<PadRumbles>,
+    mut requests: EventWriter<GamepadRumbleRequest>,
+) {
+    let now = time.elapsed_seconds();
+    for event in events.read() {
+        // Players on the keyboard have nothing to shake
+        let Some(gamepad) = assignments.0[event.player] else {
+            continue;
+        };
+        let rumble = if event.destroyed { DESTROYED_RUMBLE } else { HIT_RUMBLE };
+        // A graze doesn't cut the rumble of a bigger hit short
+        if let Some((playing, ends_at)) = rumbles.0[event.player] {
+            if ends_at > now && playing.strength > rumble.strength {
+                continue;
+            }
+        }
+        requests.send(GamepadRumbleRequest::Stop { gamepad });
+        requests.send(GamepadRumbleRequest::Add {
+            gamepad,
+            intensity: GamepadRumbleIntensity::strong_motor(rumble.strength),
+            duration: Duration::from_secs_f32(rumble.seconds),
+        });
+        rumbles.0[event.player] = Some((rumble, now + rumble.seconds));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sticks_are_scaled_past_the_deadzone() {
+        assert_eq!(past_deadzone(0.), 0.);
+        assert_eq!(past_deadzone(DEADZONE * 0.5), 0.);
+        assert_eq!(past_deadzone(1.), 1.);
+        assert_eq!(past_deadzone(-1.), -1.);
+        let halfway = DEADZONE + (1. - DEADZONE) * 0.5;
+        assert!((past_deadzone(-halfway) + 0.5).abs() < 1e-6);
+    }
+}
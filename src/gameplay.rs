@@ -0,0 +1,556 @@
+use bevy::prelude::*;
+
+use crate::asteroid::{
+    add_asteroids, check_asteroid_bullet_coll, move_asteroids, AsteroidSettings,
+};
+use crate::bindings::KeyBindings;
+use crate::bullet::{
+    check_bullet_coll, expire_bullets, handle_bullet_movement, spawn_bullet, steer_missiles,
+    BulletImpact,
+};
+use crate::collision::{detect_collisions, CollisionEvent};
+use crate::debris::{move_debris, DebrisRng};
+use crate::effects::despawn_timed_effects;
+use crate::game_state::{
+    check_overtime_winner, cleanup_match, escalate_overtime, in_match, not_paused, reset_match,
+    tick_match_timer, GameState, MatchTimer, OvertimeSettings, Paused,
+};
+use crate::grid::CollisionGrid;
+use crate::hitbox::HitboxSettings;
+use crate::hyperspace::{hyperspace_jump, tick_hyperspace_cooldowns, HyperspaceSettings};
+use crate::net::runs_simulation;
+use crate::powerup::{
+    collect_powerups, move_orbiting_powerups, spawn_powerups, tick_powerup_effects,
+    PowerupSpawner,
+};
+use crate::respawn::{
+    process_respawns, queue_respawns, tick_spawn_protection, Lives, RespawnQueue, RespawnSettings,
+};
+use crate::rng::{GameRng, NextSeed};
+use crate::rollback::{save_frame, Rollback};
+use crate::rocket::{
+    add_rockets, clear_presses, clip_to_arena, destroy_rockets, fit_arena_to_window, Arena,
+    BoundaryMode, CollisionMode, PlayerCount, RocketDamaged, RocketDestroyed,
+};
+use crate::score::{
+    check_round_over, count_kills, log_round_over, start_next_round, MatchState, RoundOver,
+};
+use crate::settings::{apply_max_speed_setting, save_game_settings, GameSettings};
+use crate::shield::update_shields;
+use crate::slingshot::{log_slingshots, track_slingshots, Slingshot, SlingshotSettings};
+use crate::sun::{
+    add_sun, apply_physics_config, fit_sun_colliders, tidal_stretch, PhysicsConfig, SunHeat,
+    SunLayout, TidalStretch,
+};
+use crate::time_scale::{
+    hit_stop_on_destruction, slow_motion_on_round_over, HitStop, SlowMotion, TimeScale,
+};
+use crate::{
+    check_asteroid_coll, gravitational_pull, move_rockets, update_rocket_status, PHYSICS_HZ,
+};
+
+/// The match itself: spawning, flight, collisions, respawns and scoring. It
+/// needs no window, renderer or assets, so it also runs headless on top of
+/// `MinimalPlugins` and `StatesPlugin`, with `RocketInput` filled in by hand.
+/// Key bindings and game settings inserted before the plugin are kept, so a
+/// headless app doesn't read or write the files next to the game.
+pub struct GameplayPlugin;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<KeyBindings>() {
+            app.insert_resource(KeyBindings::load());
+        }
+        if !app.world().contains_resource::<GameSettings>() {
+            app.insert_resource(GameSettings::load());
+        }
+        app.init_state::<GameState>()
+            .insert_resource(Time::<Fixed>::from_hz(PHYSICS_HZ))
+            .insert_resource(PlayerCount::from_args())
+            .init_resource::<HitboxSettings>()
+            .init_resource::<GameRng>()
+            .init_resource::<NextSeed>()
+            .init_resource::<RespawnSettings>()
+            .init_resource::<RespawnQueue>()
+            .init_resource::<Lives>()
+            .init_resource::<TidalStretch>()
+            .init_resource::<PhysicsConfig>()
+            .init_resource::<SunLayout>()
+            .init_resource::<SunHeat>()
+            .init_resource::<MatchTimer>()
+            .init_resource::<OvertimeSettings>()
+            .insert_resource(MatchState::from_args())
+            .init_resource::<BoundaryMode>()
+            .init_resource::<CollisionMode>()
+            .init_resource::<Paused>()
+            .init_resource::<CollisionGrid>()
+            .init_resource::<BulletImpact>()
+            .init_resource::<PowerupSpawner>()
+            .init_resource::<AsteroidSettings>()
+            .init_resource::<TimeScale>()
+            .init_resource::<SlingshotSettings>()
+            .init_resource::<SlowMotion>()
+            .init_resource::<HitStop>()
+            .init_resource::<HyperspaceSettings>()
+            .init_resource::<DebrisRng>()
+            .init_resource::<Arena>()
+            .add_event::<RoundOver>()
+            .add_event::<RocketDamaged>()
+            .add_event::<RocketDestroyed>()
+            .add_event::<Slingshot>()
+            .add_event::<CollisionEvent>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                // Cleaned up once more for a match restarted with a new seed,
+                // a match ended the usual way was cleared when leaving GameOver
+                (cleanup_match, reset_match, add_sun, add_rockets, add_asteroids).chain(),
+            )
+            .add_systems(OnExit(GameState::GameOver), cleanup_match)
+            .add_systems(
+                Update,
+                (
+                    apply_physics_config,
+                    fit_arena_to_window,
+                    despawn_timed_effects,
+                    move_debris.run_if(not_paused),
+                    log_slingshots,
+                    save_game_settings,
+                ),
+            )
+            // Everything that moves or decides the match runs on the fixed timestep,
+            // so a replayed match follows the recorded one exactly
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_spawn_protection,
+                    check_bullet_coll,
+                    check_asteroid_bullet_coll,
+                    spawn_bullet,
+                    update_rocket_status,
+                    move_rockets,
+                    check_asteroid_coll,
+                    destroy_rockets,
+                    count_kills,
+                    check_round_over,
+                    log_round_over,
+                    // Ticked on the fixed clock, so kills slow down the same
+                    // steps in a replay
+                    (slow_motion_on_round_over, hit_stop_on_destruction).chain(),
+                    move_asteroids,
+                    clip_to_arena,
+                    gravitational_pull,
+                    steer_missiles,
+                    handle_bullet_movement,
+                    expire_bullets,
+                    tidal_stretch,
+                    clear_presses,
+                )
+                    .chain()
+                    .run_if(in_match)
+                    .run_if(not_paused)
+                    .run_if(runs_simulation),
+            )
+            .add_systems(
+                FixedUpdate,
+                (tick_hyperspace_cooldowns, hyperspace_jump)
+                    .chain()
+                    .run_if(in_match)
+                    .run_if(not_paused)
+                    .run_if(runs_simulation)
+                    .after(tick_spawn_protection)
+                    .before(check_bullet_coll),
+            )
+            // After the jumps so rockets are hit where they landed
+            .add_systems(
+                FixedUpdate,
+                (fit_sun_colliders, detect_collisions)
+                    .chain()
+                    .run_if(in_match)
+                    .run_if(not_paused)
+                    .run_if(runs_simulation)
+                    .after(hyperspace_jump)
+                    .before(check_bullet_coll),
+            )
+            .add_systems(
+                FixedUpdate,
+                update_shields
+                    .run_if(in_match)
+                    .run_if(not_paused)
+                    .run_if(runs_simulation)
+                    .after(tick_spawn_protection)
+                    .before(check_bullet_coll),
+            )
+            .add_systems(
+                FixedUpdate,
+                save_frame
+                    .run_if(resource_exists::<Rollback>)
+                    .run_if(in_match)
+                    .run_if(not_paused)
+                    .before(tick_spawn_protection),
+            )
+            // Before a rollback saves the step, so it saves the scaled top speed
+            .add_systems(
+                FixedUpdate,
+                apply_max_speed_setting.before(save_frame).before(move_rockets),
+            )
+            .add_systems(
+                FixedUpdate,
+                track_slingshots
+                    .run_if(in_match)
+                    .run_if(not_paused)
+                    .run_if(runs_simulation)
+                    .after(gravitational_pull),
+            )
+            .add_systems(
+                FixedUpdate,
+                (queue_respawns, process_respawns, start_next_round)
+                    .chain()
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(runs_simulation)
+                    .after(destroy_rockets),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_match_timer.run_if(in_state(GameState::Playing)),
+                    (escalate_overtime, check_overtime_winner)
+                        .run_if(in_state(GameState::Overtime)),
+                )
+                    .run_if(runs_simulation)
+                    .after(destroy_rockets),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_powerup_effects,
+                    move_orbiting_powerups,
+                    spawn_powerups,
+                    collect_powerups,
+                )
+                    .chain()
+                    .run_if(in_match)
+                    .run_if(not_paused)
+                    .run_if(runs_simulation)
+                    .after(update_rocket_status),
+            );
+    }
+}
+
+/// A headless match for tests, every `update` runs exactly one fixed step and
+/// nothing is read from or written to the files next to the game
+#[cfg(test)]
+pub fn headless_app() -> App {
+    use bevy::state::app::StatesPlugin;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1. / PHYSICS_HZ,
+        )))
+        .insert_resource(KeyBindings::default())
+        .insert_resource(GameSettings::default())
+        .add_plugins(GameplayPlugin);
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::ManualEventReader;
+
+    use crate::bullet::test_bullet;
+    use crate::collision::Collider;
+    use crate::effects::Explosion;
+    use crate::game_state::PauseBanner;
+    use crate::rocket::{DestroyCause, Rocket, RocketInput, ScreenWrap};
+
+    /// A headless app with a match of `players` just started, seeded and
+    /// without the asteroids and power-ups that would get in the way
+    fn start_match(players: usize) -> App {
+        let mut app = headless_app();
+        app.insert_resource(AsteroidSettings {
+            count: 0,
+            ..default()
+        })
+        .insert_resource(PowerupSpawner {
+            kinds: Vec::new(),
+            ..default()
+        })
+        .insert_resource(GameRng::new(3))
+        .insert_resource(PlayerCount(players));
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        app.update();
+        app
+    }
+
+    fn players(app: &mut App) -> Vec<usize> {
+        let world = app.world_mut();
+        let mut players: Vec<usize> = world
+            .query::<&Rocket>()
+            .iter(world)
+            .map(|rocket| rocket.player)
+            .collect();
+        players.sort();
+        players
+    }
+
+    fn place(app: &mut App, player: usize, position: Vec2) {
+        let world = app.world_mut();
+        for (rocket, mut transform) in world.query::<(&Rocket, &mut Transform)>().iter_mut(world) {
+            if rocket.player == player {
+                transform.translation = position.extend(transform.translation.z);
+            }
+        }
+    }
+
+    fn destroyed(
+        app: &App,
+        reader: &mut ManualEventReader<RocketDestroyed>,
+    ) -> Vec<(usize, DestroyCause)> {
+        let events = app.world().resource::<Events<RocketDestroyed>>();
+        reader.read(events).map(|event| (event.player, event.cause)).collect()
+    }
+
+    #[test]
+    fn rockets_start_in_orbit() {
+        let mut app = start_match(2);
+        let mut reader = app.world().resource::<Events<RocketDestroyed>>().get_reader();
+        for _ in 0..10 * 60 {
+            app.update();
+        }
+        assert!(destroyed(&app, &mut reader).is_empty());
+        assert_eq!(players(&mut app), [0, 1]);
+    }
+
+    #[test]
+    fn idle_rockets_fall_into_the_sun() {
+        let mut app = start_match(2);
+        // Knocked out of their orbits
+        let world = app.world_mut();
+        for mut rocket in world.query::<&mut Rocket>().iter_mut(world) {
+            rocket.velocity = Vec2::ZERO;
+        }
+        let mut reader = app.world().resource::<Events<RocketDestroyed>>().get_reader();
+        let mut burnt = Vec::new();
+        // Twenty seconds of fixed steps
+        for _ in 0..20 * 60 {
+            app.update();
+            burnt.extend(destroyed(&app, &mut reader));
+            if !burnt.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(burnt, [(0, DestroyCause::Sun), (1, DestroyCause::Sun)]);
+        assert!(players(&mut app).is_empty());
+    }
+
+    /// What every rocket flies on a fixed step of the scripted matches
+    fn scripted_input(step: u32, player: usize) -> RocketInput {
+        let phase = (step / 40 + player as u32) % 3;
+        RocketInput {
+            thrust: phase != 0,
+            rotate_left: phase == 1,
+            rotate_right: phase == 2,
+            fire: step.is_multiple_of(20),
+            ..default()
+        }
+    }
+
+    /// Every rocket's position and velocity after the same inputs from the
+    /// same seed, asteroids and power-ups included
+    fn scripted_match() -> Vec<(usize, Vec3, Vec2)> {
+        let mut app = headless_app();
+        app.insert_resource(GameRng::new(11));
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        app.update();
+        for step in 0..600_u32 {
+            let world = app.world_mut();
+            for (rocket, mut input) in world.query::<(&Rocket, &mut RocketInput)>().iter_mut(world) {
+                *input = scripted_input(step, rocket.player);
+            }
+            app.update();
+        }
+        let world = app.world_mut();
+        let mut rockets: Vec<_> = world
+            .query::<(&Rocket, &Transform)>()
+            .iter(world)
+            .map(|(rocket, transform)| (rocket.player, transform.translation, rocket.velocity))
+            .collect();
+        rockets.sort_by_key(|(player, ..)| *player);
+        rockets
+    }
+
+    #[test]
+    fn the_same_inputs_replay_the_same_match() {
+        let first = scripted_match();
+        assert!(!first.is_empty());
+        assert_eq!(scripted_match(), first);
+    }
+
+    /// Fixed steps run so far and the rockets as they were after `SCRIPT_STEPS`
+    #[derive(Resource, Default)]
+    struct Script {
+        step: u32,
+        rockets: Option<Vec<(usize, Vec3, Vec2)>>,
+    }
+
+    const SCRIPT_STEPS: u32 = 600;
+
+    /// Flies the scripted inputs by fixed step rather than by frame, however
+    /// many steps a frame runs
+    fn fly_script(
+        mut script: ResMut<Script>,
+        mut rockets: Query<(&Rocket, &Transform, &mut RocketInput)>,
+    ) {
+        if script.step == SCRIPT_STEPS {
+            let mut taken: Vec<_> = rockets
+                .iter()
+                .map(|(rocket, transform, _)| {
+                    (rocket.player, transform.translation, rocket.velocity)
+                })
+                .collect();
+            taken.sort_by_key(|(player, ..)| *player);
+            script.rockets = Some(taken);
+        }
+        for (rocket, _, mut input) in rockets.iter_mut() {
+            *input = scripted_input(script.step, rocket.player);
+        }
+        script.step += 1;
+    }
+
+    /// The scripted match with frames of `frame` seconds
+    fn match_at_frame_rate(frame: f64) -> Vec<(usize, Vec3, Vec2)> {
+        use bevy::time::TimeUpdateStrategy;
+        use std::time::Duration;
+
+        let mut app = headless_app();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(frame)))
+            .insert_resource(GameRng::new(11))
+            .init_resource::<Script>()
+            .add_systems(
+                FixedUpdate,
+                fly_script.run_if(in_match).before(tick_spawn_protection),
+            );
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        while app.world().resource::<Script>().rockets.is_none() {
+            app.update();
+        }
+        app.world_mut().resource_mut::<Script>().rockets.take().unwrap()
+    }
+
+    #[test]
+    fn the_frame_rate_does_not_change_the_match() {
+        let slow = match_at_frame_rate(1. / 30.);
+        assert!(!slow.is_empty());
+        assert_eq!(match_at_frame_rate(1. / 144.), slow);
+    }
+
+    #[test]
+    fn only_the_rockets_that_collide_are_destroyed() {
+        let mut app = start_match(3);
+        place(&mut app, 0, Vec2::new(300., 250.));
+        place(&mut app, 1, Vec2::new(320., 250.));
+        place(&mut app, 2, Vec2::new(-300., -250.));
+        let mut reader = app.world().resource::<Events<RocketDestroyed>>().get_reader();
+        app.update();
+        let burnt = destroyed(&app, &mut reader);
+        assert_eq!(burnt, [(0, DestroyCause::Collision), (1, DestroyCause::Collision)]);
+        assert_eq!(players(&mut app), [2]);
+    }
+
+    #[test]
+    fn rockets_destroyed_in_the_same_step_each_explode() {
+        let mut app = start_match(3);
+        place(&mut app, 0, Vec2::new(300., 250.));
+        place(&mut app, 1, Vec2::new(320., 250.));
+        place(&mut app, 2, Vec2::new(-300., -250.));
+        app.update();
+        let world = app.world_mut();
+        let mut explosions: Vec<Vec2> = world
+            .query_filtered::<&Transform, With<Explosion>>()
+            .iter(world)
+            .map(|transform| transform.translation.truncate())
+            .collect();
+        explosions.sort_by(|a, b| a.x.total_cmp(&b.x));
+        assert_eq!(explosions.len(), 2);
+        // Each where its own rocket was, not both at one of them
+        assert!(explosions[0].distance(Vec2::new(300., 250.)) < 10.);
+        assert!(explosions[1].distance(Vec2::new(320., 250.)) < 10.);
+    }
+
+    #[test]
+    fn restarting_while_paused_resumes_the_match() {
+        let mut app = start_match(2);
+        // As Escape leaves it
+        app.world_mut().resource_mut::<Paused>().0 = true;
+        app.world_mut().resource_mut::<Time<Virtual>>().pause();
+        let banner = app.world_mut().spawn(PauseBanner).id();
+
+        // As entering a seed restarts it
+        app.world_mut().run_schedule(OnEnter(GameState::Playing));
+        assert!(!app.world().resource::<Paused>().0);
+        assert!(!app.world().resource::<Time<Virtual>>().is_paused());
+        app.update();
+        assert!(app.world().get_entity(banner).is_none());
+
+        let positions = |app: &mut App| {
+            let world = app.world_mut();
+            let mut positions: Vec<Vec3> = world
+                .query_filtered::<&Transform, With<Rocket>>()
+                .iter(world)
+                .map(|transform| transform.translation)
+                .collect();
+            positions.sort_by(|a, b| a.x.total_cmp(&b.x));
+            positions
+        };
+        let before = positions(&mut app);
+        app.update();
+        assert_eq!(before.len(), 2);
+        assert_ne!(positions(&mut app), before);
+    }
+
+    fn spawn_bullet_at(app: &mut App, position: Vec2, velocity: Vec2) -> Entity {
+        app.world_mut()
+            .spawn((
+                SpriteBundle {
+                    transform: Transform::from_translation(position.extend(0.)),
+                    ..default()
+                },
+                test_bullet(velocity),
+                Collider { radius: 3. },
+                ScreenWrap,
+            ))
+            .id()
+    }
+
+    #[test]
+    fn bullets_past_the_edge_are_removed() {
+        for mode in [BoundaryMode::Clamp, BoundaryMode::Death] {
+            let mut app = start_match(2);
+            app.insert_resource(mode);
+            let edge = app.world().resource::<Arena>().w_clip;
+            let bullet = spawn_bullet_at(&mut app, Vec2::new(edge - 1., 0.), Vec2::X * 300.);
+            app.update();
+            assert!(app.world().get_entity(bullet).is_none(), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn wrapping_bullets_carry_on_from_the_other_side() {
+        let mut app = start_match(2);
+        let edge = app.world().resource::<Arena>().w_clip;
+        let bullet = spawn_bullet_at(&mut app, Vec2::new(edge - 1., 0.), Vec2::X * 300.);
+        app.update();
+        app.update();
+        let position = app.world().get::<Transform>(bullet).unwrap().translation;
+        assert!(position.x < 0.);
+    }
+}
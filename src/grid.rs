@@ -0,0 +1,71 @@
+use bevy::{prelude::*, utils::HashMap};
+
+/// Uniform spatial hash of colliders, rebuilt every step so each body only
+/// has to test the bodies in its own and the neighbouring cells. Entries
+/// are indices into the caller's list of bodies.
+#[derive(Resource, Default)]
+pub struct CollisionGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+impl CollisionGrid {
+    /// Empties the grid, keeping its allocations. `cell_size` must be at
+    /// least the largest distance at which two bodies can touch.
+    pub fn reset(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        for entries in self.cells.values_mut() {
+            entries.clear();
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, position: Vec2) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    /// Everything in the 3x3 block of cells around `position`, in insertion order
+    pub fn query_nearby(&self, position: Vec2) -> Vec<usize> {
+        let center = self.cell(position);
+        let mut nearby: Vec<usize> = (-1..=1)
+            .flat_map(|x| (-1..=1).map(move |y| center + IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        nearby.sort_unstable();
+        nearby
+    }
+
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_bodies_in_the_neighbouring_cells_only() {
+        let mut grid = CollisionGrid::default();
+        grid.reset(10.);
+        grid.insert(0, Vec2::new(5., 5.));
+        grid.insert(1, Vec2::new(-5., 15.));
+        grid.insert(2, Vec2::new(25., 5.));
+        grid.insert(3, Vec2::new(-15., -5.));
+        assert_eq!(grid.query_nearby(Vec2::new(1., 1.)), vec![0, 1]);
+        assert_eq!(grid.query_nearby(Vec2::new(19., 0.)), vec![0, 2]);
+    }
+
+    #[test]
+    fn reset_empties_the_grid() {
+        let mut grid = CollisionGrid::default();
+        grid.reset(10.);
+        grid.insert(0, Vec2::ZERO);
+        grid.reset(20.);
+        assert!(grid.query_nearby(Vec2::ZERO).is_empty());
+        grid.insert(1, Vec2::new(35., 0.));
+        assert_eq!(grid.query_nearby(Vec2::new(5., 0.)), vec![1]);
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::game_state::GameState;
+use crate::rocket::PlayerCount;
+use crate::ron_file::{load_ron, save_ron};
+use crate::score::MatchState;
+
+const HIGH_SCORES_PATH: &str = "highscores.ron";
+const MAX_HIGH_SCORES: usize = 10;
+
+/// Keeps the best match results in `highscores.ron` next to `display.ron`
+pub struct HighScoresPlugin;
+
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighScores::load())
+            .add_systems(OnEnter(GameState::GameOver), record_high_scores);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    // Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// Best results first, at most `MAX_HIGH_SCORES` of them
+#[derive(Resource, Serialize, Deserialize, Default, Debug)]
+pub struct HighScores(pub Vec<HighScore>);
+
+impl HighScores {
+    /// Reads the scores from disk, starting with an empty list if the file is missing or invalid
+    pub fn load() -> Self {
+        let mut scores: Self = load_ron(HIGH_SCORES_PATH).unwrap_or_default();
+        // The file may have been edited by hand
+        scores.0.sort_by_key(|entry| Reverse(entry.score));
+        scores.0.truncate(MAX_HIGH_SCORES);
+        scores
+    }
+
+    pub fn save(&self) {
+        save_ron(HIGH_SCORES_PATH, self);
+    }
+
+    /// Adds `entry` if it makes the list, a tie ranks below the older score.
+    /// Returns whether it was kept.
+    pub fn insert(&mut self, entry: HighScore) -> bool {
+        let rank = self.0.partition_point(|kept| kept.score >= entry.score);
+        if rank >= MAX_HIGH_SCORES {
+            return false;
+        }
+        self.0.insert(rank, entry);
+        self.0.truncate(MAX_HIGH_SCORES);
+        true
+    }
+}
+
+fn record_high_scores(
+    match_state: Res<MatchState>,
+    players: Res<PlayerCount>,
+    mut high_scores: ResMut<HighScores>,
+) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let mut changed = false;
+    for player in 0..players.0 {
+        let score = match_state.score(player);
+        // A match without a single point isn't worth a place
+        if score == 0 {
+            continue;
+        }
+        let entry = HighScore {
+            name: format!("P{}", player + 1),
+            score,
+            timestamp,
+        };
+        if high_scores.insert(entry) {
+            info!("New high score for player {}: {score}", player + 1);
+            changed = true;
+        }
+    }
+    if changed {
+        high_scores.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: u32) -> HighScore {
+        HighScore {
+            name: name.into(),
+            score,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_the_best_scores_in_order() {
+        let mut scores = HighScores::default();
+        for (index, score) in [5, 30, 12, 1, 30, 8, 20, 3, 15, 9, 25, 2].into_iter().enumerate() {
+            scores.insert(entry(&format!("P{index}"), score));
+        }
+        let kept: Vec<u32> = scores.0.iter().map(|entry| entry.score).collect();
+        assert_eq!(kept, [30, 30, 25, 20, 15, 12, 9, 8, 5, 3]);
+        // The tie ranks below the older score
+        assert_eq!(scores.0[0].name, "P1");
+        assert_eq!(scores.0[1].name, "P4");
+    }
+
+    #[test]
+    fn a_full_list_turns_away_lower_scores() {
+        let full = (0..MAX_HIGH_SCORES as u32).rev().map(|score| entry("P1", score + 10));
+        let mut scores = HighScores(full.collect());
+        assert!(!scores.insert(entry("P2", 10)));
+        assert!(!scores.insert(entry("P2", 3)));
+        assert!(scores.insert(entry("P2", 11)));
+        assert_eq!(scores.0.len(), MAX_HIGH_SCORES);
+        assert_eq!(scores.0.last().map(|entry| entry.score), Some(11));
+    }
+}
@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+
+/// Half size of the rocket sprites in world units, measured from the artwork at its spawn scale
+pub const ROCKET_HALF_EXTENTS: Vec2 = Vec2::new(26., 40.);
+
+#[derive(Clone, Copy)]
+pub struct Hitbox {
+    pub half_extents: Vec2,
+}
+
+#[derive(Resource, Default)]
+pub struct HitboxSettings {
+    // Use oriented boxes instead of the plain `radius_collision` circles
+    pub use_obb: bool,
+    pub debug: bool,
+}
+
+/// An oriented bounding box in the XY plane
+#[derive(Clone, Copy, Debug)]
+pub struct Obb {
+    pub center: Vec2,
+    pub half_extents: Vec2,
+    pub rotation: f32,
+}
+
+impl Obb {
+    pub fn from_transform(transform: &Transform, hitbox: &Hitbox) -> Self {
+        let (_, _, rotation) = transform.rotation.to_euler(EulerRot::XYZ);
+        Self {
+            center: transform.translation.truncate(),
+            half_extents: hitbox.half_extents,
+            rotation,
+        }
+    }
+
+    pub fn axes(&self) -> [Vec2; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        [Vec2::new(cos, sin), Vec2::new(-sin, cos)]
+    }
+
+    /// Half length of the box projected onto `axis`
+    fn projected_radius(&self, axis: Vec2) -> f32 {
+        let [x, y] = self.axes();
+        self.half_extents.x * x.dot(axis).abs() + self.half_extents.y * y.dot(axis).abs()
+    }
+
+    pub fn corners(&self) -> [Vec2; 4] {
+        let [x, y] = self.axes();
+        let x = x * self.half_extents.x;
+        let y = y * self.half_extents.y;
+        [
+            self.center - x - y,
+            self.center + x - y,
+            self.center + x + y,
+            self.center - x + y,
+        ]
+    }
+
+    pub fn closest_point(&self, point: Vec2) -> Vec2 {
+        let [x, y] = self.axes();
+        let offset = point - self.center;
+        let local_x = offset.dot(x).clamp(-self.half_extents.x, self.half_extents.x);
+        let local_y = offset.dot(y).clamp(-self.half_extents.y, self.half_extents.y);
+        self.center + x * local_x + y * local_y
+    }
+}
+
+/// Separating axis test, two boxes in 2D only need their own four edge normals checked
+pub fn obb_vs_obb(a: &Obb, b: &Obb) -> bool {
+    let offset = b.center - a.center;
+    a.axes()
+        .into_iter()
+        .chain(b.axes())
+        .all(|axis| offset.dot(axis).abs() <= a.projected_radius(axis) + b.projected_radius(axis))
+}
+
+pub fn obb_vs_circle(obb: &Obb, center: Vec2, radius: f32) -> bool {
+    obb.closest_point(center).distance_squared(center) <= radius * radius
+}
+
+/// F3 toggles the debug view, F4 switches rocket collisions to oriented boxes
+pub fn toggle_hitboxes(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<HitboxSettings>) {
+    if keys.just_pressed(KeyCode::F3) {
+        settings.debug = !settings.debug;
+    }
+    if keys.just_pressed(KeyCode::F4) {
+        settings.use_obb = !settings.use_obb;
+        info!("Oriented hitboxes: {}", settings.use_obb);
+    }
+}
+
+pub fn draw_hitboxes(
+    settings: Res<HitboxSettings>,
+    mut gizmos: Gizmos,
+    rockets: Query<(&crate::rocket::Rocket, &Transform)>,
+) {
+    if !settings.debug {
+        return;
+    }
+    for (rocket, transform) in rockets.iter() {
+        if settings.use_obb {
+            let corners = Obb::from_transform(transform, &rocket.hitbox).corners();
+            gizmos.linestrip_2d(
+                corners.into_iter().chain([corners[0]]),
+                Color::srgb(0.2, 1.0, 0.2),
+            );
+        } else {
+            // `radius_collision` is the distance between two rocket centres, so each one owns half of it
+            gizmos.circle_2d(
+                transform.translation.truncate(),
+                rocket.radius_collision * 0.5,
+                Color::srgb(0.2, 1.0, 0.2),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    fn obb(x: f32, y: f32, rotation: f32) -> Obb {
+        Obb {
+            center: Vec2::new(x, y),
+            half_extents: Vec2::new(10., 20.),
+            rotation,
+        }
+    }
+
+    #[test]
+    fn boxes_overlap_only_within_their_extents() {
+        let a = obb(0., 0., 0.);
+        assert!(obb_vs_obb(&a, &obb(19., 0., 0.)));
+        assert!(!obb_vs_obb(&a, &obb(21., 0., 0.)));
+        assert!(obb_vs_obb(&a, &obb(0., 39., 0.)));
+        assert!(!obb_vs_obb(&a, &obb(0., 41., 0.)));
+    }
+
+    #[test]
+    fn rotated_boxes_are_tested_on_their_own_axes() {
+        let a = obb(0., 0., 0.);
+        // Apart side by side, a quarter turn brings the long side in reach
+        assert!(!obb_vs_obb(&a, &obb(29., 0., 0.)));
+        assert!(obb_vs_obb(&a, &obb(29., 0., std::f32::consts::FRAC_PI_2)));
+        // Diagonal boxes whose bounding circles overlap but whose corners miss
+        let b = obb(28., 28., FRAC_PI_4);
+        assert!(!obb_vs_obb(&a, &b));
+        assert_eq!(obb_vs_obb(&a, &b), obb_vs_obb(&b, &a));
+    }
+
+    #[test]
+    fn circles_touch_the_closest_point_of_a_box() {
+        let a = obb(0., 0., 0.);
+        assert!(obb_vs_circle(&a, Vec2::new(15., 0.), 5.));
+        assert!(!obb_vs_circle(&a, Vec2::new(16., 0.), 5.));
+        // Just past a corner, closer than the radius on either axis but not diagonally
+        assert!(!obb_vs_circle(&a, Vec2::new(14., 24.), 5.));
+        assert!(obb_vs_circle(&a, Vec2::new(0., 0.), 1.));
+    }
+}
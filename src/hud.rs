@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+
+use crate::game_state::GameState;
+use crate::hyperspace::HyperspaceCooldown;
+use crate::rocket::{PlayerCount, Rocket, MAX_PLAYERS};
+use crate::score::MatchState;
+
+// Health below this is shown in red
+const LOW_HEALTH: f32 = 30.;
+const HUD_COLOR: Color = Color::srgba(1., 1., 1., 0.8);
+const LOW_HEALTH_COLOR: Color = Color::srgb(1., 0.2, 0.2);
+const DESTROYED_COLOR: Color = Color::srgba(1., 1., 1., 0.4);
+const PLAYER_COUNT_COLOR: Color = Color::srgba(1., 1., 1., 0.7);
+
+/// Status line of one player, the second section holds the health so it can be coloured
+#[derive(Component)]
+pub struct HudText {
+    pub player: usize,
+}
+
+/// Lets the menu tell how many players the next match is for
+#[derive(Component)]
+pub struct PlayerCountText;
+
+/// Player stats in the top corners, even players on the left and odd ones on the right.
+/// Every player gets a line, the ones not in the match are hidden.
+pub fn add_hud(mut commands: Commands) {
+    let style = TextStyle {
+        font_size: 16.,
+        color: HUD_COLOR,
+        ..default()
+    };
+    for player in 0..MAX_PLAYERS {
+        let top = Val::Px(8. + (player / 2) as f32 * 20.);
+        let (left, right) = if player % 2 == 0 {
+            (Val::Px(8.), Val::Auto)
+        } else {
+            (Val::Auto, Val::Px(8.))
+        };
+        commands.spawn((
+            TextBundle::from_sections([
+                TextSection::new("", style.clone()),
+                TextSection::new("", style.clone()),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top,
+                left,
+                right,
+                ..default()
+            }),
+            HudText { player },
+        ));
+    }
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.,
+                color: PLAYER_COUNT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(88.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+        PlayerCountText,
+    ));
+}
+
+pub fn update_player_count_text(
+    state: Res<State<GameState>>,
+    players: Res<PlayerCount>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<PlayerCountText>>,
+) {
+    for (mut text, mut visibility) in &mut texts {
+        *visibility = if *state.get() == GameState::Menu {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        text.sections[0].value = format!("Players: {}  [2-{MAX_PLAYERS} to change]", players.0);
+    }
+}
+
+pub fn update_hud(
+    state: Res<State<GameState>>,
+    match_state: Res<MatchState>,
+    players: Res<PlayerCount>,
+    rockets: Query<(&Rocket, Option<&HyperspaceCooldown>)>,
+    mut texts: Query<(&mut Text, &mut Visibility, &HudText)>,
+) {
+    for (mut text, mut visibility, hud) in &mut texts {
+        // Nothing to report before the first match starts
+        if *state.get() == GameState::Menu || hud.player >= players.0 {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+
+        let label = format!("P{}  kills {}  ", hud.player + 1, match_state.kills[hud.player]);
+        let rocket = rockets.iter().find(|(rocket, _)| rocket.player == hud.player);
+        let Some((rocket, cooldown)) = rocket else {
+            text.sections[0].value = label;
+            text.sections[1].value = "DESTROYED".into();
+            text.sections[1].style.color = DESTROYED_COLOR;
+            continue;
+        };
+
+        let reloading = if rocket.reload_progress > 0. { " (reloading)" } else { "" };
+        let jump = match cooldown {
+            Some(cooldown) => format!("{:.0}s", cooldown.0.remaining_secs().ceil()),
+            None => "ready".into(),
+        };
+        text.sections[0].value = format!(
+            "{label}speed {:>3.0}  ammo {}/{}{reloading}  fuel {:.0}  shield {:.0}  jump {jump}  bonus {}  health ",
+            rocket.velocity.length(),
+            rocket.ammo,
+            rocket.max_ammo,
+            rocket.fuel,
+            rocket.shield_energy,
+            match_state.bonus[hud.player],
+        );
+        text.sections[1].value = format!("{:.0}", rocket.health);
+        text.sections[1].style.color = if rocket.health < LOW_HEALTH {
+            LOW_HEALTH_COLOR
+        } else {
+            HUD_COLOR
+        };
+    }
+}
@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+
+use crate::effects::spawn_warp;
+use crate::respawn::safe_spawn_position;
+use crate::rng::GameRng;
+use crate::rocket::{Arena, DestroyCause, Rocket, RocketDestroyed, RocketInput};
+use crate::sun::Sun;
+
+/// How often a rocket can jump and how likely a jump is to go wrong
+#[derive(Resource)]
+pub struct HyperspaceSettings {
+    // Seconds after a jump before the next one
+    pub cooldown: f32,
+    // Chance from 0 to 1 that the rocket breaks up instead of arriving
+    pub failure_chance: f32,
+}
+
+impl Default for HyperspaceSettings {
+    fn default() -> Self {
+        Self {
+            cooldown: 8.,
+            failure_chance: 0.1,
+        }
+    }
+}
+
+/// Added to a rocket when it jumps and removed once it can jump again
+#[derive(Component)]
+pub struct HyperspaceCooldown(pub Timer);
+
+pub fn tick_hyperspace_cooldowns(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rockets: Query<(Entity, &mut HyperspaceCooldown)>,
+) {
+    for (entity, mut cooldown) in rockets.iter_mut() {
+        if cooldown.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<HyperspaceCooldown>();
+        }
+    }
+}
+
+/// Moves every rocket whose player pressed the hyperspace key to a random point
+/// clear of the suns and the other rockets, keeping its velocity and heading.
+/// A failed jump is reported like any other death, it counts for whoever hit
+/// the rocket last.
+#[allow(clippy::type_complexity)]
+pub fn hyperspace_jump(
+    mut commands: Commands,
+    settings: Res<HyperspaceSettings>,
+    mut rng: ResMut<GameRng>,
+    arena: Res<Arena>,
+    mut destroyed: EventWriter<RocketDestroyed>,
+    suns: Query<&Transform, (With<Sun>, Without<Rocket>)>,
+    mut rockets: Query<(
+        Entity,
+        &Rocket,
+        &RocketInput,
+        &mut Transform,
+        Has<HyperspaceCooldown>,
+    )>,
+) {
+    let sun_positions: Vec<Vec2> = suns
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let positions: Vec<(Entity, Vec2)> = rockets
+        .iter()
+        .map(|(entity, _, _, transform, _)| (entity, transform.translation.truncate()))
+        .collect();
+
+    for (entity, rocket, input, mut transform, cooling_down) in rockets.iter_mut() {
+        if !input.hyperspace || cooling_down {
+            continue;
+        }
+        let cooldown = Timer::from_seconds(settings.cooldown, TimerMode::Once);
+        commands.entity(entity).insert(HyperspaceCooldown(cooldown));
+        spawn_warp(&mut commands, transform.translation, rocket.color);
+
+        if rng.next_f32() < settings.failure_chance {
+            destroyed.send(RocketDestroyed {
+                entity,
+                player: rocket.player,
+                position: transform.translation,
+                velocity: rocket.velocity,
+                color: rocket.color,
+                cause: DestroyCause::Hyperspace,
+                killer: rocket.last_hit_by,
+            });
+            continue;
+        }
+
+        let others: Vec<Vec2> = positions
+            .iter()
+            .filter(|(other, _)| *other != entity)
+            .map(|(_, position)| *position)
+            .collect();
+        let target = safe_spawn_position(&mut rng, &arena, &others, &sun_positions);
+        transform.translation.x = target.x;
+        transform.translation.y = target.y;
+        spawn_warp(&mut commands, transform.translation, rocket.color);
+    }
+}
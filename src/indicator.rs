@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+
+use crate::rocket::Rocket;
+
+// Gap between the arrow tips and the edge of the view
+const INDICATOR_MARGIN: f32 = 16.;
+const INDICATOR_LENGTH: f32 = 24.;
+
+/// Draws an arrow on the edge of the view pointing at every rocket outside of
+/// it, such as one caught between the arena edge and its wrap. Despawned
+/// rockets aren't queried, so their arrow goes away with them.
+pub fn draw_offscreen_indicators(
+    mut gizmos: Gizmos,
+    cameras: Query<(&OrthographicProjection, &Transform), With<Camera2d>>,
+    rockets: Query<(&Rocket, &Transform)>,
+) {
+    let Ok((projection, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    // The view follows the camera as it tracks the rockets and shakes
+    let centre = camera_transform.translation.truncate() + projection.area.center();
+    let half_size = projection.area.half_size();
+    let inset = (half_size - Vec2::splat(INDICATOR_MARGIN)).max(Vec2::ZERO);
+
+    for (rocket, transform) in rockets.iter() {
+        let offset = transform.translation.truncate() - centre;
+        if offset.x.abs() <= half_size.x && offset.y.abs() <= half_size.y {
+            continue;
+        }
+        let (tip, direction) = edge_point(offset, inset);
+        gizmos.arrow_2d(
+            centre + tip - direction * INDICATOR_LENGTH,
+            centre + tip,
+            rocket.color,
+        );
+    }
+}
+
+/// Where the line from the centre towards `offset` crosses the box of
+/// `half_size`, and the direction of that line
+fn edge_point(offset: Vec2, half_size: Vec2) -> (Vec2, Vec2) {
+    let direction = offset.normalize_or_zero();
+    // The axis that reaches its edge first decides where the line leaves the
+    // box, an axis the line runs parallel to divides by zero and never wins
+    let scale = (half_size / direction.abs()).min_element();
+    (direction * scale, direction)
+}
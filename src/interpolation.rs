@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::rocket::ScreenWrap;
+
+// A body that moved further than this in one step was teleported, by a wrap,
+// a respawn or a hyperspace jump, and is drawn at its new spot straight away
+const TELEPORT_DISTANCE: f32 = 100.;
+
+/// Where a body was at the last two fixed steps, so it can be drawn in between
+/// on frames that fall between steps. `rendered` is what was last written to
+/// the `Transform` for drawing, anything else found there was set on purpose.
+#[derive(Component)]
+pub struct Interpolated {
+    previous: Transform,
+    current: Transform,
+    rendered: Transform,
+}
+
+impl Interpolated {
+    fn new(transform: Transform) -> Self {
+        Self {
+            previous: transform,
+            current: transform,
+            rendered: transform,
+        }
+    }
+}
+
+/// Starts tracking the rockets, bullets and asteroids as they are spawned
+#[allow(clippy::type_complexity)]
+pub fn track_new_bodies(
+    mut commands: Commands,
+    bodies: Query<(Entity, &Transform), (With<ScreenWrap>, Without<Interpolated>)>,
+) {
+    for (entity, transform) in bodies.iter() {
+        commands.entity(entity).insert(Interpolated::new(*transform));
+    }
+}
+
+/// Puts every body back where the simulation left it before the next step
+pub fn restore_physical_transforms(mut bodies: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in bodies.iter_mut() {
+        if *transform == interpolated.rendered {
+            *transform = interpolated.current;
+        }
+        interpolated.previous = *transform;
+    }
+}
+
+pub fn record_physical_transforms(mut bodies: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in bodies.iter_mut() {
+        interpolated.current = *transform;
+        let jump = interpolated.previous.translation.distance(transform.translation);
+        if jump > TELEPORT_DISTANCE {
+            interpolated.previous = *transform;
+        }
+    }
+}
+
+/// Draws each body between its last two steps, by how far the clock has got
+/// towards the next one, so motion stays smooth on screens faster than 60 Hz
+pub fn interpolate_transforms(
+    fixed_time: Res<Time<Fixed>>,
+    mut bodies: Query<(&mut Transform, &mut Interpolated)>,
+) {
+    let blend = fixed_time.overstep_fraction();
+    for (mut transform, mut interpolated) in bodies.iter_mut() {
+        // Moved outside the simulation since the last step, e.g. by a network snapshot
+        if *transform != interpolated.rendered && *transform != interpolated.current {
+            interpolated.previous = *transform;
+            interpolated.current = *transform;
+        }
+        let (previous, current) = (interpolated.previous, interpolated.current);
+        transform.translation = previous.translation.lerp(current.translation, blend);
+        transform.rotation = previous.rotation.slerp(current.rotation, blend);
+        interpolated.rendered = *transform;
+    }
+}
@@ -1,29 +1,91 @@
-use bevy::{
-    prelude::*, render::render_resource::*, sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle}, window::WindowMode
-};
+use bevy::{prelude::*, render::camera::ScalingMode, window::WindowMode};
+mod ai;
+mod asteroid;
+mod audio;
+mod bindings;
 mod bullet;
+mod camera;
+mod collision;
+mod debris;
+mod effects;
+mod game_state;
+mod gamepad;
+mod gameplay;
+mod grid;
+mod high_score;
+mod hitbox;
+mod hud;
+mod hyperspace;
+mod indicator;
+mod interpolation;
+mod match_log;
+mod minimap;
+mod motion_trail;
+mod mouse_aim;
+mod net;
 mod post_process;
+mod powerup;
+mod presentation;
+mod respawn;
+mod rng;
+// Not used by the game until it has peer to peer matches, only by its tests
+#[cfg_attr(not(test), allow(dead_code))]
+mod rollback;
 mod rocket;
-use bullet::{check_bullet_coll, handle_bullet_movement, spawn_bullet};
-use rocket::{add_rockets, clip_rockets, Rocket};
+mod ron_file;
+mod safe_zone;
+mod score;
+mod seed;
+mod settings;
+mod shield;
+mod slingshot;
+mod starfield;
+mod sun;
+mod time_scale;
+mod trajectory;
+mod weapon;
+use camera::{
+    apply_screen_shake, camera_follow, gravity_rumble, shake_on_explosions, CameraFollow,
+    GravityRumble, ScreenShake,
+};
+use collision::{CollisionEvent, CollisionKind};
+use effects::EXPLOSION_EFFECT;
+use game_state::{
+    advance_on_enter, clear_banner, in_match, not_paused, show_game_over_banner,
+    show_menu_banner, show_overtime_banner, toggle_pause, GameState,
+};
+use gamepad::{assign_gamepads, rumble_on_damage, GamepadAssignments, PadRumbles};
+use hitbox::{draw_hitboxes, toggle_hitboxes};
+use hud::{add_hud, update_hud, update_player_count_text};
+use indicator::draw_offscreen_indicators;
+use minimap::{add_minimap, spawn_minimap_dots, update_minimap};
+use motion_trail::{draw_motion_trails, record_motion_trails, MotionTrailSettings};
+use respawn::{cycle_respawn_strategy, SpawnProtection};
+use powerup::{draw_powerups, SpeedBoost, SPEED_BOOST_SCALE};
+use rocket::{
+    choose_player_count, read_player_input, toggle_boundary_mode, toggle_collision_mode,
+    CollisionMode, DestroyCause, Rocket, RocketDamaged, RocketDestroyed, RocketInput,
+    BOOST_ACCELERATION_SCALE,
+};
+use seed::{add_seed_text, seed_input, update_seed_text, SeedInput};
+use sun::{draw_physics_debug, gravity_step, kill_distance, PhysicsConfig, Sun, SunHeat};
+use time_scale::TimeScale;
+use trajectory::{draw_trajectories, toggle_trajectories, TrajectorySettings};
 
 use bevy_hanabi::prelude::*;
 
 use std::f32::consts::TAU;
 
-#[derive(Asset, TypePath, AsBindGroup, Clone)]
-pub struct MovingPatternMaterial {
-    #[uniform(0)]
-    color: LinearRgba,
-    #[uniform(1)]
-    time: f32,
-}
-
-impl Material2d for MovingPatternMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/stars_material.wgsl".into()
-    }
-}
+// World area that is always visible, the window shows more of the
+// background when its aspect ratio differs
+const ARENA_WIDTH: f32 = 980.;
+const ARENA_HEIGHT: f32 = 735.;
+// Rate of the fixed timestep the simulation runs on
+const PHYSICS_HZ: f64 = 60.;
+// Health lost by each rocket when two bounce off each other
+const ROCKET_BOUNCE_DAMAGE: f32 = 15.;
+// Share of the knockback from a bounce that wears off per second
+const KNOCKBACK_DRAG: f32 = 1.5;
 
 fn main() {
     App::new()
@@ -32,104 +94,193 @@ fn main() {
                 primary_window: Some(Window {
                     title: "SpaceWars".into(),
                     name: Some("bevy.app".into()),
-                    resolution: (980., 735.).into(),
+                    resolution: (ARENA_WIDTH, ARENA_HEIGHT).into(),
                     mode: WindowMode::Windowed,
-                    resizable: false,
+                    resizable: true,
                     ..default()
                 }),
                 ..default()
             }),
+            gameplay::GameplayPlugin,
+            presentation::PresentationPlugin,
             post_process::PostProcessPlugin,
-            Material2dPlugin::<MovingPatternMaterial>::default(),
+            settings::DisplaySettingsPlugin,
+            high_score::HighScoresPlugin,
+            match_log::MatchLogPlugin,
+            ai::AiPlugin,
+            mouse_aim::MouseAimPlugin,
+            safe_zone::SafeZonePlugin,
+            net::NetPlugin,
+            audio::GameAudioPlugin,
         ))
-        .add_plugins(HanabiPlugin)
+        .init_resource::<SeedInput>()
+        .init_resource::<ScreenShake>()
+        .init_resource::<GravityRumble>()
+        .init_resource::<CameraFollow>()
+        .init_resource::<GamepadAssignments>()
+        .init_resource::<PadRumbles>()
+        .init_resource::<TrajectorySettings>()
+        .init_resource::<MotionTrailSettings>()
+        .init_resource::<post_process::PostProcessPulse>()
+        .add_systems(Startup, (add_seed_text, add_hud, add_minimap).chain())
+        .add_systems(OnEnter(GameState::Menu), show_menu_banner)
+        .add_systems(OnExit(GameState::Menu), clear_banner)
+        .add_systems(OnExit(GameState::GameOver), clear_banner)
+        // A network client follows the host's state
         .add_systems(
-            Startup,
-            (setup, add_background, add_sun, add_rockets).chain(),
+            Update,
+            (
+                advance_on_enter.run_if(net::lobby_ready),
+                toggle_pause.run_if(in_match),
+            )
+                .run_if(net::runs_simulation),
+        )
+        .add_systems(
+            Update,
+            (assign_gamepads, read_player_input.run_if(in_match).run_if(not_paused)).chain(),
         )
         .add_systems(
             Update,
             (
-                check_bullet_coll,
-                spawn_bullet,
-                update_rocket_status,
-                clip_rockets,
-                gravitational_pull,
-                handle_bullet_movement,
+                toggle_boundary_mode,
+                toggle_collision_mode,
+                toggle_hitboxes,
+                draw_hitboxes,
+                draw_physics_debug,
+                toggle_trajectories,
+                draw_trajectories,
+                record_motion_trails,
+                draw_motion_trails,
+                draw_offscreen_indicators,
+                draw_powerups,
+                update_hud,
                 post_process::rotate,
+                post_process::send_post_process_events,
+                post_process::apply_post_process_events,
                 post_process::update_settings,
             )
                 .chain(),
         )
+        .add_systems(Update, (spawn_minimap_dots, update_minimap).chain())
+        .add_systems(Update, (cycle_respawn_strategy, rumble_on_damage))
+        .add_systems(
+            Update,
+            (
+                choose_player_count
+                    .run_if(in_state(GameState::Menu))
+                    .run_if(net::runs_simulation),
+                update_player_count_text,
+            )
+                .chain(),
+        )
+        .add_systems(OnEnter(GameState::Overtime), show_overtime_banner)
+        .add_systems(OnExit(GameState::Overtime), clear_banner)
+        .add_systems(OnEnter(GameState::GameOver), show_game_over_banner)
+        .add_systems(
+            Update,
+            (seed_input.run_if(in_match), update_seed_text).chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                gravity_rumble,
+                camera_follow,
+                shake_on_explosions,
+                apply_screen_shake,
+            )
+                .chain(),
+        )
         .run();
 }
 
-fn check_collision(rocket1: &Transform, rocket2: &Transform, radius_collison: f32) -> bool {
-    let distance = rocket1
-        .translation
-        .truncate()
-        .distance(rocket2.translation.truncate());
-    // println!("{}", distance);
-    distance < radius_collison
+/// Rockets flying into an asteroid are destroyed, unless a shield takes the hit
+fn check_asteroid_coll(
+    mut collisions: EventReader<CollisionEvent>,
+    mut damaged: EventWriter<RocketDamaged>,
+    mut destroyed: EventWriter<RocketDestroyed>,
+    mut rockets: Query<(&mut Rocket, &Transform), Without<SpawnProtection>>,
+) {
+    // Touching two asteroids at once is still one hit
+    let mut hit: Vec<Entity> = Vec::new();
+    for collision in collisions.read() {
+        let entity = collision.a;
+        if collision.kind != CollisionKind::ShipAsteroid || hit.contains(&entity) {
+            continue;
+        }
+        let Ok((mut rocket, transform)) = rockets.get_mut(entity) else {
+            continue;
+        };
+        hit.push(entity);
+        if rocket.absorb_hit() {
+            damaged.send(RocketDamaged {
+                player: rocket.player,
+                destroyed: false,
+                attacker: None,
+            });
+        } else {
+            destroyed.send(RocketDestroyed {
+                entity,
+                player: rocket.player,
+                position: transform.translation,
+                velocity: rocket.velocity,
+                color: rocket.color,
+                cause: DestroyCause::Asteroid,
+                killer: rocket.last_hit_by,
+            });
+        }
+    }
 }
 
-fn check_sun_collision(rocket: &Transform, radius_collision: f32) -> bool {
-    let distance = rocket.translation.truncate().distance(Vec2::new(0.0, 0.0));
+fn check_sun_collision(rocket: &Transform, sun: &Transform, radius_collision: f32) -> bool {
+    let distance = rocket
+        .translation
+        .truncate()
+        .distance(sun.translation.truncate());
     distance < radius_collision
 }
 
-fn gravitational_pull(mut rocket_query: Query<(&mut Rocket, &mut Transform)>, time: Res<Time>) {
-    let sun_position = Vec2::ZERO;
-    const G_FORCE: f64 = 125000000.0;
+/// Pulls every rocket towards the suns and moves it along its velocity
+fn gravitational_pull(
+    mut rocket_query: Query<(&mut Rocket, &mut Transform)>,
+    suns: Query<&Transform, (With<Sun>, Without<Rocket>)>,
+    config: Res<PhysicsConfig>,
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+) {
+    let suns: Vec<Vec2> = suns
+        .iter()
+        .map(|sun| sun.translation.truncate())
+        .collect();
 
     for (mut rocket, mut transform) in rocket_query.iter_mut() {
-        let rocket_position = Vec2::new(transform.translation.x, transform.translation.y);
-
-        let direction = sun_position - rocket_position;
-        let distance = direction.length();
-
-        if distance < 65.0 {
-            continue;
-        }
-
-        let force = G_FORCE / (distance * distance) as f64;
-
-        let acceleration = direction.normalize() * force as f32;
-
-        rocket.velocity += acceleration * time.delta_seconds();
-
-        if rocket.velocity.length() > rocket.max_speed {
-            rocket.velocity = rocket.velocity.normalize() * rocket.max_speed;
-        }
-        transform.translation.x += rocket.velocity.x * time.delta_seconds();
-        transform.translation.y += rocket.velocity.y * time.delta_seconds();
+        let (position, velocity) = gravity_step(
+            transform.translation.truncate(),
+            rocket.velocity,
+            &suns,
+            &config,
+            // Gravity doesn't add to the speed left over from a boost
+            rocket.top_speed().max(rocket.velocity.length()),
+            time_scale.delta(&time),
+        );
+        rocket.velocity = velocity;
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
     }
 }
 
-fn add_background(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<MovingPatternMaterial>>,
-    window: Query<&Window>,
-) {
-    let window = window.single();
-    let resolution = Vec2::new(window.width(), window.height());
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes
-            .add(Mesh::from(Rectangle::from_size(resolution)))
-            .into(),
-        material: materials.add(MovingPatternMaterial {
-            color: LinearRgba::WHITE,
-            time: 0.0,
-        }),
-        ..default()
-    });
-}
-
 fn setup(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
+    // Scales the view so the whole arena stays visible and centred at any resolution
+    let projection = OrthographicProjection {
+        scaling_mode: ScalingMode::AutoMin {
+            min_width: ARENA_WIDTH,
+            min_height: ARENA_HEIGHT,
+        },
+        ..Camera2dBundle::default().projection
+    };
     commands.spawn((
         Camera2dBundle {
             camera: Camera { ..default() },
+            projection,
             ..default()
         },
         post_process::PostProcessSettings {
@@ -179,9 +330,11 @@ fn setup(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
 
     let module = writer.finish();
 
-    let spawner = Spawner::once(100.0.into(), false);
+    // Every explosion is its own entity, bursting as soon as it is spawned
+    let spawner = Spawner::once(100.0.into(), true);
 
-    let effect = effects.add(
+    effects.insert(
+        &EXPLOSION_EFFECT,
         EffectAsset::new(32768, spawner, module)
             .with_name("explosion")
             .init(init_pos)
@@ -194,149 +347,409 @@ fn setup(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
                 size: Vec3::splat(3.).into(),
             }),
     );
-
-    commands
-        .spawn(ParticleEffectBundle {
-            // Assign the Z layer so it appears in the egui inspector and can be modified at runtime
-            effect: ParticleEffect::new(effect).with_z_layer_2d(Some(0.1)),
-            ..default()
-        })
-        .insert(Name::new("effect:meteor_explosion"));
-}
-
-#[derive(Component)]
-struct Sun {}
-
-fn add_sun(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: meshes.add(Mesh::from(Circle::new(50.0))).into(),
-            material: materials.add(ColorMaterial::from(Color::srgb(7.0, 7.0, 0.0))),
-            transform: Transform::from_xyz(0.0, 0.0, 3.0),
-            ..default()
-        },
-        Sun {},
-    ));
 }
 
 fn handle_rocket_movement(
-    time: &Res<Time>,
-    keys: &Res<ButtonInput<KeyCode>>,
+    delta: f32,
+    input: &RocketInput,
     rocket: &mut Rocket,
     transform: &mut Transform,
+    speed_boost: bool,
 ) {
-    if keys.pressed(rocket.controls.accelerate) {
-        if rocket.speed < rocket.max_speed {
-            rocket.speed += 50.0 * time.delta_seconds();
-        }
+    let boosting = rocket.update_boost(delta, input.boost);
+    let mut acceleration = if boosting {
+        rocket.thrust_accel * BOOST_ACCELERATION_SCALE
     } else {
-        if rocket.speed > 0.0 {
-            rocket.speed -= 50.0 * time.delta_seconds();
-        }
+        rocket.thrust_accel
+    };
+    let mut top_speed = rocket.top_speed();
+    if speed_boost {
+        acceleration *= SPEED_BOOST_SCALE;
+        top_speed *= SPEED_BOOST_SCALE;
     }
 
-    let mut rotation_input = 0.0;
-
-    if keys.pressed(rocket.controls.rotate_left) {
-        rotation_input += 4.0;
+    // Thrust wins while both thrust and brake are held. Coasting keeps the
+    // velocity, so gravity can hold a rocket in orbit.
+    let direction = (transform.rotation * Vec3::Y).truncate();
+    if input.thrust {
+        // Thrust alone can't take the rocket past its top speed, though it
+        // keeps any speed it already had
+        let cap = top_speed.max(rocket.velocity.length());
+        rocket.velocity += direction * acceleration * input.throttle() * delta;
+        rocket.velocity = rocket.velocity.clamp_length_max(cap);
+    } else if input.brake {
+        // Brakes along the heading, down to flying backwards at `max_reverse_speed`
+        let forward = rocket.velocity.dot(direction);
+        let braked = (forward - rocket.brake_decel * delta).max(-rocket.max_reverse_speed);
+        if braked < forward {
+            rocket.velocity += direction * (braked - forward);
+        }
     }
-
-    if keys.pressed(rocket.controls.rotate_right) {
-        rotation_input -= 4.0;
+    // Bleeds off the extra speed once a boost ends
+    let speed = rocket.velocity.length();
+    if speed > top_speed {
+        rocket.velocity *= (speed - 50.0 * delta).max(top_speed) / speed;
     }
 
-    let max_rotation_speed = f32::to_radians(70.0);
-    let rotation_acceleration = f32::to_radians(50.0 * time.delta_seconds());
+    let turn = input.turn();
+    let rotation_input = turn * rocket.turn_rate;
+
+    let max_rotation_speed = rocket.max_rotation_speed;
+    let rotation_acceleration = f32::to_radians(50.0 * delta);
     rocket.rotation_speed += rotation_input * rotation_acceleration;
+    // Settles on a heading once the keys are released, without overshooting.
+    // A half pushed stick settles on half the top rotation speed the same way.
+    let excess = rocket.rotation_speed.abs() - max_rotation_speed * turn.abs();
+    if excess > 0.0 {
+        let damping = (rocket.rotation_damping * delta).min(excess);
+        rocket.rotation_speed -= damping.copysign(rocket.rotation_speed);
+    }
     rocket.rotation_speed = rocket
         .rotation_speed
         .clamp(-max_rotation_speed, max_rotation_speed);
 
-    transform.rotation *= Quat::from_rotation_z(rocket.rotation_speed * time.delta_seconds());
+    transform.rotation *= Quat::from_rotation_z(rocket.rotation_speed * delta);
 
-    let direction = transform.rotation * Vec3::Y;
-    rocket.velocity = Vec2::new(direction.x, direction.y) * rocket.speed;
+    // Exponential so the knockback never reverses, whatever the frame time.
+    // Only what is left of it wears off, the rest of the velocity is kept.
+    let knockback = rocket.knockback * (-KNOCKBACK_DRAG * delta).exp();
+    rocket.velocity += knockback - rocket.knockback;
+    rocket.knockback = knockback;
+    rocket.speed = rocket.velocity.dot((transform.rotation * Vec3::Y).truncate());
+}
 
-    transform.translation += rocket.velocity.extend(0.0) * time.delta_seconds();
+/// Elastic collision between two rockets of equal mass: the velocity
+/// components along the line between their centres are swapped, the rest is
+/// kept, so the total momentum and energy are unchanged. Returns the change in
+/// the second rocket's velocity, the first one changes by the opposite, or
+/// `None` if the rockets are already moving apart.
+fn elastic_bounce(
+    position: Vec2,
+    velocity: Vec2,
+    other_position: Vec2,
+    other_velocity: Vec2,
+) -> Option<Vec2> {
+    let normal = (other_position - position).try_normalize()?;
+    let approach = (velocity - other_velocity).dot(normal);
+    // Still overlapping right after a bounce, don't bounce them back together
+    if approach <= 0. {
+        return None;
+    }
+    Some(normal * approach)
 }
 
+/// Finds the rockets burnt by a sun or hit by another rocket. Only reports
+/// them, `destroy_rockets` removes the destroyed ones.
+#[allow(clippy::too_many_arguments)]
 fn update_rocket_status(
-    mut commands: Commands,
-    keys: Res<ButtonInput<KeyCode>>,
-    mut entities: Query<(Entity, &mut Rocket, &mut Transform), Without<EffectProperties>>,    
-    mut effect: Query<(
-        &mut EffectProperties,
-        &mut EffectInitializers,
-        &mut Transform,
-    )>,
+    collision_mode: Res<CollisionMode>,
+    mut collisions: EventReader<CollisionEvent>,
+    suns: Query<(&Sun, &Transform), Without<Rocket>>,
+    mut entities: Query<(Entity, &mut Rocket, &Transform)>,
+    protected: Query<(), With<SpawnProtection>>,
+    mut damaged: EventWriter<RocketDamaged>,
+    mut destroyed_events: EventWriter<RocketDestroyed>,
+    sun_heat: Res<SunHeat>,
     time: Res<Time>,
+    time_scale: Res<TimeScale>,
 ) {
-    let rockets: Vec<(Entity, Mut<'_, Rocket>, Mut<'_, Transform>)> = entities.iter_mut().collect();
-    let Ok((mut properties, mut initializers, mut effect_transform)) = effect.get_single_mut()
-    else {
-        return;
+    let delta = time_scale.delta(&time);
+    let collisions: Vec<CollisionEvent> = collisions.read().copied().collect();
+    let mut destroyed: Vec<RocketDestroyed> = Vec::new();
+    let report = |entity: Entity, rocket: &Rocket, position: Vec3, cause: DestroyCause| {
+        RocketDestroyed {
+            entity,
+            player: rocket.player,
+            position,
+            velocity: rocket.velocity,
+            color: rocket.color,
+            cause,
+            killer: rocket.last_hit_by,
+        }
     };
 
-    if rockets.len() > 1 {
-        let (entity, rocket1, transform1) = &rockets[0];
-        let (entity2, rocket2, transform2) = &rockets[1];
+    // Touching a sun destroys a rocket outright, its corona heats the rocket up
+    // the closer it gets so a quick graze is survivable
+    for (entity, mut rocket, transform) in entities.iter_mut() {
+        if protected.contains(entity) {
+            continue;
+        }
+        let touching = collisions
+            .iter()
+            .any(|collision| collision.kind == CollisionKind::ShipSun && collision.a == entity);
+        let mut heat = 0.;
+        for (sun, sun_transform) in suns.iter() {
+            let distance = transform
+                .translation
+                .truncate()
+                .distance(sun_transform.translation.truncate());
+            heat += sun_heat.damage(distance, kill_distance(sun, &rocket));
+        }
+        let burnt = if touching {
+            let health = rocket.health;
+            rocket.take_damage(health)
+        } else {
+            heat > 0. && rocket.take_damage(heat * delta)
+        };
+        if burnt {
+            destroyed.push(report(entity, &rocket, transform.translation, DestroyCause::Sun));
+        }
+    }
 
-        if check_sun_collision(transform1, rocket1.radius_collision + 30.) {
-            commands.entity(*entity).despawn();
-            // spawn_particles(commands, meshes, materials, transform1, &time);
+    // Each touching pair of rockets is reported once, only the rockets
+    // actually involved in a collision are destroyed
+    // A shielded rocket loses its shield instead and survives the collision
+    // In bounce mode the pair swaps momentum instead, see `elastic_bounce`
+    let mut bounces: Vec<(Entity, Vec2)> = Vec::new();
+    let mut shields_lost: Vec<Entity> = Vec::new();
+    let mut destroy = |entity: Entity, rocket: &Rocket, position: Vec3| {
+        if destroyed.iter().any(|dead| dead.entity == entity) {
+            return;
+        }
+        if rocket.shielded {
+            if !shields_lost.contains(&entity) {
+                shields_lost.push(entity);
+            }
+        } else {
+            destroyed.push(report(entity, rocket, position, DestroyCause::Collision));
+        }
+    };
 
-            effect_transform.translation = transform1.translation;
+    for collision in collisions.iter() {
+        if collision.kind != CollisionKind::ShipShip {
+            continue;
+        }
+        if protected.contains(collision.a) || protected.contains(collision.b) {
+            continue;
+        }
+        // Gone already if a bullet destroyed it this step
+        let (Ok((entity, rocket, transform)), Ok((other_entity, other_rocket, other_transform))) =
+            (entities.get(collision.a), entities.get(collision.b))
+        else {
+            continue;
+        };
+        match *collision_mode {
+            CollisionMode::Destroy => {
+                destroy(entity, rocket, transform.translation);
+                destroy(other_entity, other_rocket, other_transform.translation);
+            }
+            CollisionMode::Bounce => {
+                let impulse = elastic_bounce(
+                    transform.translation.truncate(),
+                    rocket.velocity,
+                    other_transform.translation.truncate(),
+                    other_rocket.velocity,
+                );
+                if let Some(impulse) = impulse {
+                    bounces.push((entity, -impulse));
+                    bounces.push((other_entity, impulse));
+                }
+            }
+        }
+    }
 
-            let r = 255.;
-            let g = 165.;
-            let b = 0.;
-            let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-            properties.set("spawn_color", color.into());
-            initializers.reset();
+    for (entity, impulse) in bounces {
+        if destroyed.iter().any(|dead| dead.entity == entity) {
+            continue;
+        }
+        if let Ok((_, mut rocket, transform)) = entities.get_mut(entity) {
+            rocket.knockback += impulse;
+            rocket.velocity += impulse;
+            if rocket.take_damage(ROCKET_BOUNCE_DAMAGE) {
+                let position = transform.translation;
+                destroyed.push(report(entity, &rocket, position, DestroyCause::Bounce));
+            } else {
+                damaged.send(RocketDamaged {
+                    player: rocket.player,
+                    destroyed: false,
+                    attacker: None,
+                });
+            }
+        }
+    }
+
+    for entity in shields_lost {
+        if let Ok((_, mut rocket, _)) = entities.get_mut(entity) {
+            rocket.absorb_hit();
+            damaged.send(RocketDamaged {
+                player: rocket.player,
+                destroyed: false,
+                attacker: None,
+            });
         }
+    }
+
+    destroyed_events.send_batch(destroyed);
+}
 
-        if check_sun_collision(transform2, rocket2.radius_collision + 30.) {
-            commands.entity(*entity2).despawn();
-            effect_transform.translation = transform2.translation;
+/// Turns and thrusts every rocket according to its latched input,
+/// `gravitational_pull` then moves it
+fn move_rockets(
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    mut rockets: Query<(&RocketInput, &mut Rocket, &mut Transform, Has<SpeedBoost>)>,
+) {
+    let delta = time_scale.delta(&time);
+    for (input, mut rocket, mut transform, speed_boost) in rockets.iter_mut() {
+        handle_rocket_movement(delta, input, &mut rocket, &mut transform, speed_boost);
+    }
+}
 
-            let r = 255.;
-            let g = 165.;
-            let b = 0.;
-            let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-            properties.set("spawn_color", color.into());
-            initializers.reset();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use rocket::{orbit_velocity, test_rocket};
+    use std::time::Duration;
+    use sun::test_sun;
+
+    const DELTA: f32 = 1. / PHYSICS_HZ as f32;
+
+    /// Runs `steps` steps of movement with the same input
+    fn fly(rocket: &mut Rocket, input: RocketInput, steps: usize) {
+        let mut transform = Transform::default();
+        for _ in 0..steps {
+            handle_rocket_movement(DELTA, &input, rocket, &mut transform, false);
         }
+    }
 
-        if check_collision(transform1, transform2, rocket1.radius_collision) {
-            effect_transform.translation = transform1.translation;
+    fn thrust() -> RocketInput {
+        RocketInput {
+            thrust: true,
+            ..default()
+        }
+    }
 
-            let r = 255.;
-            let g = 165.;
-            let b = 0.;
-            let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-            properties.set("spawn_color", color.into());
-            initializers.reset();
+    #[test]
+    fn ship_stats_change_how_it_flies() {
+        let mut light = test_rocket(0);
+        light.thrust_accel = 120.;
+        light.max_speed = 300.;
+        let mut heavy = test_rocket(0);
+        heavy.thrust_accel = 30.;
+        heavy.max_speed = 100.;
+
+        fly(&mut light, thrust(), 1);
+        fly(&mut heavy, thrust(), 1);
+        assert!((light.speed - 120. * DELTA).abs() < 1e-5);
+        assert!((heavy.speed - 30. * DELTA).abs() < 1e-5);
+
+        // Each one levels off at its own top speed
+        fly(&mut light, thrust(), 600);
+        fly(&mut heavy, thrust(), 600);
+        assert!((light.speed - 300.).abs() < 120. * DELTA);
+        assert!((heavy.speed - 100.).abs() < 30. * DELTA);
+    }
 
-            effect_transform.translation = transform2.translation;
+    #[test]
+    fn rotation_settles_once_the_keys_are_released() {
+        let mut rocket = test_rocket(0);
+        let left = RocketInput {
+            rotate_left: true,
+            ..default()
+        };
+        fly(&mut rocket, left, 30);
+        assert!(rocket.rotation_speed > 0.);
+        assert!(rocket.rotation_speed <= rocket.max_rotation_speed);
+
+        let mut last = rocket.rotation_speed;
+        for _ in 0..120 {
+            fly(&mut rocket, RocketInput::default(), 1);
+            assert!(rocket.rotation_speed <= last && rocket.rotation_speed >= 0.);
+            last = rocket.rotation_speed;
+        }
+        assert_eq!(rocket.rotation_speed, 0.);
+    }
 
-            let r = 255.;
-            let g = 165.;
-            let b = 0.;
-            let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-            properties.set("spawn_color", color.into());
-            initializers.reset();
+    #[test]
+    fn bounces_conserve_momentum_and_energy() {
+        let cases = [
+            (Vec2::ZERO, Vec2::new(80., 10.), Vec2::new(40., 0.), Vec2::new(-30., 25.)),
+            (Vec2::new(5., 5.), Vec2::new(0., -60.), Vec2::new(20., -30.), Vec2::ZERO),
+        ];
+        for (position, velocity, other_position, other_velocity) in cases {
+            let impulse = elastic_bounce(position, velocity, other_position, other_velocity)
+                .expect("the rockets are closing in");
+            let after = velocity - impulse;
+            let other_after = other_velocity + impulse;
+            assert!((after + other_after).distance(velocity + other_velocity) < 1e-3);
+            let energy = velocity.length_squared() + other_velocity.length_squared();
+            let energy_after = after.length_squared() + other_after.length_squared();
+            assert!((energy - energy_after).abs() < 1e-2);
+            // Moving apart now
+            let normal = (other_position - position).normalize();
+            assert!((after - other_after).dot(normal) <= 0.);
+        }
+    }
 
-            for (entity, _, _) in entities.iter() {
-                commands.entity(entity).despawn();
-            }
+    #[test]
+    fn rockets_moving_apart_do_not_bounce() {
+        let bounce = elastic_bounce(Vec2::ZERO, Vec2::new(-10., 0.), Vec2::X * 40., Vec2::ZERO);
+        assert_eq!(bounce, None);
+    }
+
+    #[test]
+    fn a_half_pushed_stick_flies_at_half_strength() {
+        let half = |rotate_left| RocketInput {
+            thrust: true,
+            rotate_left,
+            analog: Some(Vec2::new(if rotate_left { -0.5 } else { 0. }, 0.5)),
+            ..default()
+        };
+        let mut full = test_rocket(0);
+        let mut analog = test_rocket(0);
+        fly(&mut full, thrust(), 1);
+        fly(&mut analog, half(false), 1);
+        assert!((analog.speed - full.speed * 0.5).abs() < 1e-5);
+
+        // Turns half as fast for as long as the stick is held there
+        let mut rocket = test_rocket(0);
+        fly(&mut rocket, half(true), 300);
+        let turn = rocket.rotation_speed;
+        assert!((turn - rocket.max_rotation_speed * 0.5).abs() < 0.05);
+    }
+
+    /// Farthest and closest a rocket started 300 units out with `orbit_velocity`
+    /// gets to the sun over fifteen seconds of coasting
+    fn orbit(eccentricity: f32) -> (f32, f32) {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.init_resource::<TimeScale>();
+        world.init_resource::<PhysicsConfig>();
+        world.spawn((Transform::default(), test_sun(40.)));
+        let start = Vec2::new(300., 0.);
+        let g_force = world.resource::<PhysicsConfig>().g_force;
+        let mut rocket = test_rocket(0);
+        rocket.velocity = orbit_velocity(start, Vec2::ZERO, g_force, eccentricity);
+        let entity = world
+            .spawn((Transform::from_translation(start.extend(0.)), rocket, RocketInput::default()))
+            .id();
+
+        let (mut farthest, mut closest) = (0_f32, f32::MAX);
+        for _ in 0..15 * PHYSICS_HZ as usize {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(DELTA));
+            world.run_system_once(move_rockets);
+            world.run_system_once(gravitational_pull);
+            let distance = world.get::<Transform>(entity).unwrap().translation.length();
+            farthest = farthest.max(distance);
+            closest = closest.min(distance);
         }
+        (farthest, closest)
+    }
+
+    #[test]
+    fn a_rocket_put_into_orbit_keeps_its_distance() {
+        let (farthest, closest) = orbit(0.);
+        assert!(farthest < 303. && closest > 297., "{closest}..{farthest}");
     }
 
-    for (_, mut rocket, mut transform) in entities.iter_mut() {
-        handle_rocket_movement(&time, &keys, &mut rocket, &mut transform);
+    #[test]
+    fn an_eccentric_orbit_swings_further_out() {
+        // Starts at the closest point of an ellipse reaching out to 450
+        let (farthest, closest) = orbit(0.2);
+        assert!(closest > 297., "{closest}");
+        assert!((farthest - 450.).abs() < 10., "{farthest}");
     }
 }
@@ -0,0 +1,469 @@
+use bevy::{app::AppExit, prelude::*};
+use std::{fmt::Write as _, fs, path::PathBuf};
+
+use crate::bullet::check_bullet_coll;
+use crate::game_state::{in_match, not_paused, reset_match, GameState};
+use crate::rng::{random_seed, GameRng, NextSeed};
+use crate::rocket::{Rocket, RocketInput, MAX_PLAYERS};
+use crate::rollback::save_frame;
+
+const DEFAULT_EXPORT_PATH: &str = "match_log.csv";
+const CSV_HEADER: &str = "tick,player,action";
+const SEED_PREFIX: &str = "# seed ";
+
+/// Records every input change as a `tick,player,action` timeline that can be
+/// exported with `--export-log <path>` (on exit) or F7, and played back with `--replay-log <path>`.
+/// Ticks are fixed timesteps and the log carries the match seed, so a replay
+/// reproduces the match exactly. Every match starts a fresh log and seed.
+pub struct MatchLogPlugin;
+
+impl Plugin for MatchLogPlugin {
+    fn build(&self, app: &mut App) {
+        let export_path = arg_value("--export-log").map(PathBuf::from);
+        let replay = arg_value("--replay-log").and_then(|path| {
+            fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|contents| parse_csv(&contents))
+                .map_err(|err| warn!("Could not load replay {path}: {err}"))
+                .ok()
+        });
+
+        app.insert_resource(MatchLog {
+            export_path,
+            ..default()
+        });
+        if let Some((seed, events)) = replay {
+            app.insert_resource(ReplayInputs {
+                seed,
+                events,
+                ..default()
+            });
+        }
+        app.add_systems(OnEnter(GameState::Playing), start_match_log.before(reset_match))
+        .add_systems(
+            FixedUpdate,
+            (replay_inputs.run_if(resource_exists::<ReplayInputs>), record_inputs)
+                .chain()
+                .run_if(in_match)
+                .run_if(not_paused)
+                // Logs the inputs a rollback saved the step with
+                .after(save_frame)
+                .before(check_bullet_coll),
+        )
+        .add_systems(Last, export_log);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    ThrustOn,
+    ThrustOff,
+    RotateLeftOn,
+    RotateLeftOff,
+    RotateRightOn,
+    RotateRightOff,
+    ReloadOn,
+    ReloadOff,
+    BoostOn,
+    BoostOff,
+    BrakeOn,
+    BrakeOff,
+    ShieldOn,
+    ShieldOff,
+    Fire,
+    CycleWeapon,
+    Hyperspace,
+    // Mouse aim moved to this world position, logged as `aim,x,y`
+    Aim(Vec2),
+    AimOff,
+    // How far a stick and trigger are pushed, logged as `analog,x,y`
+    Analog(Vec2),
+    AnalogOff,
+}
+
+impl Action {
+    // Every action without a position
+    const ALL: [Action; 19] = [
+        Action::ThrustOn,
+        Action::ThrustOff,
+        Action::RotateLeftOn,
+        Action::RotateLeftOff,
+        Action::RotateRightOn,
+        Action::RotateRightOff,
+        Action::ReloadOn,
+        Action::ReloadOff,
+        Action::BoostOn,
+        Action::BoostOff,
+        Action::BrakeOn,
+        Action::BrakeOff,
+        Action::ShieldOn,
+        Action::ShieldOff,
+        Action::Fire,
+        Action::CycleWeapon,
+        Action::Hyperspace,
+        Action::AimOff,
+        Action::AnalogOff,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::ThrustOn => "thrust_on",
+            Action::ThrustOff => "thrust_off",
+            Action::RotateLeftOn => "rotate_left_on",
+            Action::RotateLeftOff => "rotate_left_off",
+            Action::RotateRightOn => "rotate_right_on",
+            Action::RotateRightOff => "rotate_right_off",
+            Action::ReloadOn => "reload_on",
+            Action::ReloadOff => "reload_off",
+            Action::BoostOn => "boost_on",
+            Action::BoostOff => "boost_off",
+            Action::BrakeOn => "brake_on",
+            Action::BrakeOff => "brake_off",
+            Action::ShieldOn => "shield_on",
+            Action::ShieldOff => "shield_off",
+            Action::Fire => "fire",
+            Action::CycleWeapon => "cycle_weapon",
+            Action::Hyperspace => "hyperspace",
+            Action::Aim(_) => "aim",
+            Action::AimOff => "aim_off",
+            Action::Analog(_) => "analog",
+            Action::AnalogOff => "analog_off",
+        }
+    }
+
+    /// Reads an action from its name and, for `aim` and `analog`, the two
+    /// fields after it
+    pub fn parse<'a>(name: &str, mut fields: impl Iterator<Item = &'a str>) -> Option<Self> {
+        let with_position: [fn(Vec2) -> Action; 2] = [Action::Aim, Action::Analog];
+        if let Some(action) = with_position
+            .into_iter()
+            .find(|action| action(Vec2::ZERO).as_str() == name)
+        {
+            let x = fields.next()?.parse().ok()?;
+            let y = fields.next()?.parse().ok()?;
+            return Some(action(Vec2::new(x, y)));
+        }
+        Self::ALL.into_iter().find(|action| action.as_str() == name)
+    }
+
+    /// The position an `aim` or `analog` action carries
+    fn position(self) -> Option<Vec2> {
+        match self {
+            Action::Aim(position) | Action::Analog(position) => Some(position),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MatchEvent {
+    pub tick: u64,
+    pub player: usize,
+    pub action: Action,
+}
+
+#[derive(Resource, Default)]
+pub struct MatchLog {
+    pub tick: u64,
+    pub events: Vec<MatchEvent>,
+    pub export_path: Option<PathBuf>,
+    last_inputs: [RocketInput; MAX_PLAYERS],
+}
+
+/// How far a `MatchLog` had got, see `MatchLog::rewind`
+#[derive(Clone, Copy)]
+pub struct LogMark {
+    tick: u64,
+    len: usize,
+    last_inputs: [RocketInput; MAX_PLAYERS],
+}
+
+impl MatchLog {
+    pub fn mark(&self) -> LogMark {
+        LogMark {
+            tick: self.tick,
+            len: self.events.len(),
+            last_inputs: self.last_inputs,
+        }
+    }
+
+    /// Forgets everything logged since `mark`, for steps that are run again
+    pub fn rewind(&mut self, mark: LogMark) {
+        self.tick = mark.tick;
+        self.events.truncate(mark.len);
+        self.last_inputs = mark.last_inputs;
+    }
+}
+
+/// Inputs loaded from an exported log, fed to the rockets instead of the keyboard
+#[derive(Resource, Default)]
+pub struct ReplayInputs {
+    seed: Option<u64>,
+    events: Vec<MatchEvent>,
+    cursor: usize,
+    state: [RocketInput; MAX_PLAYERS],
+}
+
+pub fn to_csv(seed: u64, events: &[MatchEvent]) -> String {
+    let mut csv = format!("{SEED_PREFIX}{seed}\n{CSV_HEADER}\n");
+    for event in events {
+        let _ = write!(csv, "{},{},{}", event.tick, event.player, event.action.as_str());
+        // Floats print back to the exact same value, so an aimed shot replays exactly
+        if let Some(position) = event.action.position() {
+            let _ = write!(csv, ",{},{}", position.x, position.y);
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Reads the seed line, if any, and the events of an exported log
+pub fn parse_csv(contents: &str) -> Result<(Option<u64>, Vec<MatchEvent>), String> {
+    let seed = contents
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(SEED_PREFIX))
+        .map(|seed| {
+            seed.trim()
+                .parse()
+                .map_err(|_| format!("invalid seed {seed:?}"))
+        })
+        .transpose()?;
+    let events = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && line != CSV_HEADER && !line.starts_with('#')
+        })
+        .map(|(number, line)| {
+            let invalid = || format!("line {}: invalid entry {line:?}", number + 1);
+            let mut fields = line.trim().split(',');
+            let tick = fields.next().and_then(|tick| tick.parse().ok());
+            let player = fields.next().and_then(|player| player.parse().ok());
+            let action = fields
+                .next()
+                .and_then(|name| Action::parse(name, &mut fields));
+            match (tick, player, action, fields.next()) {
+                (Some(tick), Some(player), Some(action), None) if player < MAX_PLAYERS => {
+                    Ok(MatchEvent {
+                        tick,
+                        player,
+                        action,
+                    })
+                }
+                _ => Err(invalid()),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((seed, events))
+}
+
+/// Clears the previous match from the log and seeds the new one before anything
+/// is spawned, a replay starts every match over from the recorded seed
+fn start_match_log(
+    mut log: ResMut<MatchLog>,
+    mut rng: ResMut<GameRng>,
+    mut next_seed: ResMut<NextSeed>,
+    replay: Option<ResMut<ReplayInputs>>,
+) {
+    log.tick = 0;
+    log.events.clear();
+    log.last_inputs = default();
+    let mut seed = next_seed.0.take();
+    if let Some(mut replay) = replay {
+        replay.cursor = 0;
+        replay.state = default();
+        seed = replay.seed.or(seed);
+    }
+    rng.reseed(seed.unwrap_or_else(random_seed));
+}
+
+fn replay_inputs(
+    log: Res<MatchLog>,
+    mut replay: ResMut<ReplayInputs>,
+    mut rockets: Query<(&Rocket, &mut RocketInput)>,
+) {
+    let replay = &mut *replay;
+    for input in replay.state.iter_mut() {
+        input.fire = false;
+        input.cycle_weapon = false;
+        input.hyperspace = false;
+    }
+    while let Some(event) = replay.events.get(replay.cursor) {
+        if event.tick > log.tick {
+            break;
+        }
+        let input = &mut replay.state[event.player];
+        match event.action {
+            Action::ThrustOn => input.thrust = true,
+            Action::ThrustOff => input.thrust = false,
+            Action::RotateLeftOn => input.rotate_left = true,
+            Action::RotateLeftOff => input.rotate_left = false,
+            Action::RotateRightOn => input.rotate_right = true,
+            Action::RotateRightOff => input.rotate_right = false,
+            Action::ReloadOn => input.reload = true,
+            Action::ReloadOff => input.reload = false,
+            Action::BoostOn => input.boost = true,
+            Action::BoostOff => input.boost = false,
+            Action::BrakeOn => input.brake = true,
+            Action::BrakeOff => input.brake = false,
+            Action::ShieldOn => input.shield = true,
+            Action::ShieldOff => input.shield = false,
+            Action::Fire => input.fire = true,
+            Action::CycleWeapon => input.cycle_weapon = true,
+            Action::Hyperspace => input.hyperspace = true,
+            Action::Aim(aim) => input.aim = Some(aim),
+            Action::AimOff => input.aim = None,
+            Action::Analog(analog) => input.analog = Some(analog),
+            Action::AnalogOff => input.analog = None,
+        }
+        replay.cursor += 1;
+    }
+    for (rocket, mut input) in rockets.iter_mut() {
+        *input = replay.state[rocket.player];
+    }
+}
+
+fn record_inputs(mut log: ResMut<MatchLog>, rockets: Query<(&Rocket, &RocketInput)>) {
+    let log = &mut *log;
+    for (rocket, input) in rockets.iter() {
+        let last = &mut log.last_inputs[rocket.player];
+        let changes = [
+            (input.thrust, last.thrust, Action::ThrustOn, Action::ThrustOff),
+            (
+                input.rotate_left,
+                last.rotate_left,
+                Action::RotateLeftOn,
+                Action::RotateLeftOff,
+            ),
+            (
+                input.rotate_right,
+                last.rotate_right,
+                Action::RotateRightOn,
+                Action::RotateRightOff,
+            ),
+            (input.reload, last.reload, Action::ReloadOn, Action::ReloadOff),
+            (input.boost, last.boost, Action::BoostOn, Action::BoostOff),
+            (input.brake, last.brake, Action::BrakeOn, Action::BrakeOff),
+            (input.shield, last.shield, Action::ShieldOn, Action::ShieldOff),
+        ];
+        for (now, before, on, off) in changes {
+            if now != before {
+                log.events.push(MatchEvent {
+                    tick: log.tick,
+                    player: rocket.player,
+                    action: if now { on } else { off },
+                });
+            }
+        }
+        if input.aim != last.aim {
+            log.events.push(MatchEvent {
+                tick: log.tick,
+                player: rocket.player,
+                action: input.aim.map_or(Action::AimOff, Action::Aim),
+            });
+        }
+        if input.analog != last.analog {
+            log.events.push(MatchEvent {
+                tick: log.tick,
+                player: rocket.player,
+                action: input.analog.map_or(Action::AnalogOff, Action::Analog),
+            });
+        }
+        // One-frame presses, logged on the frame they happen
+        let presses = [
+            (input.fire, Action::Fire),
+            (input.cycle_weapon, Action::CycleWeapon),
+            (input.hyperspace, Action::Hyperspace),
+        ];
+        for (pressed, action) in presses {
+            if pressed {
+                log.events.push(MatchEvent {
+                    tick: log.tick,
+                    player: rocket.player,
+                    action,
+                });
+            }
+        }
+        *last = *input;
+    }
+    log.tick += 1;
+}
+
+/// Writes the log when F7 is pressed, or on exit if `--export-log` was given
+fn export_log(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut exits: EventReader<AppExit>,
+    log: Res<MatchLog>,
+    rng: Res<GameRng>,
+) {
+    let exiting = exits.read().next().is_some() && log.export_path.is_some();
+    if !exiting && !keys.just_pressed(KeyCode::F7) {
+        return;
+    }
+    let path = log
+        .export_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_EXPORT_PATH));
+    match fs::write(&path, to_csv(rng.seed(), &log.events)) {
+        Ok(()) => info!("Exported {} match events to {}", log.events.len(), path.display()),
+        Err(err) => warn!("Could not export the match log to {}: {err}", path.display()),
+    }
+}
+
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    args.next()?;
+    args.next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(tick: u64, player: usize, action: Action) -> MatchEvent {
+        MatchEvent {
+            tick,
+            player,
+            action,
+        }
+    }
+
+    #[test]
+    fn csv_round_trips_every_action() {
+        let mut events: Vec<MatchEvent> = Action::ALL
+            .into_iter()
+            .enumerate()
+            .map(|(tick, action)| event(tick as u64, tick % MAX_PLAYERS, action))
+            .collect();
+        events.push(event(40, 1, Action::Aim(Vec2::new(-123.456, 0.1))));
+        events.push(event(41, 2, Action::Analog(Vec2::new(-0.3, 1. / 3.))));
+
+        let csv = to_csv(42, &events);
+        assert!(csv.contains("40,1,aim,-123.456,0.1\n"));
+        assert_eq!(parse_csv(&csv), Ok((Some(42), events)));
+    }
+
+    #[test]
+    fn csv_without_a_seed_line_still_parses() {
+        let csv = "tick,player,action\n\n3,0,thrust_on\n# a comment\n5,1,fire\n";
+        let events = vec![event(3, 0, Action::ThrustOn), event(5, 1, Action::Fire)];
+        assert_eq!(parse_csv(csv), Ok((None, events)));
+    }
+
+    #[test]
+    fn invalid_lines_are_reported() {
+        for line in [
+            "3,0,warp",
+            "3,9,fire",
+            "x,0,fire",
+            "3,0,fire,1",
+            "3,0,aim,1",
+            "3,0,aim,1,y",
+        ] {
+            let err = parse_csv(line).unwrap_err();
+            assert!(err.starts_with("line 1"), "{line}: {err}");
+        }
+        assert!(parse_csv("# seed x\n").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+
+use crate::bullet::Bullet;
+use crate::game_state::GameState;
+use crate::rocket::{Arena, Rocket, HALF_HEIGHT, HALF_WIDTH};
+use crate::sun::Sun;
+
+// Same aspect ratio as the arena
+const MINIMAP_WIDTH: f32 = 160.;
+const MINIMAP_HEIGHT: f32 = MINIMAP_WIDTH * HALF_HEIGHT / HALF_WIDTH;
+const MINIMAP_MARGIN: f32 = 8.;
+const MINIMAP_BACKGROUND: Color = Color::srgba(0., 0., 0., 0.5);
+const ROCKET_DOT: f32 = 6.;
+const SUN_DOT: f32 = 10.;
+const SUN_DOT_COLOR: Color = Color::srgb(1., 0.85, 0.2);
+const BULLET_DOT: f32 = 3.;
+const BULLET_DOT_COLOR: Color = Color::srgba(1., 1., 1., 0.35);
+
+/// Frame of the minimap in the bottom right corner. It is drawn by the UI pass,
+/// which runs after the post processing, so it isn't curved or pixelated.
+#[derive(Component)]
+pub struct Minimap;
+
+/// Dot following `target` on the minimap, removed once the target is gone
+#[derive(Component)]
+pub struct MinimapDot {
+    pub target: Entity,
+    pub size: f32,
+}
+
+pub fn add_minimap(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(MINIMAP_MARGIN),
+                right: Val::Px(MINIMAP_MARGIN),
+                width: Val::Px(MINIMAP_WIDTH),
+                height: Val::Px(MINIMAP_HEIGHT),
+                ..default()
+            },
+            background_color: MINIMAP_BACKGROUND.into(),
+            ..default()
+        },
+        Minimap,
+    ));
+}
+
+/// Gives every new rocket, sun and bullet its dot
+pub fn spawn_minimap_dots(
+    mut commands: Commands,
+    minimap: Query<Entity, With<Minimap>>,
+    rockets: Query<(Entity, &Rocket), Added<Rocket>>,
+    suns: Query<Entity, Added<Sun>>,
+    bullets: Query<Entity, Added<Bullet>>,
+) {
+    let Ok(minimap) = minimap.get_single() else {
+        return;
+    };
+    let dots = rockets
+        .iter()
+        .map(|(entity, rocket)| (entity, rocket.color, ROCKET_DOT))
+        .chain(suns.iter().map(|entity| (entity, SUN_DOT_COLOR, SUN_DOT)))
+        .chain(bullets.iter().map(|entity| (entity, BULLET_DOT_COLOR, BULLET_DOT)));
+
+    commands.entity(minimap).with_children(|parent| {
+        for (target, color, size) in dots {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(size),
+                        height: Val::Px(size),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    border_radius: BorderRadius::MAX,
+                    ..default()
+                },
+                MinimapDot { target, size },
+            ));
+        }
+    });
+}
+
+pub fn update_minimap(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    arena: Res<Arena>,
+    mut minimap: Query<&mut Visibility, With<Minimap>>,
+    transforms: Query<&Transform>,
+    mut dots: Query<(Entity, &MinimapDot, &mut Style)>,
+) {
+    // Nothing to show before the first match starts
+    let visibility = if *state.get() == GameState::Menu {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for mut minimap_visibility in &mut minimap {
+        *minimap_visibility = visibility;
+    }
+
+    for (entity, dot, mut style) in &mut dots {
+        let Ok(transform) = transforms.get(dot.target) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        let position = minimap_position(transform.translation.truncate(), &arena);
+        style.left = Val::Px(position.x - dot.size / 2.);
+        style.top = Val::Px(position.y - dot.size / 2.);
+    }
+}
+
+/// Maps arena coordinates, centred with y up, to pixels from the minimap's top left corner
+fn minimap_position(world: Vec2, arena: &Arena) -> Vec2 {
+    // Rockets can briefly leave the arena before they wrap, keep their dot on the edge
+    let x = (world.x / arena.half_width).clamp(-1., 1.) * 0.5 + 0.5;
+    let y = 0.5 - (world.y / arena.half_height).clamp(-1., 1.) * 0.5;
+    Vec2::new(x * MINIMAP_WIDTH, y * MINIMAP_HEIGHT)
+}
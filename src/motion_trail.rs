@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::rocket::{Rocket, HALF_WIDTH};
+
+// Rockets that barely move, or sit still while paused, don't add points
+const MIN_POINT_SPACING: f32 = 2.;
+// A jump longer than this is a wrap to the opposite edge, not flight
+const MAX_POINT_SPACING: f32 = HALF_WIDTH;
+// Opacity of the newest end of the trail, it fades out to nothing at the tail
+const HEAD_ALPHA: f32 = 0.6;
+
+/// Fading line behind every rocket showing where it has just been
+#[derive(Resource)]
+pub struct MotionTrailSettings {
+    pub enabled: bool,
+    // Points kept per rocket, so memory per trail stays fixed
+    pub length: usize,
+}
+
+impl Default for MotionTrailSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            length: 40,
+        }
+    }
+}
+
+/// Recent positions of a rocket, newest last. It lives on the rocket, so it
+/// goes away with it and a respawned rocket starts a fresh trail.
+#[derive(Component, Default)]
+pub struct MotionTrail {
+    points: VecDeque<Vec2>,
+}
+
+pub fn record_motion_trails(
+    settings: Res<MotionTrailSettings>,
+    mut rockets: Query<(&Transform, &mut MotionTrail), With<Rocket>>,
+) {
+    for (transform, mut trail) in rockets.iter_mut() {
+        let position = transform.translation.truncate();
+        match trail.points.back() {
+            Some(last) if last.distance(position) < MIN_POINT_SPACING => continue,
+            // Drawn across the arena otherwise
+            Some(last) if last.distance(position) > MAX_POINT_SPACING => trail.points.clear(),
+            _ => {}
+        }
+        trail.points.push_back(position);
+        while trail.points.len() > settings.length {
+            trail.points.pop_front();
+        }
+    }
+}
+
+pub fn draw_motion_trails(
+    mut gizmos: Gizmos,
+    settings: Res<MotionTrailSettings>,
+    rockets: Query<(&Rocket, &MotionTrail)>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    for (rocket, trail) in rockets.iter() {
+        let count = trail.points.len();
+        if count < 2 {
+            continue;
+        }
+        let points = trail.points.iter().enumerate().map(|(index, point)| {
+            let alpha = HEAD_ALPHA * index as f32 / (count - 1) as f32;
+            (*point, rocket.color.with_alpha(alpha))
+        });
+        gizmos.linestrip_gradient_2d(points);
+    }
+}
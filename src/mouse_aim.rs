@@ -0,0 +1,62 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::game_state::{in_match, not_paused};
+use crate::rocket::{read_player_input, Rocket, RocketInput};
+
+// The player at the keyboard in a solo match
+const MOUSE_PLAYER: usize = 0;
+
+/// Lets the first player shoot towards the mouse cursor, and fire with the
+/// left button, started with `--mouse-aim`
+pub struct MouseAimPlugin;
+
+impl Plugin for MouseAimPlugin {
+    fn build(&self, app: &mut App) {
+        let enabled = std::env::args().any(|arg| arg == "--mouse-aim");
+        app.insert_resource(MouseAim(enabled)).add_systems(
+            Update,
+            (enable_mouse_aim, read_mouse_aim)
+                .chain()
+                .run_if(in_match)
+                .run_if(not_paused)
+                .after(read_player_input),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MouseAim(pub bool);
+
+/// Turns on mouse aim for every new rocket of the mouse player, including respawned ones
+fn enable_mouse_aim(mouse_aim: Res<MouseAim>, mut rockets: Query<&mut Rocket, Added<Rocket>>) {
+    for mut rocket in rockets.iter_mut() {
+        if rocket.player == MOUSE_PLAYER {
+            rocket.mouse_aim = mouse_aim.0;
+        }
+    }
+}
+
+fn read_mouse_aim(
+    buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut rockets: Query<(&Rocket, &mut RocketInput)>,
+) {
+    // A cursor outside the window leaves the last aim in place
+    let cursor = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .zip(cameras.get_single().ok())
+        .and_then(|(cursor, (camera, transform))| camera.viewport_to_world_2d(transform, cursor));
+
+    for (rocket, mut input) in rockets.iter_mut() {
+        if !rocket.mouse_aim {
+            continue;
+        }
+        if cursor.is_some() {
+            input.aim = cursor;
+        }
+        input.fire |= buttons.just_pressed(MouseButton::Left);
+    }
+}
@@ -0,0 +1,1207 @@
+use bevy::prelude::*;
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use crate::asteroid::Asteroid;
+use crate::bindings::KeyBindings;
+use crate::bullet::Bullet;
+use crate::debris::{spawn_debris, DebrisRng};
+use crate::effects::spawn_explosion;
+use crate::game_state::GameState;
+use crate::rocket::{
+    clear_presses, read_player_input, spawn_rocket, PlayerCount, Rocket, RocketInput, ScreenWrap,
+    MAX_PLAYERS,
+};
+use crate::score::MatchState;
+use crate::sun::PhysicsConfig;
+
+const DEFAULT_PORT: u16 = 7777;
+// Large enough for a snapshot of a busy arena
+const MAX_PACKET_SIZE: usize = 65_507;
+const LOBBY_COLOR: Color = Color::srgba(1., 1., 1., 0.8);
+// A client sends its keys every frame, one this quiet has gone
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Matches of up to `MAX_PLAYERS` over UDP, started with `--host [port]` on one
+/// machine and `--join <address>[:port]` on the others. The host runs the whole
+/// simulation and streams the result every fixed step, each client only sends
+/// the keys of its player and mirrors what it receives. Players the host has
+/// no client for fly from its keyboard.
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let Some(session) = NetSession::from_args() else {
+            return;
+        };
+        app.insert_resource(session)
+            .add_sub_state::<LobbyState>()
+            .add_systems(Startup, add_lobby_text)
+            .add_systems(Update, (update_lobby_state, update_lobby_text).chain())
+            .add_systems(
+                Update,
+                (
+                    (receive_inputs, apply_remote_input)
+                        .chain()
+                        .after(read_player_input)
+                        .run_if(is_host),
+                    (send_local_input, receive_snapshots).chain().run_if(is_client),
+                ),
+            )
+            .add_systems(FixedUpdate, send_snapshot.after(clear_presses).run_if(is_host));
+    }
+}
+
+/// Where the menu is at in gathering the players of a network match
+#[derive(SubStates, Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+#[source(GameState = GameState::Menu)]
+pub enum LobbyState {
+    // The host waits for someone to join, a client for the host to answer
+    #[default]
+    Waiting,
+    // The host can start the match, a client has been given its player
+    Ready,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NetRole {
+    Host,
+    Client,
+}
+
+/// A client that joined the host
+struct Peer {
+    address: SocketAddr,
+    player: usize,
+    // Latest input of its player, presses are kept until a rocket takes them
+    input: RocketInput,
+    // Real time of its last packet
+    last_heard: Duration,
+}
+
+#[derive(Resource)]
+pub struct NetSession {
+    pub role: NetRole,
+    socket: UdpSocket,
+    // The host, for a client
+    host: Option<SocketAddr>,
+    // The clients, for the host. They can only join on the menu.
+    peers: Vec<Peer>,
+    // The player a client flies, given by the host when it joins
+    player: Option<usize>,
+    // Why the host last turned a client away, until it lets it in
+    refused: Option<Refusal>,
+    // Fixed step of the last snapshot sent or applied, older ones arriving late are dropped
+    tick: u64,
+    // Kept between calls so sending and receiving don't allocate every frame
+    send_buffer: Vec<u8>,
+    receive_buffer: Vec<u8>,
+}
+
+impl NetSession {
+    fn from_args() -> Option<Self> {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let session = match arg.as_str() {
+                "--host" => {
+                    let port = args.next().and_then(|port| port.parse().ok());
+                    Self::host(port.unwrap_or(DEFAULT_PORT))
+                }
+                "--join" => match args.next() {
+                    Some(address) => Self::join(&address),
+                    None => Err("--join needs the host's address".into()),
+                },
+                _ => continue,
+            };
+            return session
+                .map_err(|err| warn!("Playing offline, could not start the network: {err}"))
+                .ok();
+        }
+        None
+    }
+
+    fn host(port: u16) -> Result<Self, String> {
+        let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(|err| err.to_string())?;
+        Self::new(NetRole::Host, socket, None)
+    }
+
+    fn join(address: &str) -> Result<Self, String> {
+        let with_port = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{address}:{DEFAULT_PORT}")
+        };
+        let host = with_port
+            .to_socket_addrs()
+            .map_err(|err| err.to_string())?
+            .next()
+            .ok_or_else(|| format!("{address} does not resolve"))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|err| err.to_string())?;
+        Self::new(NetRole::Client, socket, Some(host))
+    }
+
+    fn new(role: NetRole, socket: UdpSocket, host: Option<SocketAddr>) -> Result<Self, String> {
+        socket.set_nonblocking(true).map_err(|err| err.to_string())?;
+        Ok(Self {
+            role,
+            socket,
+            host,
+            peers: Vec::new(),
+            player: None,
+            refused: None,
+            tick: 0,
+            send_buffer: Vec::new(),
+            receive_buffer: vec![0; MAX_PACKET_SIZE],
+        })
+    }
+
+    fn send(&mut self, packet: &Packet, to: SocketAddr) {
+        self.send_buffer.clear();
+        packet.encode(&mut self.send_buffer);
+        if let Err(err) = self.socket.send_to(&self.send_buffer, to) {
+            warn!("Could not send to {to}: {err}");
+        }
+    }
+
+    /// Sends the packet to the host, or from the host to every client
+    fn broadcast(&mut self, packet: &Packet) {
+        let targets: Vec<SocketAddr> = match self.role {
+            NetRole::Host => self.peers.iter().map(|peer| peer.address).collect(),
+            NetRole::Client => self.host.into_iter().collect(),
+        };
+        for target in targets {
+            self.send(packet, target);
+        }
+    }
+
+    /// Every packet waiting on the socket, unreadable ones are skipped
+    fn receive(&mut self) -> Vec<(SocketAddr, Packet)> {
+        let mut packets = Vec::new();
+        loop {
+            match self.socket.recv_from(&mut self.receive_buffer) {
+                Ok((len, from)) => match Packet::decode(&self.receive_buffer[..len]) {
+                    Ok(packet) => packets.push((from, packet)),
+                    Err(err) => warn!("Ignoring a packet from {from}: {err}"),
+                },
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("Could not receive: {err}");
+                    break;
+                }
+            }
+        }
+        packets
+    }
+
+    /// The lowest player no client flies yet, the first one stays on the host
+    fn free_player(&self) -> Option<usize> {
+        (1..MAX_PLAYERS).find(|player| self.peers.iter().all(|peer| peer.player != *player))
+    }
+
+    /// Forgets the clients the host hasn't heard from in `PEER_TIMEOUT`
+    fn drop_silent_peers(&mut self, now: Duration) {
+        self.peers.retain(|peer| {
+            let alive = now.saturating_sub(peer.last_heard) < PEER_TIMEOUT;
+            if !alive {
+                let (address, player) = (peer.address, peer.player + 1);
+                info!("{address} timed out, player {player} flies from the keyboard");
+            }
+            alive
+        });
+    }
+}
+
+/// Run conditions, `runs_simulation` is also true without a network session
+pub fn is_host(session: Option<Res<NetSession>>) -> bool {
+    session.is_some_and(|session| session.role == NetRole::Host)
+}
+
+pub fn is_client(session: Option<Res<NetSession>>) -> bool {
+    session.is_some_and(|session| session.role == NetRole::Client)
+}
+
+pub fn runs_simulation(session: Option<Res<NetSession>>) -> bool {
+    !is_client(session)
+}
+
+/// Whether Enter may start a match, a host has to wait for someone to join
+pub fn lobby_ready(lobby: Option<Res<State<LobbyState>>>) -> bool {
+    lobby.is_none_or(|lobby| *lobby.get() == LobbyState::Ready)
+}
+
+#[derive(Debug, PartialEq)]
+enum Packet {
+    // Sent by a client until the host has given it a player
+    Join,
+    Welcome { player: usize },
+    // The answer to a join the host can't take
+    Refused(Refusal),
+    Input(RocketInput),
+    Snapshot(Snapshot),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Refusal {
+    // Clients can only join on the menu
+    Started,
+    Full,
+}
+
+/// Everything the clients draw, taken from the host at the end of a fixed step
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    tick: u64,
+    state: GameState,
+    players: usize,
+    // Grows in overtime
+    sun_radius: f32,
+    wins: [u32; MAX_PLAYERS],
+    kills: [u32; MAX_PLAYERS],
+    bonus: [u32; MAX_PLAYERS],
+    rockets: Vec<RocketState>,
+    bullets: Vec<BodyState>,
+    asteroids: Vec<BodyState>,
+}
+
+#[derive(Debug, PartialEq)]
+struct RocketState {
+    player: usize,
+    translation: Vec3,
+    // Rockets only turn about z, so the angle is all that is sent
+    angle: f32,
+    velocity: Vec2,
+    speed: f32,
+    health: f32,
+    ammo: u32,
+    fuel: f32,
+    reload_progress: f32,
+    shield_energy: f32,
+    shield_up: bool,
+    // Drives the thruster flame
+    input: RocketInput,
+}
+
+/// A bullet or an asteroid, `id` is its entity on the host
+#[derive(Debug, PartialEq)]
+struct BodyState {
+    id: u64,
+    translation: Vec3,
+    angle: f32,
+    velocity: Vec2,
+    radius: f32,
+}
+
+// Tags in front of every packet
+const JOIN: u8 = 0;
+const WELCOME: u8 = 1;
+const INPUT: u8 = 2;
+const SNAPSHOT: u8 = 3;
+const REFUSED: u8 = 4;
+
+// Bits of an input's flags, the aim and the analog input follow as two floats
+// each when they are set
+const INPUT_FLAGS: [fn(&mut RocketInput) -> &mut bool; 10] = [
+    |input| &mut input.thrust,
+    |input| &mut input.rotate_left,
+    |input| &mut input.rotate_right,
+    |input| &mut input.fire,
+    |input| &mut input.reload,
+    |input| &mut input.cycle_weapon,
+    |input| &mut input.boost,
+    |input| &mut input.brake,
+    |input| &mut input.hyperspace,
+    |input| &mut input.shield,
+];
+const AIM_FLAG: u16 = 1 << INPUT_FLAGS.len();
+const ANALOG_FLAG: u16 = AIM_FLAG << 1;
+
+/// Appends little endian values to a packet
+struct Writer<'a>(&'a mut Vec<u8>);
+
+impl Writer<'_> {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn vec2(&mut self, value: Vec2) {
+        self.f32(value.x);
+        self.f32(value.y);
+    }
+
+    fn vec3(&mut self, value: Vec3) {
+        self.vec2(value.truncate());
+        self.f32(value.z);
+    }
+
+    fn input(&mut self, input: &RocketInput) {
+        let mut copy = *input;
+        let mut flags = INPUT_FLAGS
+            .iter()
+            .enumerate()
+            .filter(|(_, flag)| *flag(&mut copy))
+            .fold(0, |flags, (bit, _)| flags | 1 << bit);
+        if input.aim.is_some() {
+            flags |= AIM_FLAG;
+        }
+        if input.analog.is_some() {
+            flags |= ANALOG_FLAG;
+        }
+        self.u16(flags);
+        for value in [input.aim, input.analog].into_iter().flatten() {
+            self.vec2(value);
+        }
+    }
+
+    fn body(&mut self, body: &BodyState) {
+        self.u64(body.id);
+        self.vec3(body.translation);
+        self.f32(body.angle);
+        self.vec2(body.velocity);
+        self.f32(body.radius);
+    }
+}
+
+/// Reads back what `Writer` wrote, `None` once the packet runs out
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|[value]| value)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+
+    fn vec2(&mut self) -> Option<Vec2> {
+        Some(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    fn vec3(&mut self) -> Option<Vec3> {
+        Some(self.vec2()?.extend(self.f32()?))
+    }
+
+    fn player(&mut self) -> Option<usize> {
+        Some(self.u8()? as usize).filter(|player| *player < MAX_PLAYERS)
+    }
+
+    fn input(&mut self) -> Option<RocketInput> {
+        let flags = self.u16()?;
+        let mut input = RocketInput::default();
+        for (bit, flag) in INPUT_FLAGS.iter().enumerate() {
+            *flag(&mut input) = flags & 1 << bit != 0;
+        }
+        if flags & AIM_FLAG != 0 {
+            input.aim = Some(self.vec2()?);
+        }
+        if flags & ANALOG_FLAG != 0 {
+            input.analog = Some(self.vec2()?);
+        }
+        Some(input)
+    }
+
+    fn body(&mut self) -> Option<BodyState> {
+        Some(BodyState {
+            id: self.u64()?,
+            translation: self.vec3()?,
+            angle: self.f32()?,
+            velocity: self.vec2()?,
+            radius: self.f32()?,
+        })
+    }
+
+    fn scores(&mut self) -> Option<[u32; MAX_PLAYERS]> {
+        let mut scores = [0; MAX_PLAYERS];
+        for score in scores.iter_mut() {
+            *score = self.u32()?;
+        }
+        Some(scores)
+    }
+}
+
+fn encode_state(state: GameState) -> u8 {
+    match state {
+        GameState::Menu => 0,
+        GameState::Playing => 1,
+        GameState::Overtime => 2,
+        GameState::GameOver => 3,
+    }
+}
+
+fn decode_state(value: u8) -> Option<GameState> {
+    match value {
+        0 => Some(GameState::Menu),
+        1 => Some(GameState::Playing),
+        2 => Some(GameState::Overtime),
+        3 => Some(GameState::GameOver),
+        _ => None,
+    }
+}
+
+impl Packet {
+    /// Writes the packet as a tag byte followed by its fields
+    fn encode(&self, out: &mut Vec<u8>) {
+        let mut writer = Writer(out);
+        match self {
+            Packet::Join => writer.u8(JOIN),
+            Packet::Welcome { player } => {
+                writer.u8(WELCOME);
+                writer.u8(*player as u8);
+            }
+            Packet::Refused(refusal) => {
+                writer.u8(REFUSED);
+                writer.u8(match refusal {
+                    Refusal::Started => 0,
+                    Refusal::Full => 1,
+                });
+            }
+            Packet::Input(input) => {
+                writer.u8(INPUT);
+                writer.input(input);
+            }
+            Packet::Snapshot(snapshot) => {
+                writer.u8(SNAPSHOT);
+                writer.u64(snapshot.tick);
+                writer.u8(encode_state(snapshot.state));
+                writer.u8(snapshot.players as u8);
+                writer.f32(snapshot.sun_radius);
+                for scores in [&snapshot.wins, &snapshot.kills, &snapshot.bonus] {
+                    for score in scores {
+                        writer.u32(*score);
+                    }
+                }
+                writer.u8(snapshot.rockets.len() as u8);
+                for rocket in &snapshot.rockets {
+                    writer.u8(rocket.player as u8);
+                    writer.vec3(rocket.translation);
+                    writer.f32(rocket.angle);
+                    writer.vec2(rocket.velocity);
+                    writer.f32(rocket.speed);
+                    writer.f32(rocket.health);
+                    writer.u32(rocket.ammo);
+                    writer.f32(rocket.fuel);
+                    writer.f32(rocket.reload_progress);
+                    writer.f32(rocket.shield_energy);
+                    writer.u8(rocket.shield_up as u8);
+                    writer.input(&rocket.input);
+                }
+                for bodies in [&snapshot.bullets, &snapshot.asteroids] {
+                    writer.u16(bodies.len() as u16);
+                    for body in bodies {
+                        writer.body(body);
+                    }
+                }
+            }
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader(bytes);
+        let tag = reader.u8().ok_or("empty packet")?;
+        let packet = match tag {
+            JOIN => Some(Packet::Join),
+            WELCOME => reader.player().map(|player| Packet::Welcome { player }),
+            REFUSED => match reader.u8() {
+                Some(0) => Some(Packet::Refused(Refusal::Started)),
+                Some(1) => Some(Packet::Refused(Refusal::Full)),
+                _ => None,
+            },
+            INPUT => reader.input().map(Packet::Input),
+            SNAPSHOT => decode_snapshot(&mut reader).map(Packet::Snapshot),
+            _ => return Err(format!("unknown packet type {tag}")),
+        };
+        match packet {
+            Some(packet) if reader.0.is_empty() => Ok(packet),
+            Some(_) => Err("trailing bytes".into()),
+            None => Err("truncated or invalid packet".into()),
+        }
+    }
+}
+
+fn decode_snapshot(reader: &mut Reader) -> Option<Snapshot> {
+    let tick = reader.u64()?;
+    let state = decode_state(reader.u8()?)?;
+    let players = reader.u8()? as usize;
+    let sun_radius = reader.f32()?;
+    let wins = reader.scores()?;
+    let kills = reader.scores()?;
+    let bonus = reader.scores()?;
+    let rockets = (0..reader.u8()?)
+        .map(|_| {
+            Some(RocketState {
+                player: reader.player()?,
+                translation: reader.vec3()?,
+                angle: reader.f32()?,
+                velocity: reader.vec2()?,
+                speed: reader.f32()?,
+                health: reader.f32()?,
+                ammo: reader.u32()?,
+                fuel: reader.f32()?,
+                reload_progress: reader.f32()?,
+                shield_energy: reader.f32()?,
+                shield_up: reader.u8()? != 0,
+                input: reader.input()?,
+            })
+        })
+        .collect::<Option<_>>()?;
+    let bullets = (0..reader.u16()?).map(|_| reader.body()).collect::<Option<_>>()?;
+    let asteroids = (0..reader.u16()?).map(|_| reader.body()).collect::<Option<_>>()?;
+    Some(Snapshot {
+        tick,
+        state,
+        players,
+        sun_radius,
+        wins,
+        kills,
+        bonus,
+        rockets,
+        bullets,
+        asteroids,
+    })
+}
+
+/// Ties a mirrored bullet or asteroid to its entity on the host
+#[derive(Component)]
+pub struct NetId(u64);
+
+/// Lets clients join while the host is on the menu, each one takes the next
+/// free player, turns away the ones that come too late, collects the inputs of
+/// the ones that joined and drops those that went quiet
+fn receive_inputs(
+    mut session: ResMut<NetSession>,
+    state: Res<State<GameState>>,
+    time: Res<Time<Real>>,
+    mut players: ResMut<PlayerCount>,
+) {
+    let now = time.elapsed();
+    for (from, packet) in session.receive() {
+        let known = session.peers.iter().position(|peer| peer.address == from);
+        if let Some(index) = known {
+            session.peers[index].last_heard = now;
+        }
+        match (packet, known) {
+            (Packet::Join, Some(index)) => {
+                // The welcome was lost, the client asks again
+                let player = session.peers[index].player;
+                session.send(&Packet::Welcome { player }, from);
+            }
+            (Packet::Join, None) => match session.free_player() {
+                Some(_) if *state.get() != GameState::Menu => {
+                    session.send(&Packet::Refused(Refusal::Started), from);
+                }
+                Some(player) => {
+                    info!("{from} joined as player {}", player + 1);
+                    session.peers.push(Peer {
+                        address: from,
+                        player,
+                        input: RocketInput::default(),
+                        last_heard: now,
+                    });
+                    session.send(&Packet::Welcome { player }, from);
+                }
+                None => session.send(&Packet::Refused(Refusal::Full), from),
+            },
+            (Packet::Input(input), Some(index)) => {
+                let peer = &mut session.peers[index];
+                let last = peer.input;
+                peer.input = RocketInput {
+                    fire: input.fire || last.fire,
+                    cycle_weapon: input.cycle_weapon || last.cycle_weapon,
+                    hyperspace: input.hyperspace || last.hyperspace,
+                    ..input
+                };
+            }
+            _ => {}
+        }
+    }
+    session.drop_silent_peers(now);
+    // Enough rockets for everyone who joined, even if fewer players were picked
+    // on the menu. More can fly from the keyboard.
+    let joined = session.peers.iter().map(|peer| peer.player + 1).max().unwrap_or(1);
+    if players.0 < joined {
+        players.0 = joined;
+    }
+}
+
+/// Replaces the keyboard input of every remote player's rocket, one-frame
+/// presses it hasn't stepped on yet are kept like in `read_player_input`
+fn apply_remote_input(
+    mut session: ResMut<NetSession>,
+    mut rockets: Query<(&Rocket, &mut RocketInput)>,
+) {
+    for (rocket, mut input) in rockets.iter_mut() {
+        let Some(peer) = session.peers.iter().find(|peer| peer.player == rocket.player) else {
+            continue;
+        };
+        let mut next = peer.input;
+        next.fire |= input.fire;
+        next.cycle_weapon |= input.cycle_weapon;
+        next.hyperspace |= input.hyperspace;
+        *input = next;
+    }
+    for peer in session.peers.iter_mut() {
+        peer.input.fire = false;
+        peer.input.cycle_weapon = false;
+        peer.input.hyperspace = false;
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn send_snapshot(
+    mut session: ResMut<NetSession>,
+    state: Res<State<GameState>>,
+    players: Res<PlayerCount>,
+    match_state: Res<MatchState>,
+    rockets: Query<(&Rocket, &Transform, &RocketInput)>,
+    bullets: Query<(Entity, &Bullet, &Transform)>,
+    asteroids: Query<(Entity, &Asteroid, &Transform)>,
+    config: Res<PhysicsConfig>,
+) {
+    if session.peers.is_empty() {
+        return;
+    }
+    let angle = |transform: &Transform| transform.rotation.to_euler(EulerRot::XYZ).2;
+    session.tick += 1;
+    let snapshot = Snapshot {
+        tick: session.tick,
+        state: *state.get(),
+        players: players.0,
+        sun_radius: config.sun_radius,
+        wins: match_state.wins,
+        kills: match_state.kills,
+        bonus: match_state.bonus,
+        rockets: rockets
+            .iter()
+            .map(|(rocket, transform, input)| RocketState {
+                player: rocket.player,
+                translation: transform.translation,
+                angle: angle(transform),
+                velocity: rocket.velocity,
+                speed: rocket.speed,
+                health: rocket.health,
+                ammo: rocket.ammo,
+                fuel: rocket.fuel,
+                reload_progress: rocket.reload_progress,
+                shield_energy: rocket.shield_energy,
+                shield_up: rocket.shield_up,
+                input: *input,
+            })
+            .collect(),
+        bullets: bullets
+            .iter()
+            .map(|(entity, bullet, transform)| BodyState {
+                id: entity.to_bits(),
+                translation: transform.translation,
+                angle: angle(transform),
+                velocity: bullet.velocity,
+                radius: 0.,
+            })
+            .collect(),
+        asteroids: asteroids
+            .iter()
+            .map(|(entity, asteroid, transform)| BodyState {
+                id: entity.to_bits(),
+                translation: transform.translation,
+                angle: angle(transform),
+                velocity: asteroid.velocity,
+                radius: asteroid.radius,
+            })
+            .collect(),
+    };
+    session.broadcast(&Packet::Snapshot(snapshot));
+}
+
+/// Asks to join until the host has answered, then sends the client's keys,
+/// read with the first player's bindings, every frame
+fn send_local_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut session: ResMut<NetSession>,
+) {
+    if session.player.is_none() {
+        session.broadcast(&Packet::Join);
+        return;
+    }
+    let local = bindings.keys(0);
+    let input = RocketInput {
+        thrust: keys.pressed(local.accelerate),
+        rotate_left: keys.pressed(local.rotate_left),
+        rotate_right: keys.pressed(local.rotate_right),
+        fire: keys.just_pressed(local.fire),
+        reload: keys.pressed(local.reload),
+        cycle_weapon: keys.just_pressed(local.cycle_weapon),
+        boost: keys.pressed(local.boost),
+        brake: local.brake.is_some_and(|key| keys.pressed(key)),
+        hyperspace: local.hyperspace.is_some_and(|key| keys.just_pressed(key)),
+        shield: local.shield.is_some_and(|key| keys.pressed(key)),
+        aim: None,
+        analog: None,
+    };
+    session.broadcast(&Packet::Input(input));
+}
+
+/// Mirrors the newest snapshot: follows the host's game state, moves the local
+/// copies of rockets, bullets and asteroids and spawns or removes them to match.
+/// A rocket the host no longer has explodes where it was last seen, and
+/// breaks up as it would on the host.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn receive_snapshots(
+    mut commands: Commands,
+    mut session: ResMut<NetSession>,
+    bindings: Res<KeyBindings>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut players: ResMut<PlayerCount>,
+    mut match_state: ResMut<MatchState>,
+    mut config: ResMut<PhysicsConfig>,
+    mut debris_rng: ResMut<DebrisRng>,
+    mut rockets: Query<(Entity, &mut Rocket, &mut Transform, &mut RocketInput)>,
+    mut bullets: Query<(Entity, &NetId, &mut Transform), (With<Bullet>, Without<Rocket>)>,
+    mut asteroids: Query<
+        (Entity, &NetId, &mut Transform),
+        (With<Asteroid>, Without<Rocket>, Without<Bullet>),
+    >,
+    unmirrored: Query<Entity, (Or<(With<Bullet>, With<Asteroid>)>, Without<NetId>)>,
+) {
+    let host = session.host;
+    let mut newest: Option<Snapshot> = None;
+    for (from, packet) in session.receive() {
+        if Some(from) != host {
+            continue;
+        }
+        match packet {
+            Packet::Welcome { player } if session.player.is_none() => {
+                info!("Joined the host as player {}", player + 1);
+                session.player = Some(player);
+                session.refused = None;
+            }
+            Packet::Refused(refusal) if session.player.is_none() => {
+                session.refused = Some(refusal);
+            }
+            Packet::Snapshot(snapshot)
+                if newest.as_ref().is_none_or(|newest| snapshot.tick > newest.tick) =>
+            {
+                newest = Some(snapshot);
+            }
+            _ => {}
+        }
+    }
+    let Some(snapshot) = newest.filter(|snapshot| snapshot.tick > session.tick) else {
+        return;
+    };
+    session.tick = snapshot.tick;
+    if players.0 != snapshot.players {
+        players.0 = snapshot.players;
+    }
+
+    // The state change spawns and clears rockets of its own, mirroring waits
+    // for the next snapshot
+    if snapshot.state != *state.get() {
+        next_state.set(snapshot.state);
+        return;
+    }
+    if config.sun_radius != snapshot.sun_radius {
+        config.sun_radius = snapshot.sun_radius;
+    }
+    match_state.wins = snapshot.wins;
+    match_state.kills = snapshot.kills;
+    match_state.bonus = snapshot.bonus;
+
+    // Only the host's bullets and asteroids are shown, not the ones spawned
+    // locally when a match starts
+    for entity in unmirrored.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let mut missing: Vec<&RocketState> = snapshot.rockets.iter().collect();
+    let mut mirrored: Vec<usize> = Vec::new();
+    for (entity, mut rocket, mut transform, mut input) in rockets.iter_mut() {
+        if mirrored.contains(&rocket.player) {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let Some(index) = missing.iter().position(|state| state.player == rocket.player) else {
+            let (position, velocity) = (transform.translation, rocket.velocity);
+            spawn_explosion(&mut commands, position, rocket.color);
+            spawn_debris(&mut commands, &mut debris_rng.0, position, velocity, rocket.color);
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        mirrored.push(rocket.player);
+        let state = missing.swap_remove(index);
+        transform.translation = state.translation;
+        transform.rotation = Quat::from_rotation_z(state.angle);
+        rocket.velocity = state.velocity;
+        rocket.speed = state.speed;
+        rocket.health = state.health;
+        rocket.ammo = state.ammo;
+        rocket.fuel = state.fuel;
+        rocket.reload_progress = state.reload_progress;
+        rocket.shield_energy = state.shield_energy;
+        rocket.shield_up = state.shield_up;
+        *input = state.input;
+    }
+    for state in missing {
+        let position = state.translation.truncate();
+        spawn_rocket(&mut commands, &bindings, state.player, position, state.velocity);
+    }
+
+    for body in sync_bodies(&mut commands, &snapshot.bullets, bullets.iter_mut()) {
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform {
+                    translation: body.translation,
+                    rotation: Quat::from_rotation_z(body.angle),
+                    scale: Vec3::new(2., 2., 1.),
+                },
+                ..default()
+            },
+            // Only drawn, the host decides what it hits and when it expires
+            Bullet {
+                velocity: body.velocity,
+                damage: 0.,
+                lifetime: Timer::default(),
+                bounces_left: 0,
+                owner: 0,
+                travelled: 0.,
+                falloff_range: None,
+            },
+            ScreenWrap,
+            NetId(body.id),
+        ));
+    }
+    for body in sync_bodies(&mut commands, &snapshot.asteroids, asteroids.iter_mut()) {
+        commands.spawn((
+            SpatialBundle::from_transform(
+                Transform::from_translation(body.translation)
+                    .with_rotation(Quat::from_rotation_z(body.angle)),
+            ),
+            Asteroid {
+                velocity: body.velocity,
+                radius: body.radius,
+                health: 0.,
+            },
+            ScreenWrap,
+            NetId(body.id),
+        ));
+    }
+}
+
+/// Moves the mirrored bodies the host still has, despawns the others and
+/// returns the bodies that have no local copy yet
+fn sync_bodies<'a, 'w>(
+    commands: &mut Commands,
+    bodies: &'a [BodyState],
+    local: impl Iterator<Item = (Entity, &'w NetId, Mut<'w, Transform>)>,
+) -> Vec<&'a BodyState> {
+    let mut missing: HashMap<u64, &BodyState> =
+        bodies.iter().map(|body| (body.id, body)).collect();
+    for (entity, id, mut transform) in local {
+        match missing.remove(&id.0) {
+            Some(body) => {
+                transform.translation = body.translation;
+                transform.rotation = Quat::from_rotation_z(body.angle);
+            }
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+    missing.into_values().collect()
+}
+
+/// The host is ready once someone joined, a client once it has its player
+fn update_lobby_state(
+    session: Res<NetSession>,
+    lobby: Option<Res<State<LobbyState>>>,
+    mut next_lobby: ResMut<NextState<LobbyState>>,
+) {
+    let Some(lobby) = lobby else {
+        return;
+    };
+    let ready = match session.role {
+        NetRole::Host => !session.peers.is_empty(),
+        NetRole::Client => session.player.is_some(),
+    };
+    let target = if ready {
+        LobbyState::Ready
+    } else {
+        LobbyState::Waiting
+    };
+    if *lobby.get() != target {
+        next_lobby.set(target);
+    }
+}
+
+#[derive(Component)]
+struct LobbyText;
+
+fn add_lobby_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.,
+                color: LOBBY_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+        LobbyText,
+    ));
+}
+
+/// Tells who is connected while on the menu
+fn update_lobby_text(
+    session: Res<NetSession>,
+    lobby: Option<Res<State<LobbyState>>>,
+    players: Res<PlayerCount>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<LobbyText>>,
+) {
+    for (mut text, mut visibility) in &mut texts {
+        let Some(lobby) = lobby.as_ref() else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+        let port = session.socket.local_addr().map_or(DEFAULT_PORT, |addr| addr.port());
+        text.sections[0].value = match (session.role, lobby.get()) {
+            (NetRole::Host, LobbyState::Waiting) => {
+                format!("Hosting on port {port}, waiting for players")
+            }
+            (NetRole::Host, LobbyState::Ready) => {
+                let joined: Vec<String> = session
+                    .peers
+                    .iter()
+                    .map(|peer| format!("player {} from {}", peer.player + 1, peer.address))
+                    .collect();
+                format!(
+                    "Hosting {} players on port {port}: {}, press Enter to start",
+                    players.0,
+                    joined.join(", ")
+                )
+            }
+            (NetRole::Client, LobbyState::Ready) => format!(
+                "Joined as player {}, waiting for the host to start",
+                session.player.map_or(0, |player| player + 1)
+            ),
+            (NetRole::Client, LobbyState::Waiting) => match session.refused {
+                Some(Refusal::Started) => "The host is in a match, waiting for it to end".into(),
+                Some(Refusal::Full) => "The host has no free player, waiting for one".into(),
+                None => "Connecting to the host".into(),
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn encoded(packet: &Packet) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        packet.encode(&mut bytes);
+        bytes
+    }
+
+    fn body(id: u64) -> BodyState {
+        BodyState {
+            id,
+            translation: Vec3::new(-12.5, 40., 0.25),
+            angle: 1.5,
+            velocity: Vec2::new(3., -4.),
+            radius: 6.,
+        }
+    }
+
+    // One of each packet, with the optional parts of an input both set and not
+    fn packets() -> Vec<Packet> {
+        let input = RocketInput {
+            thrust: true,
+            fire: true,
+            hyperspace: true,
+            shield: true,
+            aim: Some(Vec2::new(0.6, -0.8)),
+            analog: Some(Vec2::new(-0.25, 1.)),
+            ..default()
+        };
+        let rocket = |player| RocketState {
+            player,
+            translation: Vec3::new(300., -20., 1.),
+            angle: -0.75,
+            velocity: Vec2::new(0., 100.),
+            speed: 100.,
+            health: 80.,
+            ammo: 7,
+            fuel: 0.5,
+            reload_progress: 0.25,
+            shield_energy: 30.,
+            shield_up: player == 1,
+            input,
+        };
+        vec![
+            Packet::Join,
+            Packet::Welcome { player: MAX_PLAYERS - 1 },
+            Packet::Refused(Refusal::Started),
+            Packet::Refused(Refusal::Full),
+            Packet::Input(RocketInput::default()),
+            Packet::Input(input),
+            Packet::Snapshot(Snapshot {
+                tick: u64::MAX - 1,
+                state: GameState::Overtime,
+                players: 3,
+                sun_radius: 42.5,
+                wins: [1, 2, 3, 4],
+                kills: [5, 6, 7, 8],
+                bonus: [9, 10, 11, 12],
+                rockets: vec![rocket(0), rocket(1)],
+                bullets: vec![body(7), body(8)],
+                asteroids: vec![body(9)],
+            }),
+        ]
+    }
+
+    #[test]
+    fn every_packet_reads_back_as_it_was_written() {
+        for packet in packets() {
+            assert_eq!(Packet::decode(&encoded(&packet)), Ok(packet));
+        }
+    }
+
+    #[test]
+    fn truncated_packets_and_trailing_bytes_are_rejected() {
+        for packet in packets() {
+            let bytes = encoded(&packet);
+            for len in 0..bytes.len() {
+                assert!(Packet::decode(&bytes[..len]).is_err(), "{packet:?} cut to {len}");
+            }
+            let mut longer = bytes.clone();
+            longer.push(0);
+            assert_eq!(Packet::decode(&longer), Err("trailing bytes".into()));
+        }
+    }
+
+    #[test]
+    fn unknown_tags_and_players_are_rejected() {
+        assert!(Packet::decode(&[u8::MAX]).is_err());
+        assert!(Packet::decode(&[WELCOME, MAX_PLAYERS as u8]).is_err());
+        assert!(Packet::decode(&[REFUSED, 2]).is_err());
+    }
+
+    fn host_world(state: GameState) -> (World, SocketAddr) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        let mut world = World::new();
+        world.insert_resource(NetSession::new(NetRole::Host, socket, None).unwrap());
+        world.insert_resource(State::new(state));
+        world.insert_resource(Time::<Real>::default());
+        world.insert_resource(PlayerCount(2));
+        (world, address)
+    }
+
+    fn client(host: SocketAddr, packet: &Packet) -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        socket.send_to(&encoded(packet), host).unwrap();
+        socket
+    }
+
+    fn answer(client: &UdpSocket) -> Packet {
+        let mut buffer = [0; 16];
+        let (len, _) = client.recv_from(&mut buffer).unwrap();
+        Packet::decode(&buffer[..len]).unwrap()
+    }
+
+    #[test]
+    fn joining_a_match_in_progress_is_answered() {
+        let (mut world, host) = host_world(GameState::Playing);
+        let late = client(host, &Packet::Join);
+        world.run_system_once(receive_inputs);
+
+        assert_eq!(answer(&late), Packet::Refused(Refusal::Started));
+        assert!(world.resource::<NetSession>().peers.is_empty());
+    }
+
+    #[test]
+    fn joining_a_full_host_is_answered() {
+        let (mut world, host) = host_world(GameState::Menu);
+        for player in 1..MAX_PLAYERS {
+            let joined = client(host, &Packet::Join);
+            world.run_system_once(receive_inputs);
+            assert_eq!(answer(&joined), Packet::Welcome { player });
+        }
+        let extra = client(host, &Packet::Join);
+        world.run_system_once(receive_inputs);
+
+        assert_eq!(answer(&extra), Packet::Refused(Refusal::Full));
+        assert_eq!(world.resource::<NetSession>().peers.len(), MAX_PLAYERS - 1);
+    }
+
+    #[test]
+    fn a_quiet_client_is_dropped_and_its_player_freed() {
+        let (mut world, host) = host_world(GameState::Menu);
+        let quiet = client(host, &Packet::Join);
+        world.run_system_once(receive_inputs);
+        assert_eq!(answer(&quiet), Packet::Welcome { player: 1 });
+
+        let advance = |world: &mut World, seconds| {
+            let duration = Duration::from_secs_f32(seconds);
+            world.resource_mut::<Time<Real>>().update_with_duration(duration);
+            world.run_system_once(receive_inputs);
+        };
+        advance(&mut world, 1.);
+        quiet.send_to(&encoded(&Packet::Input(RocketInput::default())), host).unwrap();
+        advance(&mut world, 0.);
+        // Heard from a second in, so still there just before the timeout after that
+        advance(&mut world, PEER_TIMEOUT.as_secs_f32() - 0.5);
+        assert_eq!(world.resource::<NetSession>().peers.len(), 1);
+
+        advance(&mut world, 1.);
+        assert!(world.resource::<NetSession>().peers.is_empty());
+        let next = client(host, &Packet::Join);
+        world.run_system_once(receive_inputs);
+        assert_eq!(answer(&next), Packet::Welcome { player: 1 });
+    }
+}
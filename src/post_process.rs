@@ -15,8 +15,7 @@ use bevy::{
     prelude::*,
     render::{
         extract_component::{
-            ComponentUniforms, DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin,
-            UniformComponentPlugin,
+            ComponentUniforms, DynamicUniformIndex, ExtractComponentPlugin, UniformComponentPlugin,
         },
         render_graph::{
             NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
@@ -32,6 +31,13 @@ use bevy::{
     },
 };
 
+use crate::effects::Explosion;
+use crate::rocket::{Rocket, RocketDamaged};
+use crate::sun::{kill_distance, Sun};
+
+// Distance from a sun's kill line where its distortion starts to build up
+const SUN_ONSET_DISTANCE: f32 = 200.;
+
 /// It is generally encouraged to set up post processing effects as a plugin
 pub struct PostProcessPlugin;
 
@@ -49,7 +55,8 @@ impl Plugin for PostProcessPlugin {
             // This plugin will prepare the component for the GPU by creating a uniform buffer
             // and writing the data to that buffer every frame.
             UniformComponentPlugin::<PostProcessSettings>::default(),
-        ));
+        ))
+        .add_event::<PostProcessEvent>();
 
         // We need to get the render app from the main app
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
@@ -286,17 +293,150 @@ impl FromWorld for PostProcessPipeline {
     }
 }
 
-// This is the component that will get passed to the shader
-#[derive(Component, Default, Clone, Copy, ExtractComponent, ShaderType)]
-pub struct PostProcessSettings {
-    pub intensity: f32,
+pub use shader_settings::PostProcessSettings;
+
+// The size checks `ShaderType` generates for each field are never called, only
+// an allow on the enclosing module reaches them
+#[allow(dead_code)]
+mod shader_settings {
+    use bevy::{
+        prelude::*,
+        render::{extract_component::ExtractComponent, render_resource::ShaderType},
+    };
+
+    // This is the component that will get passed to the shader
+    #[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+    pub struct PostProcessSettings {
+        pub intensity: f32,
+        // Ambient tint multiplied over the whole scene, driven by the sun's colour
+        pub tint: Vec3,
+        // How far the red and blue channels are pulled apart, in uv units
+        pub aberration: f32,
+    }
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        Self {
+            intensity: 0.0,
+            tint: Vec3::ONE,
+            aberration: 0.0,
+        }
+    }
+}
+
+// How far the scene is pulled towards the sun's hue, kept low so rockets and bullets stay readable
+const TINT_STRENGTH: f32 = 0.15;
+
+/// Turns the sun's (possibly HDR) colour into a subtle multiplicative tint
+fn sun_tint(color: Color) -> Vec3 {
+    let color = color.to_linear();
+    let rgb = Vec3::new(color.red, color.green, color.blue);
+    let peak = rgb.max_element();
+    if peak <= 0.0 {
+        return Vec3::ONE;
+    }
+    // Only keep the hue, the brightness of the sun is handled by bloom/tonemapping
+    Vec3::ONE.lerp(rgb / peak, TINT_STRENGTH)
+}
+
+/// What the game asks of the post-process effect, so nothing outside this
+/// module touches the shader settings
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub enum PostProcessEvent {
+    // A rocket got hit, a chromatic aberration pulse
+    Damage,
+    // A rocket or an asteroid blew up, a distortion pulse
+    Explosion,
+    // How close the rocket nearest to a sun is to burning up, from 0 to 1,
+    // sent every frame
+    SunProximity(f32),
+}
+
+/// Levels the post-process effect is driven by, the pulses decay back to 0
+#[derive(Resource)]
+pub struct PostProcessPulse {
+    // From 1 right after a hit or an explosion down to 0
+    pub damage: f32,
+    pub explosion: f32,
+    pub sun: f32,
+    // Seconds the damage and explosion pulses take to fade out
+    pub damage_duration: f32,
+    pub explosion_duration: f32,
+    // Channel offset at full level, scaled by the distance from the centre so
+    // it only reaches half of this at the edges of the screen
+    pub max_offset: f32,
+    // Distortion added to the idle wobble at full explosion and sun level
+    pub explosion_intensity: f32,
+    pub sun_intensity: f32,
+}
+
+impl Default for PostProcessPulse {
+    fn default() -> Self {
+        Self {
+            damage: 0.0,
+            explosion: 0.0,
+            sun: 0.0,
+            damage_duration: 0.3,
+            explosion_duration: 0.6,
+            max_offset: 0.02,
+            explosion_intensity: 0.04,
+            sun_intensity: 0.03,
+        }
+    }
+}
+
+/// Turns hits, explosions and close passes of the sun into `PostProcessEvent`s
+pub fn send_post_process_events(
+    mut damaged: EventReader<RocketDamaged>,
+    explosions: Query<(), Added<Explosion>>,
+    rockets: Query<(&Rocket, &Transform)>,
+    suns: Query<(&Sun, &Transform)>,
+    mut events: EventWriter<PostProcessEvent>,
+) {
+    if damaged.read().count() > 0 {
+        events.send(PostProcessEvent::Damage);
+    }
+    if !explosions.is_empty() {
+        events.send(PostProcessEvent::Explosion);
+    }
+    let proximity = rockets
+        .iter()
+        .flat_map(|(rocket, transform)| {
+            let position = transform.translation.truncate();
+            suns.iter().map(move |(sun, sun_transform)| {
+                let kill = kill_distance(sun, rocket);
+                let distance = sun_transform.translation.truncate().distance(position);
+                (1. - (distance - kill) / SUN_ONSET_DISTANCE).clamp(0., 1.)
+            })
+        })
+        .fold(0., f32::max);
+    events.send(PostProcessEvent::SunProximity(proximity));
+}
+
+pub fn apply_post_process_events(
+    mut events: EventReader<PostProcessEvent>,
+    mut pulse: ResMut<PostProcessPulse>,
+    // Real time so a pulse started right before pausing still fades out
+    time: Res<Time<Real>>,
+) {
+    let delta = time.delta_seconds();
+    pulse.damage = (pulse.damage - delta / pulse.damage_duration).max(0.0);
+    pulse.explosion = (pulse.explosion - delta / pulse.explosion_duration).max(0.0);
+    for event in events.read() {
+        match *event {
+            PostProcessEvent::Damage => pulse.damage = 1.0,
+            PostProcessEvent::Explosion => pulse.explosion = 1.0,
+            PostProcessEvent::SunProximity(level) => pulse.sun = level,
+        }
+    }
 }
 
 #[derive(Component)]
 pub struct Rotates;
 
 /// Rotates any entity around the x and y axis
-pub fn rotate(time: Res<Time>, mut query: Query<&mut Transform, With<Rotates>>) {
+pub fn rotate(time: Res<Time<Real>>, mut query: Query<&mut Transform, With<Rotates>>) {
     for mut transform in &mut query {
         transform.rotate_x(0.55 * time.delta_seconds());
         transform.rotate_z(0.15 * time.delta_seconds());
@@ -304,7 +444,19 @@ pub fn rotate(time: Res<Time>, mut query: Query<&mut Transform, With<Rotates>>)
 }
 
 // Change the intensity over time to show that the effect is controlled from the main world
-pub fn update_settings(mut settings: Query<&mut PostProcessSettings>, time: Res<Time>) {
+pub fn update_settings(
+    mut settings: Query<&mut PostProcessSettings>,
+    suns: Query<&Sun>,
+    pulse: Res<PostProcessPulse>,
+    // Real time so the effect keeps moving while the game is paused
+    time: Res<Time<Real>>,
+) {
+    // Read the sun every frame so a pulsing or recoloured sun is reflected immediately
+    let tint = suns
+        .iter()
+        .next()
+        .map_or(Vec3::ONE, |sun| sun_tint(sun.color));
+
     for mut setting in &mut settings {
         let mut intensity = time.elapsed_seconds().sin();
         // Make it loop periodically
@@ -314,8 +466,14 @@ pub fn update_settings(mut settings: Query<&mut PostProcessSettings>, time: Res<
         // Scale it to a more reasonable level
         intensity *= 0.015;
 
+        // Squared so a close pass only warps the screen right at the edge
+        intensity += pulse.explosion * pulse.explosion_intensity
+            + pulse.sun * pulse.sun * pulse.sun_intensity;
+
         // Set the intensity.
         // This will then be extracted to the render world and uploaded to the gpu automatically by the [`UniformComponentPlugin`]
         setting.intensity = intensity;
+        setting.tint = tint;
+        setting.aberration = pulse.damage.max(pulse.sun * 0.5) * pulse.max_offset;
     }
 }
@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use crate::collision::{Collider, CollisionEvent, CollisionKind};
+use crate::respawn::safe_spawn_position;
+use crate::rng::GameRng;
+use crate::rocket::{Arena, Rocket};
+use crate::sun::Sun;
+use crate::time_scale::TimeScale;
+
+const PICKUP_RADIUS: f32 = 14.;
+const PICKUP_COLLIDER: Collider = Collider {
+    radius: PICKUP_RADIUS,
+};
+const SHIELD_COLOR: Color = Color::srgba(0.3, 0.9, 1., 0.6);
+// Drawn around shielded rockets, in world units
+const SHIELD_RADIUS: f32 = 48.;
+// Seconds the rapid fire power-up lasts
+const RAPID_FIRE_DURATION: f32 = 8.;
+// Seconds the speed boost and the triple shot last
+const SPEED_BOOST_DURATION: f32 = 6.;
+const TRIPLE_SHOT_DURATION: f32 = 8.;
+// Applied to the speed cap and the acceleration while the speed boost lasts
+pub const SPEED_BOOST_SCALE: f32 = 1.3;
+// Angles of the bullets of a single shot fired with the triple shot
+pub const TRIPLE_SHOT_ANGLES: &[f32] = &[-10., 0., 10.];
+// Distance from the sun at which power-ups orbit, well clear of its corona
+const MIN_ORBIT_RADIUS: f32 = 160.;
+const MAX_ORBIT_RADIUS: f32 = 300.;
+// World units per second along the orbit, far slower than a real orbit at
+// this gravity so the power-ups can be caught
+const ORBIT_SPEED: f32 = 40.;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerupKind {
+    // Absorbs the next hit that would destroy the rocket
+    Shield,
+    // Fills the afterburner tank
+    Fuel,
+    // Halves the fire cooldown for a while
+    RapidFire,
+    // Refills the magazine without reloading
+    Ammo,
+    // Raises the top speed and acceleration for a while
+    SpeedBoost,
+    // Fills the energy shield
+    ShieldRecharge,
+    // Turns every single shot into three for a while
+    TripleShot,
+}
+
+impl PowerupKind {
+    fn color(self) -> Color {
+        match self {
+            PowerupKind::Shield => Color::srgb(0.3, 0.9, 1.),
+            PowerupKind::Fuel => Color::srgb(1., 0.6, 0.2),
+            PowerupKind::RapidFire => Color::srgb(1., 0.3, 0.3),
+            PowerupKind::Ammo => Color::srgb(0.9, 0.9, 0.3),
+            PowerupKind::SpeedBoost => Color::srgb(0.3, 1., 0.4),
+            PowerupKind::ShieldRecharge => Color::srgb(0.4, 0.5, 1.),
+            PowerupKind::TripleShot => Color::srgb(1., 0.4, 0.9),
+        }
+    }
+
+    fn apply(self, commands: &mut Commands, entity: Entity, rocket: &mut Rocket) {
+        match self {
+            PowerupKind::Shield => rocket.shielded = true,
+            PowerupKind::Fuel => rocket.fuel = rocket.max_fuel,
+            PowerupKind::RapidFire => rocket.rapid_fire = RAPID_FIRE_DURATION,
+            PowerupKind::Ammo => rocket.ammo = rocket.max_ammo,
+            PowerupKind::SpeedBoost => {
+                let timer = Timer::from_seconds(SPEED_BOOST_DURATION, TimerMode::Once);
+                commands.entity(entity).insert(SpeedBoost(timer));
+            }
+            PowerupKind::ShieldRecharge => rocket.shield_energy = rocket.max_shield_energy,
+            PowerupKind::TripleShot => {
+                let timer = Timer::from_seconds(TRIPLE_SHOT_DURATION, TimerMode::Once);
+                commands.entity(entity).insert(TripleShot(timer));
+            }
+        }
+    }
+}
+
+/// Raises the rocket's top speed and acceleration until the timer runs out
+#[derive(Component)]
+pub struct SpeedBoost(pub Timer);
+
+/// Fires every single shot of the rocket as three until the timer runs out
+#[derive(Component)]
+pub struct TripleShot(pub Timer);
+
+/// Circular path of a power-up around a sun
+#[derive(Component)]
+pub struct Orbit {
+    pub centre: Vec2,
+    pub radius: f32,
+    pub angle: f32,
+    // 1 for counterclockwise, -1 for clockwise
+    pub direction: f32,
+}
+
+impl Orbit {
+    fn position(&self) -> Vec2 {
+        self.centre + Vec2::from_angle(self.angle) * self.radius
+    }
+}
+
+/// A power-up lying on the field, collected by flying through it
+#[derive(Component)]
+pub struct Powerup {
+    pub kind: PowerupKind,
+}
+
+/// Drops a random power-up out of `kinds` every time the timer runs out
+#[derive(Resource)]
+pub struct PowerupSpawner {
+    pub timer: Timer,
+    // No more are dropped while this many are on the field
+    pub max_powerups: usize,
+    // Kinds that can drop, an empty list turns power-ups off
+    pub kinds: Vec<PowerupKind>,
+}
+
+impl Default for PowerupSpawner {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(Duration::from_secs(12), TimerMode::Repeating),
+            max_powerups: 3,
+            kinds: vec![
+                PowerupKind::Shield,
+                PowerupKind::Fuel,
+                PowerupKind::RapidFire,
+                PowerupKind::Ammo,
+                PowerupKind::SpeedBoost,
+                PowerupKind::ShieldRecharge,
+                PowerupKind::TripleShot,
+            ],
+        }
+    }
+}
+
+/// Periodically drops a power-up into a random orbit around one of the suns,
+/// or somewhere clear of the rockets if there is no sun
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_powerups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spawner: ResMut<PowerupSpawner>,
+    mut rng: ResMut<GameRng>,
+    arena: Res<Arena>,
+    powerups: Query<(), With<Powerup>>,
+    rockets: Query<&Transform, With<Rocket>>,
+    suns: Query<&Transform, With<Sun>>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished()
+        || spawner.kinds.is_empty()
+        || powerups.iter().count() >= spawner.max_powerups
+    {
+        return;
+    }
+    let rockets: Vec<Vec2> = rockets
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let suns: Vec<Vec2> = suns
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let index = (rng.next_u64() % spawner.kinds.len() as u64) as usize;
+    let powerup = Powerup {
+        kind: spawner.kinds[index],
+    };
+    if suns.is_empty() {
+        let position = safe_spawn_position(&mut rng, &arena, &rockets, &suns);
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(1.))),
+            PICKUP_COLLIDER,
+            powerup,
+        ));
+        return;
+    }
+    let sun = (rng.next_u64() % suns.len() as u64) as usize;
+    let orbit = Orbit {
+        centre: suns[sun],
+        radius: rng.range(MIN_ORBIT_RADIUS, MAX_ORBIT_RADIUS),
+        angle: rng.range(0., TAU),
+        direction: if rng.next_f32() < 0.5 { 1. } else { -1. },
+    };
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(orbit.position().extend(1.))),
+        PICKUP_COLLIDER,
+        powerup,
+        orbit,
+    ));
+}
+
+/// Carries the orbiting power-ups around their suns
+pub fn move_orbiting_powerups(
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    mut powerups: Query<(&mut Orbit, &mut Transform), With<Powerup>>,
+) {
+    let delta = time_scale.delta(&time);
+    for (mut orbit, mut transform) in powerups.iter_mut() {
+        orbit.angle += orbit.direction * ORBIT_SPEED / orbit.radius * delta;
+        let position = orbit.position();
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+/// Ends the timed power-ups once they run out
+pub fn tick_powerup_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut boosts: Query<(Entity, &mut SpeedBoost)>,
+    mut triple_shots: Query<(Entity, &mut TripleShot)>,
+) {
+    for (entity, mut boost) in boosts.iter_mut() {
+        if boost.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<SpeedBoost>();
+        }
+    }
+    for (entity, mut triple_shot) in triple_shots.iter_mut() {
+        if triple_shot.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<TripleShot>();
+        }
+    }
+}
+
+/// Gives each power-up a rocket flew through to the first one that reached it
+pub fn collect_powerups(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    powerups: Query<&Powerup>,
+    mut rockets: Query<&mut Rocket>,
+) {
+    let mut collected: Vec<Entity> = Vec::new();
+    for collision in collisions.read() {
+        let (rocket_entity, entity) = (collision.a, collision.b);
+        if collision.kind != CollisionKind::ShipPowerup || collected.contains(&entity) {
+            continue;
+        }
+        let (Ok(powerup), Ok(mut rocket)) = (powerups.get(entity), rockets.get_mut(rocket_entity))
+        else {
+            continue;
+        };
+        collected.push(entity);
+        powerup.kind.apply(&mut commands, rocket_entity, &mut rocket);
+        commands.entity(entity).despawn();
+    }
+}
+
+pub fn draw_powerups(
+    mut gizmos: Gizmos,
+    powerups: Query<(&Powerup, &Transform)>,
+    rockets: Query<(&Rocket, &Transform)>,
+) {
+    for (powerup, transform) in powerups.iter() {
+        gizmos.circle_2d(transform.translation.truncate(), PICKUP_RADIUS, powerup.kind.color());
+    }
+    for (rocket, transform) in rockets.iter() {
+        if rocket.shielded {
+            gizmos.circle_2d(transform.translation.truncate(), SHIELD_RADIUS, SHIELD_COLOR);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::{prelude::*, sprite::Material2dPlugin, transform::TransformSystem};
+use bevy_hanabi::prelude::*;
+
+use crate::asteroid::attach_asteroid_meshes;
+use crate::bullet::attach_bullet_textures;
+use crate::debris::{attach_debris_meshes, fade_debris};
+use crate::effects::{
+    add_impact_effect, add_thrust_trail_effect, add_warp_effect, attach_thrust_trails,
+    attach_timed_effects, update_thrust_trails, update_thruster_flames,
+};
+use crate::interpolation::{
+    interpolate_transforms, record_physical_transforms, restore_physical_transforms,
+    track_new_bodies,
+};
+use crate::respawn::blink_protected_rockets;
+use crate::rocket::{attach_rocket_textures, read_player_input};
+use crate::shield::{attach_shield_bubbles, update_shield_bubbles};
+use crate::starfield::{
+    add_background, animate_background, move_nebulae, resize_background, spawn_nebulae,
+    MovingPatternMaterial,
+};
+use crate::sun::{animate_sun_materials, attach_sun_meshes, update_sun_material, SunMaterial};
+use crate::setup;
+
+/// Draws what `GameplayPlugin` simulates: the camera, starfield, sprites,
+/// meshes and particle effects, all added to the entities it spawns
+pub struct PresentationPlugin;
+
+impl Plugin for PresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            HanabiPlugin,
+            Material2dPlugin::<MovingPatternMaterial>::default(),
+            Material2dPlugin::<SunMaterial>::default(),
+        ))
+            .add_systems(
+                Startup,
+                (
+                    setup,
+                    add_thrust_trail_effect,
+                    add_impact_effect,
+                    add_warp_effect,
+                    add_background,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    attach_rocket_textures,
+                    attach_bullet_textures,
+                    attach_asteroid_meshes,
+                    attach_timed_effects,
+                    blink_protected_rockets,
+                    (attach_shield_bubbles, update_shield_bubbles).chain(),
+                    (attach_debris_meshes, fade_debris).chain(),
+                    (attach_sun_meshes, update_sun_material, animate_sun_materials).chain(),
+                    (resize_background, animate_background, spawn_nebulae, move_nebulae),
+                ),
+            )
+            .add_systems(
+                Update,
+                (attach_thrust_trails, update_thrust_trails, update_thruster_flames)
+                    .chain()
+                    .after(read_player_input),
+            )
+            // The simulation steps at 60 Hz, frames in between draw the bodies
+            // part way from one step to the next
+            .add_systems(FixedFirst, restore_physical_transforms)
+            .add_systems(FixedLast, (track_new_bodies, record_physical_transforms))
+            .add_systems(
+                PostUpdate,
+                interpolate_transforms.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
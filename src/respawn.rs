@@ -0,0 +1,298 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::bindings::KeyBindings;
+use crate::rng::GameRng;
+use crate::rocket::{
+    orbit_nearest_sun, spawn_rocket, start_position, Arena, PlayerCount, Rocket, MAX_PLAYERS,
+};
+use crate::score::MatchState;
+use crate::sun::{PhysicsConfig, Sun};
+
+// Keep respawns away from the sun's pull and the edges of the screen
+const SAFE_SUN_DISTANCE: f32 = 200.;
+const SAFE_ROCKET_DISTANCE: f32 = 150.;
+const SPAWN_MARGIN: f32 = 60.;
+
+pub const START_LIVES: u32 = 3;
+
+// Rockets blink this many times per second while spawn protected
+const PROTECTION_BLINK_HZ: f32 = 6.;
+
+const MAX_ATTEMPTS: usize = 32;
+const CANDIDATES: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RespawnStrategy {
+    /// Back at the player's start position
+    #[default]
+    Fixed,
+    /// Any random point clear of the sun and other rockets
+    RandomSafe,
+    /// The candidate point furthest from every living rocket
+    FarthestFromEnemies,
+}
+
+#[derive(Clone, Copy)]
+pub struct RespawnOptions {
+    pub strategy: RespawnStrategy,
+    pub delay: Duration,
+    // How long a respawned rocket can't be hurt
+    pub protection: Duration,
+}
+
+impl Default for RespawnOptions {
+    fn default() -> Self {
+        Self {
+            strategy: RespawnStrategy::default(),
+            delay: Duration::from_secs(2),
+            protection: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Keeps a freshly respawned rocket from being hurt until the timer runs out,
+/// so the sun's pull or a camping enemy can't take it straight back out.
+/// Other rockets, bullets and asteroids pass through it in the meantime.
+#[derive(Component, Clone)]
+pub struct SpawnProtection(pub Timer);
+
+pub fn tick_spawn_protection(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rockets: Query<(Entity, &mut SpawnProtection)>,
+) {
+    for (entity, mut protection) in rockets.iter_mut() {
+        if protection.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<SpawnProtection>();
+        }
+    }
+}
+
+/// Blinks spawn protected rockets
+pub fn blink_protected_rockets(
+    time: Res<Time>,
+    mut rockets: Query<(&mut Sprite, Has<SpawnProtection>), With<Rocket>>,
+) {
+    let visible = (time.elapsed_seconds() * PROTECTION_BLINK_HZ).fract() < 0.5;
+    for (mut sprite, protected) in rockets.iter_mut() {
+        let alpha = if protected && !visible { 0.3 } else { 1. };
+        if sprite.color.alpha() != alpha {
+            sprite.color.set_alpha(alpha);
+        }
+    }
+}
+
+/// Respawn options per player, indexed by `Rocket::player`
+#[derive(Resource, Default)]
+pub struct RespawnSettings(pub [RespawnOptions; MAX_PLAYERS]);
+
+impl RespawnStrategy {
+    fn next(self) -> Self {
+        match self {
+            Self::Fixed => Self::RandomSafe,
+            Self::RandomSafe => Self::FarthestFromEnemies,
+            Self::FarthestFromEnemies => Self::Fixed,
+        }
+    }
+}
+
+/// F6 cycles every player through the respawn strategies
+pub fn cycle_respawn_strategy(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<RespawnSettings>,
+) {
+    if !keys.just_pressed(KeyCode::F6) {
+        return;
+    }
+    for options in settings.0.iter_mut() {
+        options.strategy = options.strategy.next();
+    }
+    info!("Respawn strategy: {:?}", settings.0[0].strategy);
+}
+
+#[derive(Clone)]
+struct PendingRespawn {
+    player: usize,
+    timer: Timer,
+}
+
+#[derive(Resource, Default, Clone)]
+pub struct RespawnQueue(Vec<PendingRespawn>);
+
+impl RespawnQueue {
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn contains(&self, player: usize) -> bool {
+        self.0.iter().any(|pending| pending.player == player)
+    }
+}
+
+/// Respawns left per player, indexed by `Rocket::player`
+#[derive(Resource, Clone)]
+pub struct Lives(pub [u32; MAX_PLAYERS]);
+
+impl Default for Lives {
+    fn default() -> Self {
+        Self([START_LIVES; MAX_PLAYERS])
+    }
+}
+
+/// Queues a respawn for every player that has no rocket on the field, each
+/// one costs a life. A player out of lives sits out the rest of the round.
+pub fn queue_respawns(
+    rockets: Query<&Rocket>,
+    settings: Res<RespawnSettings>,
+    state: Res<MatchState>,
+    players: Res<PlayerCount>,
+    mut queue: ResMut<RespawnQueue>,
+    mut lives: ResMut<Lives>,
+) {
+    // The next round puts everyone back itself
+    if state.between_rounds() {
+        return;
+    }
+    for player in 0..players.0 {
+        let alive = rockets.iter().any(|rocket| rocket.player == player);
+        if alive || queue.contains(player) || lives.0[player] == 0 {
+            continue;
+        }
+        lives.0[player] -= 1;
+        queue.0.push(PendingRespawn {
+            player,
+            timer: Timer::new(settings.0[player].delay, TimerMode::Once),
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn process_respawns(
+    mut commands: Commands,
+    time: Res<Time>,
+    bindings: Res<KeyBindings>,
+    settings: Res<RespawnSettings>,
+    players: Res<PlayerCount>,
+    mut queue: ResMut<RespawnQueue>,
+    mut rng: ResMut<GameRng>,
+    arena: Res<Arena>,
+    config: Res<PhysicsConfig>,
+    rockets: Query<&Transform, With<Rocket>>,
+    suns: Query<&Transform, With<Sun>>,
+) {
+    let enemies: Vec<Vec2> = rockets
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let suns: Vec<Vec2> = suns
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+
+    queue.0.retain_mut(|pending| {
+        if !pending.timer.tick(time.delta()).finished() {
+            return true;
+        }
+        let options = settings.0[pending.player];
+        let start = start_position(pending.player, players.0);
+        let position =
+            respawn_position(options.strategy, start, &mut rng, &arena, &enemies, &suns);
+        // Comes back circling the sun, like at the start of a match
+        let velocity = orbit_nearest_sun(position, &suns, config.g_force);
+        let rocket = spawn_rocket(&mut commands, &bindings, pending.player, position, velocity);
+        commands
+            .entity(rocket)
+            .insert(SpawnProtection(Timer::new(options.protection, TimerMode::Once)));
+        false
+    });
+}
+
+/// Where a player comes back, never inside the danger zone of one of the `suns`
+pub fn respawn_position(
+    strategy: RespawnStrategy,
+    start: Vec2,
+    rng: &mut GameRng,
+    arena: &Arena,
+    enemies: &[Vec2],
+    suns: &[Vec2],
+) -> Vec2 {
+    match strategy {
+        RespawnStrategy::Fixed => clear_of_suns(start, suns),
+        RespawnStrategy::RandomSafe => safe_spawn_position(rng, arena, enemies, suns),
+        RespawnStrategy::FarthestFromEnemies => {
+            farthest_from_enemies(rng, arena, enemies, suns)
+        }
+    }
+}
+
+/// Picks a random point clear of the suns and the given rockets, falling
+/// back to the best scoring point if the arena is too crowded
+pub fn safe_spawn_position(
+    rng: &mut GameRng,
+    arena: &Arena,
+    enemies: &[Vec2],
+    suns: &[Vec2],
+) -> Vec2 {
+    for _ in 0..MAX_ATTEMPTS {
+        let point = random_point(rng, arena);
+        let clear_of_sun = suns
+            .iter()
+            .all(|sun| sun.distance(point) > SAFE_SUN_DISTANCE);
+        let clear_of_rockets = enemies
+            .iter()
+            .all(|enemy| enemy.distance(point) > SAFE_ROCKET_DISTANCE);
+        if clear_of_sun && clear_of_rockets {
+            return point;
+        }
+    }
+    farthest_from_enemies(rng, arena, enemies, suns)
+}
+
+/// Scores random candidate points by their distance to the closest living
+/// rocket and returns the one with the highest score
+pub fn farthest_from_enemies(
+    rng: &mut GameRng,
+    arena: &Arena,
+    enemies: &[Vec2],
+    suns: &[Vec2],
+) -> Vec2 {
+    let score = |point: Vec2| {
+        enemies
+            .iter()
+            .map(|enemy| enemy.distance(point))
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    let mut best = clear_of_suns(random_point(rng, arena), suns);
+    let mut best_score = score(best);
+    for _ in 1..CANDIDATES {
+        let candidate = clear_of_suns(random_point(rng, arena), suns);
+        let candidate_score = score(candidate);
+        if candidate_score > best_score {
+            best = candidate;
+            best_score = candidate_score;
+        }
+    }
+    best
+}
+
+fn random_point(rng: &mut GameRng, arena: &Arena) -> Vec2 {
+    let half_width = arena.w_clip - SPAWN_MARGIN;
+    let half_height = arena.h_clip - SPAWN_MARGIN;
+    Vec2::new(
+        rng.range(-half_width, half_width),
+        rng.range(-half_height, half_height),
+    )
+}
+
+/// Pushes a point radially out of each sun's danger zone
+fn clear_of_suns(mut point: Vec2, suns: &[Vec2]) -> Vec2 {
+    for sun in suns {
+        let offset = point - *sun;
+        if offset.length() <= SAFE_SUN_DISTANCE {
+            point = *sun + offset.try_normalize().unwrap_or(Vec2::X) * SAFE_SUN_DISTANCE;
+        }
+    }
+    point
+}
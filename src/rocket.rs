@@ -1,117 +1,916 @@
-use bevy::prelude::*;
-use std::time::Duration;
+use bevy::{prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
 
-#[derive(Component)]
+use crate::ai::AiController;
+use crate::bindings::KeyBindings;
+use crate::bullet::Bullet;
+use crate::collision::Collider;
+use crate::debris::{spawn_debris, DebrisRng};
+use crate::effects::{spawn_explosion, spawn_thruster_flame};
+use crate::gamepad::{gamepad_input, PadBindings};
+use crate::hitbox::{Hitbox, ROCKET_HALF_EXTENTS};
+use crate::motion_trail::MotionTrail;
+use crate::seed::SeedInput;
+use crate::slingshot::SlingshotTracker;
+use crate::sun::{PhysicsConfig, SunLayout};
+use crate::weapon::WeaponKind;
+
+#[derive(Component, Clone)]
 pub struct Rocket {
+    pub player: usize,
+    // Used for the rocket's explosion
+    pub color: Color,
     pub speed: f32,
+    // Sprite scale at rest, effects such as tidal stretching scale relative to it
+    pub base_scale: f32,
     pub max_speed: f32,
+    // Speed gained per second of thrust
+    pub thrust_accel: f32,
+    // Speed lost per second while braking, and how fast the rocket can then
+    // fly backwards, 0 stops it instead
+    pub brake_decel: f32,
+    pub max_reverse_speed: f32,
+    // Strength of the turn input, and the cap on the turn speed in radians per second
+    pub turn_rate: f32,
+    pub max_rotation_speed: f32,
+    // Turn speed lost per second, in radians per second, while no rotate key is held
+    pub rotation_damping: f32,
     pub velocity: Vec2,
+    // Left over from bouncing off another rocket or being shot, carried on top
+    // of the thrust until drag wears it off
+    pub knockback: Vec2,
     pub rotation_speed: f32,
     pub radius_collision: f32,
+    pub hitbox: Hitbox,
+    pub health: f32,
+    // Absorbs the next hit that would destroy the rocket
+    pub shielded: bool,
+    // Energy shield held up with its key, it stops bullets but not the sun.
+    // Drained per second while up and recharged per second otherwise.
+    pub shield_energy: f32,
+    pub max_shield_energy: f32,
+    pub shield_drain: f32,
+    pub shield_recharge: f32,
+    pub shield_up: bool,
+    // Player whose bullet hit it last, credited with the kill if it then dies
+    // some other way, e.g. knocked into the sun
+    pub last_hit_by: Option<usize>,
     pub controls: RocketControls,
     pub spawn_key: KeyCode,
-    pub cooldown: Duration,
-    pub last_shot_time: Duration,
+    // Seconds between two shots, and seconds since the last one
+    pub fire_cooldown: f32,
+    pub last_fired: f32,
+    // Seconds left of the rapid fire power-up
+    pub rapid_fire: f32,
+    pub weapon: WeaponKind,
+    // Shots go towards `RocketInput::aim` instead of straight ahead
+    pub mouse_aim: bool,
+    pub ammo: u32,
+    pub max_ammo: u32,
+    // Seconds the reload key has to be held, and how long it has been held so far
+    pub reload_time: f32,
+    pub reload_progress: f32,
+    // Afterburner tank, drained per second while boosting and refilled per
+    // second otherwise
+    pub fuel: f32,
+    pub max_fuel: f32,
+    pub fuel_drain: f32,
+    pub fuel_regen: f32,
+    // Fuel needed before the boost works again after running dry
+    pub min_boost_fuel: f32,
+    pub boosting: bool,
+    boost_locked: bool,
+}
+
+impl Rocket {
+    /// Advances the fire cooldown by `delta` seconds, returns whether a shot
+    /// was fired and restarts the cooldown if so
+    pub fn try_fire(&mut self, delta: f32, wants_fire: bool) -> bool {
+        self.last_fired += delta;
+        self.rapid_fire = (self.rapid_fire - delta).max(0.0);
+        let mut cooldown = self.fire_cooldown * self.weapon.cooldown_scale();
+        if self.rapid_fire > 0.0 {
+            cooldown *= RAPID_FIRE_COOLDOWN_SCALE;
+        }
+        if !wants_fire || self.last_fired < cooldown {
+            return false;
+        }
+        self.last_fired = 0.0;
+        true
+    }
+
+    /// Drains fuel while `wants_boost` and refills it otherwise. A dry tank
+    /// keeps the boost off until it is back at `min_boost_fuel`, so holding
+    /// the key doesn't stutter the boost on and off. Returns whether the
+    /// rocket is boosting this frame.
+    pub fn update_boost(&mut self, delta: f32, wants_boost: bool) -> bool {
+        if self.boost_locked && self.fuel >= self.min_boost_fuel {
+            self.boost_locked = false;
+        }
+        self.boosting = wants_boost && !self.boost_locked && self.fuel > 0.0;
+        if self.boosting {
+            self.fuel = (self.fuel - self.fuel_drain * delta).max(0.0);
+            if self.fuel <= 0.0 {
+                self.boost_locked = true;
+            }
+        } else {
+            self.fuel = (self.fuel + self.fuel_regen * delta).min(self.max_fuel);
+        }
+        self.boosting
+    }
+
+    /// Drains shield energy while `wants_shield` and recharges it otherwise.
+    /// Raising the shield takes `MIN_SHIELD_ENERGY`, so an empty one doesn't
+    /// flicker on and off while the key is held. Returns whether it is up.
+    pub fn update_shield(&mut self, delta: f32, wants_shield: bool) -> bool {
+        let can_raise = self.shield_up || self.shield_energy >= MIN_SHIELD_ENERGY;
+        self.shield_up = wants_shield && can_raise && self.shield_energy > 0.0;
+        if self.shield_up {
+            self.shield_energy = (self.shield_energy - self.shield_drain * delta).max(0.0);
+        } else {
+            self.shield_energy =
+                (self.shield_energy + self.shield_recharge * delta).min(self.max_shield_energy);
+        }
+        self.shield_up
+    }
+
+    /// Speed cap for this frame, raised while boosting
+    pub fn top_speed(&self) -> f32 {
+        if self.boosting {
+            self.max_speed * BOOST_SPEED_SCALE
+        } else {
+            self.max_speed
+        }
+    }
+
+    /// Pushes the rocket along `impulse` on top of its thrust. Knockback alone can't
+    /// take the rocket past `max_speed`, though it keeps any speed it already had.
+    pub fn apply_knockback(&mut self, impulse: Vec2) {
+        let thrust = self.velocity - self.knockback;
+        let cap = self.max_speed.max(thrust.length());
+        self.velocity = (self.velocity + impulse).clamp_length_max(cap);
+        self.knockback = self.velocity - thrust;
+    }
+
+    /// Uses up the shield if there is one, returns whether it absorbed the hit
+    pub fn absorb_hit(&mut self) -> bool {
+        std::mem::replace(&mut self.shielded, false)
+    }
+
+    /// Advances a reload while `holding` the reload key, refilling the ammo once
+    /// `reload_time` is reached. Letting go early cancels it. Returns whether
+    /// the rocket is busy reloading and so cannot fire.
+    pub fn update_reload(&mut self, delta: f32, holding: bool) -> bool {
+        if !holding || self.ammo == self.max_ammo {
+            self.reload_progress = 0.0;
+            return false;
+        }
+        self.reload_progress += delta;
+        if self.reload_progress >= self.reload_time {
+            self.ammo = self.max_ammo;
+            self.reload_progress = 0.0;
+        }
+        true
+    }
+
+    /// Subtracts `amount` from the rocket's health, returns whether it is destroyed.
+    /// A shield is used up instead of the hit that would have been lethal.
+    pub fn take_damage(&mut self, amount: f32) -> bool {
+        if self.health - amount <= 0.0 && self.absorb_hit() {
+            return false;
+        }
+        self.health = (self.health - amount).max(0.0);
+        self.health <= 0.0
+    }
+}
+
+/// Sent whenever a rocket is hit, whether it survives it or not
+#[derive(Event, Debug, Clone, Copy)]
+pub struct RocketDamaged {
+    pub player: usize,
+    // The hit destroyed the rocket
+    pub destroyed: bool,
+    // Player credited with the hit
+    pub attacker: Option<usize>,
 }
 
+/// Why a rocket was destroyed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DestroyCause {
+    // Touched a sun or burnt up in its corona
+    Sun,
+    // Rammed by another rocket
+    Collision,
+    // Worn down by bouncing off other rockets
+    Bounce,
+    Asteroid,
+    // Broke up in a failed hyperspace jump
+    Hyperspace,
+    // Shot down
+    Bullet,
+    // Flew out of the arena with `BoundaryMode::Death`
+    Boundary,
+}
+
+/// Sent by the collision checks for every rocket they destroy, `destroy_rockets`
+/// then removes it. Several checks may report the same rocket in one step.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct RocketDestroyed {
+    pub entity: Entity,
+    pub player: usize,
+    pub position: Vec3,
+    pub velocity: Vec2,
+    pub color: Color,
+    pub cause: DestroyCause,
+    // See `RocketDamaged::attacker`
+    pub killer: Option<usize>,
+}
+
+/// Despawns and blows up every rocket reported destroyed, once each
+pub fn destroy_rockets(
+    mut commands: Commands,
+    mut debris_rng: ResMut<DebrisRng>,
+    mut events: EventReader<RocketDestroyed>,
+    mut damaged: EventWriter<RocketDamaged>,
+) {
+    let mut handled: Vec<Entity> = Vec::new();
+    for event in events.read() {
+        if handled.contains(&event.entity) {
+            continue;
+        }
+        handled.push(event.entity);
+        info!("Player {} destroyed, {:?}", event.player + 1, event.cause);
+        commands.entity(event.entity).despawn_recursive();
+        spawn_explosion(&mut commands, event.position, event.color);
+        spawn_debris(
+            &mut commands,
+            &mut debris_rng.0,
+            event.position,
+            event.velocity,
+            event.color,
+        );
+        // Sun burns only flash once they destroy the rocket, a flash every
+        // frame of the burn would never fade
+        damaged.send(RocketDamaged {
+            player: event.player,
+            destroyed: true,
+            attacker: event.killer,
+        });
+    }
+}
+
+/// What the rocket is asked to do this frame, filled from the keyboard or a replay
+#[derive(Component, Default, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RocketInput {
+    pub thrust: bool,
+    pub rotate_left: bool,
+    pub rotate_right: bool,
+    pub fire: bool,
+    pub reload: bool,
+    pub cycle_weapon: bool,
+    pub boost: bool,
+    pub brake: bool,
+    pub hyperspace: bool,
+    pub shield: bool,
+    // World position the player aims at, used by rockets with `mouse_aim`
+    pub aim: Option<Vec2>,
+    // How far a stick or trigger is pushed, x turns from -1 left to 1 right and
+    // y thrusts from 0 to 1. Keys always push all the way.
+    pub analog: Option<Vec2>,
+}
+
+impl RocketInput {
+    /// Share of the full thrust, between 0 and 1
+    pub fn throttle(&self) -> f32 {
+        match (self.thrust, self.analog) {
+            (false, _) => 0.,
+            (true, Some(analog)) => analog.y,
+            (true, None) => 1.,
+        }
+    }
+
+    /// Share of the full turn rate, positive turns left like `rotate_left`
+    pub fn turn(&self) -> f32 {
+        match self.analog {
+            Some(analog) if self.rotate_left || self.rotate_right => -analog.x,
+            _ => self.rotate_left as i8 as f32 - self.rotate_right as i8 as f32,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct RocketControls {
     pub rotate_left: KeyCode,
     pub rotate_right: KeyCode,
     pub accelerate: KeyCode,
+    pub reload: KeyCode,
+    pub cycle_weapon: KeyCode,
+    pub boost: KeyCode,
+    pub brake: Option<KeyCode>,
+    pub hyperspace: Option<KeyCode>,
+    pub shield: Option<KeyCode>,
+    // Used alongside the keys while a controller is connected
+    pub gamepad: Option<Gamepad>,
+    pub pad: PadBindings,
 }
 
-const W_CLIP: f32 = 510.;
-const H_CLIP: f32 = 380.;
+pub const W_CLIP: f32 = 510.;
+pub const H_CLIP: f32 = 380.;
 
-const SPAWN_X: f32 = 400.;
-const SPAWN_Y: f32 = 200.;
+// Start positions are spread evenly over an ellipse around the sun, the first
+// player starts in the top left
+const SPAWN_RADIUS: Vec2 = Vec2::new(420., 290.);
+const FIRST_SPAWN_ANGLE: f32 = 150.;
 
-pub fn add_rockets(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let rocket_image = asset_server.load("Rocket1-v2.png");
-    let rocket_image_b = asset_server.load("Rocket1-v3.png");
+// Half the window size, where clamped rockets stop
+pub const HALF_WIDTH: f32 = 490.;
+pub const HALF_HEIGHT: f32 = 367.5;
+pub const MAX_PLAYERS: usize = 4;
 
-    let pos = Vec2::new(-SPAWN_X, SPAWN_Y);
-    let pos_b = Vec2::new(SPAWN_X, -SPAWN_Y);
-    let direction = Vec2::ZERO - pos;
-    let direction_b = Vec2::ZERO - pos_b;
-    let angle = direction.y.atan2(direction.x) - std::f32::consts::FRAC_PI_2;
-    let angle_b = direction_b.y.atan2(direction_b.x) - std::f32::consts::FRAC_PI_2;
-
-    let rocket_a_pos = Transform {
-        translation: Vec3::new(-SPAWN_X, SPAWN_Y, 1.),
-        scale: Vec3::splat(0.12),
-        rotation: Quat::from_rotation_z(angle),
-        ..Default::default()
-    };
-    let rocket_b_pos = Transform {
-        translation: Vec3::new(SPAWN_X, -SPAWN_Y, 1.),
-        scale: Vec3::splat(0.144),
-        rotation: Quat::from_rotation_z(angle_b),
-        ..Default::default()
-    };
-    commands.spawn((
-        SpriteBundle {
-            texture: rocket_image.clone(),
-            transform: rocket_a_pos,
-            ..default()
+/// Edges of the playfield in world units. Follows the primary window, which
+/// always shows at least the default arena, and keeps the consts without one.
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct Arena {
+    // Where clamped rockets stop and bullets bounce
+    pub half_width: f32,
+    pub half_height: f32,
+    // Where things wrap, a little past the edge so they leave the screen first
+    pub w_clip: f32,
+    pub h_clip: f32,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            half_width: HALF_WIDTH,
+            half_height: HALF_HEIGHT,
+            w_clip: W_CLIP,
+            h_clip: H_CLIP,
+        }
+    }
+}
+
+impl Arena {
+    /// The arena seen through a window of `size` pixels. The camera scales the
+    /// view to fit the default arena, a window of another shape shows more of
+    /// the world along one axis.
+    pub fn fit(size: Vec2) -> Self {
+        let min_half = Vec2::new(HALF_WIDTH, HALF_HEIGHT);
+        if size.min_element() <= 0. {
+            return Self::default();
+        }
+        let half = size * (min_half / size).max_element();
+        Self {
+            half_width: half.x,
+            half_height: half.y,
+            w_clip: half.x + W_CLIP - HALF_WIDTH,
+            h_clip: half.y + H_CLIP - HALF_HEIGHT,
+        }
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        position.x.abs() <= self.w_clip && position.y.abs() <= self.h_clip
+    }
+}
+
+pub fn fit_arena_to_window(
+    windows: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
+    mut arena: ResMut<Arena>,
+) {
+    if let Ok(window) = windows.get_single() {
+        let fitted = Arena::fit(window.size());
+        // Only marked changed when the size really did
+        arena.set_if_neq(fitted);
+    }
+}
+
+pub const MAX_HEALTH: f32 = 100.;
+pub const MAX_AMMO: u32 = 8;
+pub const MAX_FUEL: f32 = 100.;
+pub const MAX_SHIELD_ENERGY: f32 = 100.;
+// Energy the shield needs before it can be raised again
+const MIN_SHIELD_ENERGY: f32 = 20.;
+
+// Applied to the fire cooldown while the rapid fire power-up lasts
+const RAPID_FIRE_COOLDOWN_SCALE: f32 = 0.5;
+// Applied to the speed cap and the acceleration while boosting
+const BOOST_SPEED_SCALE: f32 = 1.6;
+pub const BOOST_ACCELERATION_SCALE: f32 = 2.5;
+
+/// The parts of a rocket that differ between players, apart from the keys
+pub struct PlayerSpec {
+    pub texture: &'static str,
+    pub scale: f32,
+    // Multiplied into the sprite, tells apart players sharing a texture
+    pub tint: Color,
+    pub color: Color,
+    pub fire_cooldown: f32,
+    pub max_speed: f32,
+    pub thrust_accel: f32,
+    pub turn_rate: f32,
+    pub max_rotation_speed: f32,
+}
+
+pub fn player_spec(player: usize) -> PlayerSpec {
+    match player {
+        0 => PlayerSpec {
+            texture: "Rocket1-v2.png",
+            scale: 0.12,
+            tint: Color::WHITE,
+            color: Color::srgb(0.3, 0.7, 1.),
+            fire_cooldown: 0.5,
+            max_speed: 150.,
+            thrust_accel: 50.,
+            turn_rate: 4.,
+            max_rotation_speed: f32::to_radians(70.),
         },
-        Rocket {
-            speed: 0.0,
-            max_speed: 150.0,
-            velocity: Vec2::ZERO,
-            radius_collision: 50.,
-            rotation_speed: f32::to_radians(0.0),
-            controls: RocketControls {
-                rotate_left: KeyCode::KeyA,
-                rotate_right: KeyCode::KeyD,
-                accelerate: KeyCode::KeyS,
-            },
-            spawn_key: KeyCode::KeyW,
-            cooldown: Duration::from_millis(500),
-            last_shot_time: Duration::ZERO,
+        1 => PlayerSpec {
+            texture: "Rocket1-v3.png",
+            scale: 0.144,
+            tint: Color::WHITE,
+            color: Color::srgb(1., 0.65, 0.),
+            fire_cooldown: 0.3,
+            max_speed: 150.,
+            thrust_accel: 50.,
+            turn_rate: 4.,
+            max_rotation_speed: f32::to_radians(70.),
         },
-    ));
+        2 => PlayerSpec {
+            texture: "Rocket1-v2.png",
+            scale: 0.12,
+            tint: Color::srgb(0.6, 1., 0.6),
+            color: Color::srgb(0.4, 0.9, 0.4),
+            fire_cooldown: 0.5,
+            max_speed: 150.,
+            thrust_accel: 50.,
+            turn_rate: 4.,
+            max_rotation_speed: f32::to_radians(70.),
+        },
+        _ => PlayerSpec {
+            texture: "Rocket1-v3.png",
+            scale: 0.144,
+            tint: Color::srgb(1., 0.6, 1.),
+            color: Color::srgb(0.9, 0.4, 0.9),
+            fire_cooldown: 0.3,
+            max_speed: 150.,
+            thrust_accel: 50.,
+            turn_rate: 4.,
+            max_rotation_speed: f32::to_radians(70.),
+        },
+    }
+}
+
+/// Number of players in a match, from 2 to `MAX_PLAYERS`, set with `--players <n>`
+/// or the number keys on the menu
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct PlayerCount(pub usize);
+
+impl Default for PlayerCount {
+    fn default() -> Self {
+        Self(2)
+    }
+}
+
+impl PlayerCount {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let requested = args
+            .windows(2)
+            .find(|pair| pair[0] == "--players")
+            .and_then(|pair| pair[1].parse::<usize>().ok());
+        match requested {
+            Some(count) => Self(count.clamp(2, MAX_PLAYERS)),
+            None => Self::default(),
+        }
+    }
+}
+
+/// The number keys 2 to 4 pick the number of players on the menu, it holds for the next match
+pub fn choose_player_count(keys: Res<ButtonInput<KeyCode>>, mut players: ResMut<PlayerCount>) {
+    let choices = [(KeyCode::Digit2, 2), (KeyCode::Digit3, 3), (KeyCode::Digit4, 4)];
+    for (key, count) in choices {
+        if keys.just_pressed(key) && count <= MAX_PLAYERS && players.0 != count {
+            players.0 = count;
+            info!("{count} players in the next match");
+        }
+    }
+}
+
+/// Where `player` starts out of `count` players, facing the sun from an even
+/// share of the ellipse around it
+pub fn start_position(player: usize, count: usize) -> Vec2 {
+    let share = std::f32::consts::TAU * player as f32 / count as f32;
+    let angle = f32::to_radians(FIRST_SPAWN_ANGLE) + share;
+    Vec2::new(angle.cos(), angle.sin()) * SPAWN_RADIUS
+}
+
+/// Velocity that puts a rocket at `position` into orbit around a `sun` pulling
+/// with `g_force`, anticlockwise. An `eccentricity` of 0 gives the circular
+/// orbit, `v = sqrt(g_force / r)`, one between 0 and 1 starts the rocket at the
+/// closest point of an ellipse that swings further out, and 1 or more escapes.
+pub fn orbit_velocity(position: Vec2, sun: Vec2, g_force: f64, eccentricity: f32) -> Vec2 {
+    let offset = position - sun;
+    let distance = offset.length();
+    if distance == 0. {
+        return Vec2::ZERO;
+    }
+    let speed = (g_force * (1. + eccentricity as f64) / distance as f64).sqrt() as f32;
+    offset.perp() / distance * speed
+}
+
+/// Circular orbit around whichever of the `suns` is closest to `position`,
+/// at rest without any
+pub fn orbit_nearest_sun(position: Vec2, suns: &[Vec2], g_force: f64) -> Vec2 {
+    suns.iter()
+        .min_by(|a, b| a.distance_squared(position).total_cmp(&b.distance_squared(position)))
+        .map_or(Vec2::ZERO, |sun| orbit_velocity(position, *sun, g_force, 0.))
+}
+
+/// Spawns a player's rocket at `position` flying at `velocity`, facing the sun,
+/// and returns it
+pub fn spawn_rocket(
+    commands: &mut Commands,
+    bindings: &KeyBindings,
+    player: usize,
+    position: Vec2,
+    velocity: Vec2,
+) -> Entity {
+    let spec = player_spec(player);
+    let keys = bindings.keys(player);
+    let direction = Vec2::ZERO - position;
+    let angle = direction.y.atan2(direction.x) - std::f32::consts::FRAC_PI_2;
+
     commands.spawn((
         SpriteBundle {
-            texture: rocket_image_b,
-            transform: rocket_b_pos,
+            sprite: Sprite {
+                color: spec.tint,
+                ..default()
+            },
+            transform: Transform {
+                translation: position.extend(1.),
+                scale: Vec3::splat(spec.scale),
+                rotation: Quat::from_rotation_z(angle),
+            },
             ..default()
         },
         Rocket {
+            player,
+            color: spec.color,
             speed: 0.0,
-            max_speed: 150.0,
+            base_scale: spec.scale,
+            max_speed: spec.max_speed,
+            thrust_accel: spec.thrust_accel,
+            brake_decel: 150.,
+            max_reverse_speed: 0.,
+            turn_rate: spec.turn_rate,
+            max_rotation_speed: spec.max_rotation_speed,
+            rotation_damping: f32::to_radians(150.),
+            velocity,
+            knockback: Vec2::ZERO,
             radius_collision: 50.,
-            velocity: Vec2::ZERO,
+            hitbox: Hitbox {
+                half_extents: ROCKET_HALF_EXTENTS,
+            },
+            health: MAX_HEALTH,
+            shielded: false,
+            shield_energy: MAX_SHIELD_ENERGY,
+            max_shield_energy: MAX_SHIELD_ENERGY,
+            shield_drain: 40.,
+            shield_recharge: 10.,
+            shield_up: false,
+            last_hit_by: None,
             rotation_speed: f32::to_radians(0.0),
             controls: RocketControls {
-                rotate_left: KeyCode::KeyJ,
-                rotate_right: KeyCode::KeyL,
-                accelerate: KeyCode::KeyK,
+                rotate_left: keys.rotate_left,
+                rotate_right: keys.rotate_right,
+                accelerate: keys.accelerate,
+                reload: keys.reload,
+                cycle_weapon: keys.cycle_weapon,
+                boost: keys.boost,
+                brake: keys.brake,
+                hyperspace: keys.hyperspace,
+                shield: keys.shield,
+                gamepad: None,
+                pad: PadBindings::default(),
             },
-            spawn_key: KeyCode::KeyI,
-            cooldown: Duration::from_millis(300),
-            last_shot_time: Duration::ZERO
+            spawn_key: keys.fire,
+            fire_cooldown: spec.fire_cooldown,
+            // Ready to fire straight away
+            last_fired: spec.fire_cooldown,
+            rapid_fire: 0.0,
+            weapon: WeaponKind::default(),
+            mouse_aim: false,
+            ammo: MAX_AMMO,
+            max_ammo: MAX_AMMO,
+            reload_time: 1.5,
+            reload_progress: 0.0,
+            fuel: MAX_FUEL,
+            max_fuel: MAX_FUEL,
+            fuel_drain: 50.,
+            fuel_regen: 15.,
+            min_boost_fuel: 25.,
+            boosting: false,
+            boost_locked: false,
         },
-    ));
-}
-
-pub fn clip_rockets(mut query: Query<&mut Transform, With<Rocket>>) {
-    let mut pos: Vec<Mut<Transform>> = query.iter_mut().collect();
-    for i in 0..pos.len() {
-        let transform = &mut pos[i];
-        if transform.translation[0] < -W_CLIP || transform.translation[0] > W_CLIP {
-            transform.translation = Vec3::new(
-                -transform.translation[0],
-                transform.translation[1],
-                transform.translation[2],
-            );
-        }
-        if transform.translation[1] < -H_CLIP || transform.translation[1] > H_CLIP {
-            transform.translation = Vec3::new(
-                transform.translation[0],
-                -transform.translation[1],
-                transform.translation[2],
-            );
+        // Half of `radius_collision`, the rest belongs to whatever it touches
+        Collider { radius: 25. },
+        RocketInput::default(),
+        SlingshotTracker::default(),
+        MotionTrail::default(),
+        ScreenWrap,
+    ))
+    .with_children(|parent| spawn_thruster_flame(parent, spec.scale))
+    .id()
+}
+
+/// A player's rocket as `spawn_rocket` makes it, for tests that drive it by hand
+#[cfg(test)]
+pub fn test_rocket(player: usize) -> Rocket {
+    let mut world = World::new();
+    let bindings = KeyBindings::default();
+    let position = Vec2::new(300., 0.);
+    let entity = spawn_rocket(&mut world.commands(), &bindings, player, position, Vec2::ZERO);
+    world.flush();
+    world.get::<Rocket>(entity).cloned().unwrap()
+}
+
+/// Reads each rocket's keys, merged with its gamepad when one is bound
+pub fn read_player_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    seed_input: Res<SeedInput>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    button_axes: Res<Axis<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut rockets: Query<(&Rocket, &mut RocketInput), Without<AiController>>,
+) {
+    // The open seed input takes the keyboard, the number keys of player 4
+    // would fly the rocket while typing otherwise
+    let no_keys = ButtonInput::default();
+    let keys = if seed_input.active { &no_keys } else { &*keys };
+    for (rocket, mut input) in rockets.iter_mut() {
+        let mut next = RocketInput {
+            thrust: keys.pressed(rocket.controls.accelerate),
+            rotate_left: keys.pressed(rocket.controls.rotate_left),
+            rotate_right: keys.pressed(rocket.controls.rotate_right),
+            fire: keys.just_pressed(rocket.spawn_key),
+            reload: keys.pressed(rocket.controls.reload),
+            cycle_weapon: keys.just_pressed(rocket.controls.cycle_weapon),
+            boost: keys.pressed(rocket.controls.boost),
+            brake: rocket.controls.brake.is_some_and(|key| keys.pressed(key)),
+            hyperspace: rocket.controls.hyperspace.is_some_and(|key| keys.just_pressed(key)),
+            shield: rocket.controls.shield.is_some_and(|key| keys.pressed(key)),
+            // Only moves while the cursor is over the window
+            aim: input.aim,
+            analog: None,
+        };
+        if let Some(gamepad) = rocket.controls.gamepad {
+            let pad = gamepad_input(gamepad, &rocket.controls.pad, &buttons, &button_axes, &axes);
+            // A held key flies at full strength over a half pushed stick
+            let keys_steer = next.thrust || next.rotate_left || next.rotate_right;
+            if !keys_steer {
+                next.analog = pad.analog;
+            }
+            next.thrust |= pad.thrust;
+            next.rotate_left |= pad.rotate_left;
+            next.rotate_right |= pad.rotate_right;
+            next.fire |= pad.fire;
+            next.reload |= pad.reload;
+            next.cycle_weapon |= pad.cycle_weapon;
+            next.boost |= pad.boost;
+            next.brake |= pad.brake;
+            next.hyperspace |= pad.hyperspace;
+            next.shield |= pad.shield;
+        }
+        // One-frame presses are held until a fixed step has seen them, frames
+        // without a step would drop them otherwise
+        next.fire |= input.fire;
+        next.cycle_weapon |= input.cycle_weapon;
+        next.hyperspace |= input.hyperspace;
+        *input = next;
+    }
+}
+
+/// Clears the one-frame presses at the end of every fixed step
+pub fn clear_presses(mut inputs: Query<&mut RocketInput>) {
+    for mut input in inputs.iter_mut() {
+        input.fire = false;
+        input.cycle_weapon = false;
+        input.hyperspace = false;
+    }
+}
+
+pub fn add_rockets(
+    mut commands: Commands,
+    bindings: Res<KeyBindings>,
+    players: Res<PlayerCount>,
+    layout: Res<SunLayout>,
+    config: Res<PhysicsConfig>,
+) {
+    spawn_starting_rockets(&mut commands, &bindings, &players, &layout, &config);
+}
+
+/// Every player's rocket at its start position, circling the sun, used whenever
+/// a match or a round begins
+pub fn spawn_starting_rockets(
+    commands: &mut Commands,
+    bindings: &KeyBindings,
+    players: &PlayerCount,
+    layout: &SunLayout,
+    config: &PhysicsConfig,
+) {
+    for player in 0..players.0 {
+        let start = start_position(player, players.0);
+        let velocity = orbit_nearest_sun(start, &layout.0, config.g_force);
+        spawn_rocket(commands, bindings, player, start, velocity);
+    }
+}
+
+/// Loads the sprite of every new rocket. Spawning leaves the texture to this, so
+/// the simulation also runs without an asset server.
+pub fn attach_rocket_textures(
+    asset_server: Res<AssetServer>,
+    mut rockets: Query<(&Rocket, &mut Handle<Image>), Added<Rocket>>,
+) {
+    for (rocket, mut texture) in rockets.iter_mut() {
+        *texture = asset_server.load(player_spec(rocket.player).texture);
+    }
+}
+
+/// What happens to a rocket that leaves the arena
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BoundaryMode {
+    // Held at the window edge
+    Clamp,
+    // Carried over to the opposite edge with its momentum
+    #[default]
+    Wrap,
+    // Destroyed once past the wrap line, asteroids still wrap
+    Death,
+}
+
+pub fn toggle_boundary_mode(keys: Res<ButtonInput<KeyCode>>, mut mode: ResMut<BoundaryMode>) {
+    if keys.just_pressed(KeyCode::F5) {
+        *mode = match *mode {
+            BoundaryMode::Clamp => BoundaryMode::Wrap,
+            BoundaryMode::Wrap => BoundaryMode::Death,
+            BoundaryMode::Death => BoundaryMode::Clamp,
+        };
+        info!("Rockets now {:?} at the edges", *mode);
+    }
+}
+
+/// What happens when two rockets run into each other
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollisionMode {
+    // Both rockets are destroyed, unless shielded
+    #[default]
+    Destroy,
+    // The rockets swap momentum along the line between them and take some damage
+    Bounce,
+}
+
+pub fn toggle_collision_mode(keys: Res<ButtonInput<KeyCode>>, mut mode: ResMut<CollisionMode>) {
+    if keys.just_pressed(KeyCode::F12) {
+        *mode = match *mode {
+            CollisionMode::Destroy => CollisionMode::Bounce,
+            CollisionMode::Bounce => CollisionMode::Destroy,
+        };
+        info!("Rocket collisions now {:?}", *mode);
+    }
+}
+
+/// Marks what `clip_to_arena` keeps on the playfield: rockets, asteroids and bullets
+#[derive(Component)]
+pub struct ScreenWrap;
+
+/// Keeps everything marked `ScreenWrap` in the arena. Only the translation is
+/// touched, so velocity and rotation carry through a wrap, and both axes are
+/// handled in the same frame when a rocket leaves through a corner. Bullets
+/// only wrap, in the other modes `expire_bullets` removes them at the edge.
+#[allow(clippy::type_complexity)]
+pub fn clip_to_arena(
+    mode: Res<BoundaryMode>,
+    arena: Res<Arena>,
+    mut destroyed: EventWriter<RocketDestroyed>,
+    mut query: Query<(Entity, &mut Transform, Option<&mut Rocket>, Has<Bullet>), With<ScreenWrap>>,
+) {
+    for (entity, mut transform, rocket, is_bullet) in query.iter_mut() {
+        if is_bullet && *mode != BoundaryMode::Wrap {
+            continue;
+        }
+        let position = &mut transform.translation;
+        match (*mode, rocket) {
+            (BoundaryMode::Clamp, rocket) => {
+                let x = position.x.clamp(-arena.half_width, arena.half_width);
+                let y = position.y.clamp(-arena.half_height, arena.half_height);
+                // A coasting rocket would stay pressed against the edge otherwise
+                if let Some(mut rocket) = rocket {
+                    if x != position.x {
+                        rocket.velocity.x = 0.;
+                    }
+                    if y != position.y {
+                        rocket.velocity.y = 0.;
+                    }
+                }
+                position.x = x;
+                position.y = y;
+            }
+            (BoundaryMode::Wrap, _) | (BoundaryMode::Death, None) => {
+                position.x = wrap(position.x, arena.w_clip);
+                position.y = wrap(position.y, arena.h_clip);
+            }
+            (BoundaryMode::Death, Some(rocket)) => {
+                if arena.contains(position.truncate()) {
+                    continue;
+                }
+                // `destroy_rockets` removes it early in the next step, before
+                // this runs again, so the rocket still only dies once
+                destroyed.send(RocketDestroyed {
+                    entity,
+                    player: rocket.player,
+                    position: *position,
+                    velocity: rocket.velocity,
+                    color: rocket.color,
+                    cause: DestroyCause::Boundary,
+                    killer: rocket.last_hit_by,
+                });
+            }
+        }
+    }
+}
+
+fn wrap(value: f32, bound: f32) -> f32 {
+    if value > bound {
+        value - 2. * bound
+    } else if value < -bound {
+        value + 2. * bound
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage_is_taken_off_the_health() {
+        let mut rocket = test_rocket(0);
+        assert!(!rocket.take_damage(30.));
+        assert_eq!(rocket.health, MAX_HEALTH - 30.);
+        assert!(!rocket.take_damage(MAX_HEALTH - 30. - 1.));
+        assert_eq!(rocket.health, 1.);
+        // Never below zero, however big the hit
+        assert!(rocket.take_damage(50.));
+        assert_eq!(rocket.health, 0.);
+    }
+
+    #[test]
+    fn a_shield_takes_the_lethal_hit() {
+        let mut rocket = test_rocket(0);
+        rocket.shielded = true;
+        // Hits that leave the rocket alive don't use it up
+        assert!(!rocket.take_damage(40.));
+        assert!(rocket.shielded);
+        assert!(!rocket.take_damage(MAX_HEALTH));
+        assert!(!rocket.shielded);
+        assert_eq!(rocket.health, MAX_HEALTH - 40.);
+        assert!(rocket.take_damage(MAX_HEALTH));
+    }
+
+    #[test]
+    fn boosting_drains_the_fuel_and_resting_refills_it() {
+        let mut rocket = test_rocket(0);
+        // Quarter second frames add up exactly
+        let delta = 0.25;
+        for _ in 0..4 {
+            assert!(rocket.update_boost(delta, true));
+        }
+        assert_eq!(rocket.fuel, MAX_FUEL - rocket.fuel_drain);
+        for _ in 0..4 {
+            assert!(!rocket.update_boost(delta, false));
+        }
+        assert_eq!(rocket.fuel, MAX_FUEL - rocket.fuel_drain + rocket.fuel_regen);
+        // Refilling stops at a full tank
+        for _ in 0..40 {
+            rocket.update_boost(delta, false);
+        }
+        assert_eq!(rocket.fuel, MAX_FUEL);
+    }
+
+    #[test]
+    fn an_empty_tank_waits_for_the_minimum_fuel() {
+        let mut rocket = test_rocket(0);
+        let delta = 0.25;
+        let steps = (MAX_FUEL / (rocket.fuel_drain * delta)) as usize;
+        for _ in 0..steps {
+            assert!(rocket.update_boost(delta, true));
+        }
+        assert_eq!(rocket.fuel, 0.);
+        // Still held, but dry until `min_boost_fuel` has come back
+        while rocket.fuel < rocket.min_boost_fuel {
+            assert!(!rocket.update_boost(delta, true));
         }
+        assert!(rocket.update_boost(delta, true));
     }
 }
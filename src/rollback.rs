@@ -0,0 +1,483 @@
+use bevy::{app::FixedMain, prelude::*};
+use std::collections::VecDeque;
+
+use crate::bindings::KeyBindings;
+use crate::bullet::{Bullet, Missile};
+use crate::collision::Collider;
+use crate::game_state::MatchTimer;
+use crate::match_log::{LogMark, MatchLog};
+use crate::respawn::{Lives, RespawnQueue, SpawnProtection};
+use crate::rng::GameRng;
+use crate::rocket::{spawn_rocket, Rocket, RocketInput, ScreenWrap, MAX_PLAYERS};
+use crate::score::MatchState;
+use crate::slingshot::SlingshotTracker;
+use crate::time_scale::{HitStop, SlowMotion, TimeScale};
+
+// Fixed steps kept, an input arriving later than this can no longer be rolled back to
+const ROLLBACK_STEPS: usize = 30;
+
+/// A rocket and the parts of its entity that change how the match goes
+#[derive(Clone)]
+struct SavedRocket {
+    rocket: Rocket,
+    transform: Transform,
+    protection: Option<SpawnProtection>,
+    slingshot: SlingshotTracker,
+}
+
+/// Clocks ticked on every fixed step, the match timer and the slow motion and
+/// hit-stop after a kill
+#[derive(Clone)]
+struct Clocks {
+    timer: MatchTimer,
+    scale: TimeScale,
+    slow_motion: SlowMotion,
+    hit_stop: HitStop,
+}
+
+/// Rockets, bullets and the score at the start of a fixed step, and the inputs
+/// the step ran with
+struct Frame {
+    tick: u64,
+    rockets: Vec<SavedRocket>,
+    bullets: Vec<(Bullet, Option<Missile>, Collider, Transform)>,
+    rng: GameRng,
+    // Put back too, or a kill inside the window would count again when re-simulated
+    match_state: MatchState,
+    lives: Lives,
+    respawns: RespawnQueue,
+    clocks: Clocks,
+    log: Option<LogMark>,
+    inputs: [RocketInput; MAX_PLAYERS],
+    // Inputs that were received rather than guessed
+    confirmed: [bool; MAX_PLAYERS],
+}
+
+/// The last fixed steps of a match whose remote inputs arrive late. Remote
+/// players fly on a guess, their last received input held on, and when the
+/// real input turns out different `confirm_input` rolls the rockets and bullets
+/// back to its step and steps them forward again. Rockets, bullets, `GameRng`,
+/// the score, lives, respawns, the match clocks and the match log are restored,
+/// asteroids and power-ups carry on, and explosions and sounds play again.
+#[derive(Resource)]
+pub struct Rollback {
+    frames: VecDeque<Frame>,
+    next_tick: u64,
+    // Players whose inputs come from elsewhere, the others are taken as they are
+    remote: [bool; MAX_PLAYERS],
+    // Newest received input of each remote player and its step
+    last_confirmed: [Option<(u64, RocketInput)>; MAX_PLAYERS],
+    // Frame stepped again while re-simulating
+    replaying: Option<usize>,
+}
+
+impl Rollback {
+    pub fn new(remote: [bool; MAX_PLAYERS]) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(ROLLBACK_STEPS),
+            next_tick: 0,
+            remote,
+            last_confirmed: [None; MAX_PLAYERS],
+            replaying: None,
+        }
+    }
+
+    /// Step the next fixed step will be saved as
+    pub fn next_tick(&self) -> u64 {
+        self.next_tick
+    }
+
+    /// Input `player` is assumed to hold until its real one arrives. One-frame
+    /// presses aren't repeated.
+    fn guess(&self, player: usize) -> RocketInput {
+        let mut input = self.last_confirmed[player].map_or_else(default, |(_, input)| input);
+        input.fire = false;
+        input.cycle_weapon = false;
+        input.hyperspace = false;
+        input
+    }
+}
+
+/// Saves the rockets, bullets and score before anything else runs in a fixed
+/// step and gives remote players their guessed input. While re-simulating it
+/// hands out the inputs kept for the step instead.
+#[allow(clippy::too_many_arguments)]
+pub fn save_frame(
+    mut rollback: ResMut<Rollback>,
+    rng: Res<GameRng>,
+    match_state: Res<MatchState>,
+    lives: Res<Lives>,
+    respawns: Res<RespawnQueue>,
+    clocks: (Res<MatchTimer>, Res<TimeScale>, Res<SlowMotion>, Res<HitStop>),
+    log: Option<Res<MatchLog>>,
+    mut rockets: Query<(
+        &Rocket,
+        &Transform,
+        Option<&SpawnProtection>,
+        &SlingshotTracker,
+        &mut RocketInput,
+    )>,
+    bullets: Query<(&Bullet, Option<&Missile>, &Collider, &Transform)>,
+) {
+    let rollback = &mut *rollback;
+    let (tick, mut inputs, confirmed) = match rollback.replaying {
+        Some(index) => {
+            let frame = &rollback.frames[index];
+            (frame.tick, frame.inputs, frame.confirmed)
+        }
+        None => (
+            rollback.next_tick,
+            [RocketInput::default(); MAX_PLAYERS],
+            rollback.remote.map(|remote| !remote),
+        ),
+    };
+    for (rocket, .., mut input) in rockets.iter_mut() {
+        let player = rocket.player;
+        if rollback.replaying.is_some() {
+            *input = inputs[player];
+        } else {
+            if rollback.remote[player] {
+                *input = rollback.guess(player);
+            }
+            inputs[player] = *input;
+        }
+    }
+    let frame = Frame {
+        tick,
+        rockets: rockets
+            .iter()
+            .map(|(rocket, transform, protection, slingshot, _)| SavedRocket {
+                rocket: rocket.clone(),
+                transform: *transform,
+                protection: protection.cloned(),
+                slingshot: slingshot.clone(),
+            })
+            .collect(),
+        bullets: bullets
+            .iter()
+            .map(|(bullet, missile, collider, transform)| {
+                (bullet.clone(), missile.cloned(), *collider, *transform)
+            })
+            .collect(),
+        rng: rng.clone(),
+        match_state: match_state.clone(),
+        lives: lives.clone(),
+        respawns: respawns.clone(),
+        clocks: Clocks {
+            timer: clocks.0.clone(),
+            scale: clocks.1.clone(),
+            slow_motion: clocks.2.clone(),
+            hit_stop: clocks.3.clone(),
+        },
+        log: log.map(|log| log.mark()),
+        inputs,
+        confirmed,
+    };
+    match rollback.replaying {
+        Some(index) => rollback.frames[index] = frame,
+        None => {
+            if rollback.frames.len() == ROLLBACK_STEPS {
+                rollback.frames.pop_front();
+            }
+            rollback.frames.push_back(frame);
+            rollback.next_tick += 1;
+        }
+    }
+}
+
+/// Takes the real input `player` had on step `tick`. When it differs from what
+/// the step and the guesses after it ran with, the rockets and bullets are put
+/// back as they were before the step and every step since is run again.
+/// Returns false for a step too old to roll back to.
+pub fn confirm_input(world: &mut World, tick: u64, player: usize, input: RocketInput) -> bool {
+    let mut rollback = world.resource_mut::<Rollback>();
+    if rollback.last_confirmed[player].is_none_or(|(last, _)| tick > last) {
+        rollback.last_confirmed[player] = Some((tick, input));
+    }
+    let Some(first) = rollback.frames.front().map(|frame| frame.tick) else {
+        return true;
+    };
+    if tick < first {
+        return false;
+    }
+    let start = (tick - first) as usize;
+    let guess = rollback.guess(player);
+    let mut changed = false;
+    for (index, frame) in rollback.frames.iter_mut().enumerate().skip(start) {
+        let expected = if index == start {
+            frame.confirmed[player] = true;
+            input
+        } else if frame.confirmed[player] {
+            break;
+        } else {
+            guess
+        };
+        changed |= frame.inputs[player] != expected;
+        frame.inputs[player] = expected;
+    }
+    if changed {
+        restore(world, start);
+        resimulate(world, start);
+    }
+    true
+}
+
+/// Puts the rockets, bullets, `GameRng`, score and clocks back as they were in a
+/// saved frame. Rockets destroyed since are spawned again, bullets all are.
+fn restore(world: &mut World, index: usize) {
+    let frame = &world.resource::<Rollback>().frames[index];
+    let rockets = frame.rockets.clone();
+    let bullets = frame.bullets.clone();
+    let rng = frame.rng.clone();
+    let match_state = frame.match_state.clone();
+    let lives = frame.lives.clone();
+    let respawns = frame.respawns.clone();
+    let clocks = frame.clocks.clone();
+    let log = frame.log;
+    *world.resource_mut::<GameRng>() = rng;
+    *world.resource_mut::<MatchState>() = match_state;
+    *world.resource_mut::<Lives>() = lives;
+    *world.resource_mut::<RespawnQueue>() = respawns;
+    *world.resource_mut::<MatchTimer>() = clocks.timer;
+    *world.resource_mut::<TimeScale>() = clocks.scale;
+    *world.resource_mut::<SlowMotion>() = clocks.slow_motion;
+    *world.resource_mut::<HitStop>() = clocks.hit_stop;
+    if let (Some(mark), Some(mut log)) = (log, world.get_resource_mut::<MatchLog>()) {
+        log.rewind(mark);
+    }
+
+    let existing: Vec<(Entity, usize)> = world
+        .query::<(Entity, &Rocket)>()
+        .iter(world)
+        .map(|(entity, rocket)| (entity, rocket.player))
+        .collect();
+    for &(entity, player) in &existing {
+        if !rockets.iter().any(|saved| saved.rocket.player == player) {
+            world.entity_mut(entity).despawn_recursive();
+        }
+    }
+    for saved in rockets {
+        let SavedRocket {
+            rocket,
+            transform,
+            protection,
+            slingshot,
+        } = saved;
+        let found = existing.iter().find(|(_, player)| *player == rocket.player);
+        let entity = match found {
+            Some(&(entity, _)) => entity,
+            None => world.resource_scope(|world, bindings: Mut<KeyBindings>| {
+                let position = transform.translation.truncate();
+                let entity = spawn_rocket(
+                    &mut world.commands(),
+                    &bindings,
+                    rocket.player,
+                    position,
+                    rocket.velocity,
+                );
+                world.flush();
+                entity
+            }),
+        };
+        let mut entity = world.entity_mut(entity);
+        entity.insert((rocket, transform, slingshot));
+        match protection {
+            Some(protection) => entity.insert(protection),
+            None => entity.remove::<SpawnProtection>(),
+        };
+    }
+
+    let stale: Vec<Entity> = world
+        .query_filtered::<Entity, With<Bullet>>()
+        .iter(world)
+        .collect();
+    for entity in stale {
+        world.entity_mut(entity).despawn_recursive();
+    }
+    for (bullet, missile, collider, transform) in bullets {
+        let mut entity = world.spawn((
+            SpriteBundle {
+                transform,
+                ..default()
+            },
+            bullet,
+            collider,
+            ScreenWrap,
+        ));
+        if let Some(missile) = missile {
+            entity.insert(missile);
+        }
+    }
+}
+
+/// Runs the fixed steps from the frame at `start` up to the newest one again,
+/// saving each of them over the old frame
+fn resimulate(world: &mut World, start: usize) {
+    let end = world.resource::<Rollback>().frames.len();
+    for index in start..end {
+        world.resource_mut::<Rollback>().replaying = Some(index);
+        *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+        world.run_schedule(FixedMain);
+    }
+    world.resource_mut::<Rollback>().replaying = None;
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asteroid::AsteroidSettings;
+    use crate::game_state::GameState;
+    use crate::gameplay::headless_app;
+    use crate::powerup::PowerupSpawner;
+    use crate::settings::GameSettings;
+
+    const STEPS: u64 = 90;
+    // Steps a remote input takes to arrive
+    const LATENCY: u64 = 6;
+    const REMOTE_PLAYER: usize = 1;
+    const DIVE_START: f32 = 150.;
+
+    /// Input of `player` on step `tick`: turns one way then the other with the
+    /// odd shot, so most guesses turn out wrong at some point
+    fn scripted_input(player: usize, tick: u64) -> RocketInput {
+        let phase = (tick / 10 + player as u64) % 4;
+        RocketInput {
+            thrust: phase != 3,
+            rotate_left: phase == 1,
+            rotate_right: phase == 2,
+            fire: tick.is_multiple_of(15),
+            ..default()
+        }
+    }
+
+    /// Positions, velocities and top speeds of the rockets, by player, the
+    /// bullets, and the score and lives
+    #[derive(PartialEq, Debug)]
+    struct Outcome {
+        rockets: Vec<(usize, Vec3, Vec2, f32)>,
+        bullets: Vec<(Vec3, Vec2)>,
+        kills: [u32; MAX_PLAYERS],
+        lives: [u32; MAX_PLAYERS],
+    }
+
+    /// Where everything is after `STEPS` steps, with the remote player's
+    /// inputs `latency` steps late. `setup` runs once the rockets are out.
+    fn run(latency: u64, setup: fn(&mut World)) -> Outcome {
+        let mut app = headless_app();
+        let mut remote = [false; MAX_PLAYERS];
+        remote[REMOTE_PLAYER] = latency > 0;
+        app.insert_resource(AsteroidSettings {
+            count: 0,
+            ..default()
+        })
+        .insert_resource(PowerupSpawner {
+            kinds: Vec::new(),
+            ..default()
+        })
+        .insert_resource(GameRng::new(7))
+        // A rocket put back by a rollback mustn't have its top speed scaled twice
+        .insert_resource(GameSettings {
+            max_speed: 200.,
+            ..default()
+        })
+        .insert_resource(Rollback::new(remote));
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        // Spawns the rockets
+        app.update();
+        setup(app.world_mut());
+
+        let mut in_flight = VecDeque::new();
+        for _ in 0..STEPS {
+            let tick = app.world().resource::<Rollback>().next_tick();
+            let world = app.world_mut();
+            for (rocket, mut input) in world.query::<(&Rocket, &mut RocketInput)>().iter_mut(world) {
+                *input = scripted_input(rocket.player, tick);
+            }
+            app.update();
+            assert_eq!(app.world().resource::<Rollback>().next_tick(), tick + 1);
+
+            if latency > 0 {
+                in_flight.push_back((tick, scripted_input(REMOTE_PLAYER, tick)));
+            }
+            while let Some(&(sent, input)) = in_flight.front() {
+                if sent + latency > tick {
+                    break;
+                }
+                assert!(confirm_input(app.world_mut(), sent, REMOTE_PLAYER, input));
+                in_flight.pop_front();
+            }
+        }
+        // The inputs still on their way arrive after the last step
+        for (sent, input) in in_flight {
+            assert!(confirm_input(app.world_mut(), sent, REMOTE_PLAYER, input));
+        }
+
+        let world = app.world_mut();
+        let mut rockets: Vec<_> = world
+            .query::<(&Rocket, &Transform)>()
+            .iter(world)
+            .map(|(rocket, transform)| {
+                (rocket.player, transform.translation, rocket.velocity, rocket.max_speed)
+            })
+            .collect();
+        rockets.sort_by_key(|(player, ..)| *player);
+        let mut bullets: Vec<_> = world
+            .query::<(&Bullet, &Transform)>()
+            .iter(world)
+            .map(|(bullet, transform)| (transform.translation, bullet.velocity))
+            .collect();
+        bullets.sort_by(|a, b| a.0.x.total_cmp(&b.0.x).then(a.0.y.total_cmp(&b.0.y)));
+        Outcome {
+            rockets,
+            bullets,
+            kills: world.resource::<MatchState>().kills,
+            lives: world.resource::<Lives>().0,
+        }
+    }
+
+    #[test]
+    fn late_inputs_converge_to_the_on_time_match() {
+        let on_time = run(0, |_| {});
+        assert_eq!(on_time.rockets.len(), 2);
+        assert!(!on_time.bullets.is_empty());
+        assert_eq!(run(LATENCY, |_| {}), on_time);
+    }
+
+    /// Drops the local rocket into the sun from a standstill. It burns up about
+    /// a second in, inside the steps a late input rolls back over.
+    fn dive_into_the_sun(world: &mut World) {
+        let mut rockets = world.query::<(&mut Rocket, &mut Transform)>();
+        for (mut rocket, mut transform) in rockets.iter_mut(world) {
+            if rocket.player != REMOTE_PLAYER {
+                transform.translation = Vec3::new(0., -DIVE_START, transform.translation.z);
+                rocket.velocity = Vec2::ZERO;
+            }
+        }
+    }
+
+    #[test]
+    fn kills_inside_the_window_count_once() {
+        let on_time = run(0, dive_into_the_sun);
+        assert_eq!(on_time.kills.iter().sum::<u32>(), 1);
+        assert_eq!(run(LATENCY, dive_into_the_sun), on_time);
+    }
+
+    #[test]
+    fn inputs_older_than_the_buffer_are_refused() {
+        let mut app = headless_app();
+        app.insert_resource(Rollback::new([false; MAX_PLAYERS]));
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        for _ in 0..ROLLBACK_STEPS + 5 {
+            app.update();
+        }
+        let input = RocketInput::default();
+        assert!(!confirm_input(app.world_mut(), 0, REMOTE_PLAYER, input));
+        let newest = app.world().resource::<Rollback>().next_tick() - 1;
+        assert!(confirm_input(app.world_mut(), newest, REMOTE_PLAYER, input));
+    }
+}
@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::Path};
+
+/// Reads a RON file next to the game, `None` if there is none to read. An
+/// invalid file is reported and gives the defaults.
+pub fn load_ron<T: DeserializeOwned + Default>(path: &str) -> Option<T> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(parse_ron(path, &contents))
+}
+
+/// Reads the contents of the RON file at `path`, the defaults if they are invalid
+pub fn parse_ron<T: DeserializeOwned + Default>(path: &str, contents: &str) -> T {
+    ron::from_str(contents).unwrap_or_else(|err| {
+        warn!("Ignoring invalid {path}: {err}");
+        T::default()
+    })
+}
+
+/// Writes `value` to `path` as pretty RON, creating its folder if needed.
+/// Failing is only reported, the game carries on with what it has.
+pub fn save_ron<T: Serialize>(path: &str, value: &T) {
+    let result = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| {
+            let dir = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty());
+            if let Some(dir) = dir {
+                fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+            }
+            fs::write(path, contents).map_err(|err| err.to_string())
+        });
+    if let Err(err) = result {
+        warn!("Could not save {path}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+    struct Example {
+        name: String,
+        count: u32,
+    }
+
+    #[test]
+    fn saved_files_read_back() {
+        let dir = std::env::temp_dir().join(format!("spacewars-ron-{}", std::process::id()));
+        let path = dir.join("nested").join("example.ron");
+        let path = path.to_str().unwrap();
+        assert_eq!(load_ron::<Example>(path), None);
+
+        let example = Example {
+            name: "P1".into(),
+            count: 3,
+        };
+        save_ron(path, &example);
+        assert_eq!(load_ron(path), Some(example));
+
+        fs::write(path, "(name: 3)").unwrap();
+        assert_eq!(load_ron(path), Some(Example::default()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
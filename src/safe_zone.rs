@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+
+use crate::effects::spawn_explosion;
+use crate::game_state::{in_match, not_paused, GameState};
+use crate::net::runs_simulation;
+use crate::respawn::SpawnProtection;
+use crate::rocket::{Rocket, RocketDamaged};
+use crate::score::MatchState;
+use crate::sun::Sun;
+use crate::update_rocket_status;
+
+// The zone never closes in further than this past the edge of a sun, or
+// there would be nowhere left to fly
+const SUN_CLEARANCE: f32 = 150.;
+const ZONE_COLOR: Color = Color::srgba(1., 0.3, 0.3, 0.6);
+
+/// Shrinks a safe circle around the arena's centre over each round, started
+/// with `--safe-zone`
+pub struct SafeZonePlugin;
+
+impl Plugin for SafeZonePlugin {
+    fn build(&self, app: &mut App) {
+        let enabled = std::env::args().any(|arg| arg == "--safe-zone");
+        app.insert_resource(SafeZone {
+            enabled,
+            ..default()
+        })
+        .add_systems(OnEnter(GameState::Playing), reset_safe_zone)
+        .add_systems(
+            FixedUpdate,
+            (shrink_safe_zone, damage_outside_safe_zone)
+                .chain()
+                .run_if(in_match)
+                .run_if(not_paused)
+                .run_if(runs_simulation)
+                .after(update_rocket_status),
+        )
+        .add_systems(Update, draw_safe_zone.run_if(in_match));
+    }
+}
+
+/// Rockets outside `radius` burn, the circle contracts at `shrink_rate` units per
+/// second until it reaches `min_radius`
+#[derive(Resource)]
+pub struct SafeZone {
+    pub enabled: bool,
+    pub radius: f32,
+    // Radius at the start of every round
+    pub start_radius: f32,
+    pub shrink_rate: f32,
+    pub min_radius: f32,
+    // Health lost per second outside the zone
+    pub damage_per_second: f32,
+}
+
+impl Default for SafeZone {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            // Just past the arena's corners
+            radius: 640.,
+            start_radius: 640.,
+            shrink_rate: 8.,
+            min_radius: 180.,
+            damage_per_second: 20.,
+        }
+    }
+}
+
+fn reset_safe_zone(mut zone: ResMut<SafeZone>) {
+    zone.radius = zone.start_radius;
+}
+
+/// Holds still between rounds, where the zone opens back up for the next one
+fn shrink_safe_zone(
+    time: Res<Time>,
+    state: Res<MatchState>,
+    mut zone: ResMut<SafeZone>,
+    suns: Query<(&Sun, &Transform)>,
+) {
+    if !zone.enabled {
+        return;
+    }
+    if state.between_rounds() {
+        zone.radius = zone.start_radius;
+        return;
+    }
+    // Also keeps the zone clear of a sun growing in overtime
+    let floor = suns
+        .iter()
+        .map(|(sun, transform)| transform.translation.truncate().length() + sun.radius)
+        .fold(0., f32::max)
+        + SUN_CLEARANCE;
+    let min_radius = zone.min_radius.max(floor);
+    if zone.radius > min_radius {
+        zone.radius = (zone.radius - zone.shrink_rate * time.delta_seconds()).max(min_radius);
+    }
+}
+
+fn damage_outside_safe_zone(
+    mut commands: Commands,
+    time: Res<Time>,
+    zone: Res<SafeZone>,
+    mut damaged: EventWriter<RocketDamaged>,
+    mut rockets: Query<(Entity, &mut Rocket, &Transform), Without<SpawnProtection>>,
+) {
+    if !zone.enabled {
+        return;
+    }
+    for (entity, mut rocket, transform) in rockets.iter_mut() {
+        if transform.translation.truncate().length() <= zone.radius {
+            continue;
+        }
+        // Reporting every step would flash and rumble non-stop, only the deadly
+        // burn is sent
+        if rocket.take_damage(zone.damage_per_second * time.delta_seconds()) {
+            spawn_explosion(&mut commands, transform.translation, rocket.color);
+            commands.entity(entity).despawn_recursive();
+            damaged.send(RocketDamaged {
+                player: rocket.player,
+                destroyed: true,
+                attacker: rocket.last_hit_by,
+            });
+        }
+    }
+}
+
+fn draw_safe_zone(mut gizmos: Gizmos, zone: Res<SafeZone>) {
+    if zone.enabled {
+        gizmos.circle_2d(Vec2::ZERO, zone.radius, ZONE_COLOR);
+    }
+}
@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+
+use crate::bindings::KeyBindings;
+use crate::bullet::Bullet;
+use crate::game_state::GameState;
+use crate::respawn::{Lives, RespawnQueue};
+use crate::rocket::{spawn_starting_rockets, PlayerCount, Rocket, RocketDamaged, MAX_PLAYERS};
+use crate::sun::{PhysicsConfig, SunLayout};
+
+// Seconds between the deciding kill and the next round, the last explosions
+// and the slow motion play out meanwhile
+const INTERMISSION: f32 = 2.;
+// Points per round won, on top of which come the bonus points
+const ROUND_WIN_SCORE: u32 = 10;
+
+/// Sent once per round, `winner` is `None` when the last rockets died together
+#[derive(Event, Debug, Clone, Copy)]
+pub struct RoundOver {
+    pub round: u32,
+    pub winner: Option<usize>,
+}
+
+/// Progress of a best-of-N match, `wins` is indexed by `Rocket::player`
+#[derive(Resource, Clone)]
+pub struct MatchState {
+    pub round: u32,
+    pub wins: [u32; MAX_PLAYERS],
+    pub rounds_to_win: u32,
+    // Style points, e.g. for slingshots around the sun, they don't decide the match
+    pub bonus: [u32; MAX_PLAYERS],
+    // Opponents destroyed over the whole match
+    pub kills: [u32; MAX_PLAYERS],
+    // Ends the match as soon as a player reaches it, whatever the rounds say
+    pub kills_to_win: Option<u32>,
+    // Player ahead when the match timer ran out, see `MatchState::leader`
+    pub time_winner: Option<usize>,
+    // Runs while the field is cleared between two rounds
+    intermission: Option<Timer>,
+}
+
+impl Default for MatchState {
+    fn default() -> Self {
+        Self {
+            round: 1,
+            wins: [0; MAX_PLAYERS],
+            // Best of five
+            rounds_to_win: 3,
+            bonus: [0; MAX_PLAYERS],
+            kills: [0; MAX_PLAYERS],
+            kills_to_win: None,
+            time_winner: None,
+            intermission: None,
+        }
+    }
+}
+
+impl MatchState {
+    pub fn between_rounds(&self) -> bool {
+        self.intermission.is_some()
+    }
+
+    /// A fresh match, with `--kills-to-win N` setting a kill target
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let kills_to_win = args
+            .windows(2)
+            .find(|pair| pair[0] == "--kills-to-win")
+            .and_then(|pair| pair[1].parse::<u32>().ok())
+            .filter(|kills| *kills > 0);
+        Self {
+            kills_to_win,
+            ..default()
+        }
+    }
+
+    pub fn match_winner(&self) -> Option<usize> {
+        let kill_target = self.kills_to_win.unwrap_or(u32::MAX);
+        self.wins
+            .iter()
+            .position(|wins| *wins >= self.rounds_to_win)
+            .or_else(|| self.kills.iter().position(|kills| *kills >= kill_target))
+            .or(self.time_winner)
+    }
+
+    /// The one of `players` ahead on rounds won, then on kills, then on lives
+    /// left, `None` when the best of them are level on all three
+    pub fn leader(&self, players: &[usize], lives: &Lives) -> Option<usize> {
+        let standing = |player: usize| (self.wins[player], self.kills[player], lives.0[player]);
+        let best = players.iter().map(|&player| standing(player)).max()?;
+        let mut leaders = players.iter().filter(|&&player| standing(player) == best);
+        match (leaders.next(), leaders.next()) {
+            (Some(&leader), None) => Some(leader),
+            _ => None,
+        }
+    }
+
+    /// Points a player made over the match, as kept in the high scores
+    pub fn score(&self, player: usize) -> u32 {
+        self.wins[player] * ROUND_WIN_SCORE + self.bonus[player]
+    }
+}
+
+/// A round ends once at most one player is left in it, a player is out when
+/// their rocket is gone and they have no respawns left. The survivor takes the
+/// round, a round where the last players go down together is replayed without
+/// a winner.
+pub fn check_round_over(
+    rockets: Query<&Rocket>,
+    lives: Res<Lives>,
+    queue: Res<RespawnQueue>,
+    players: Res<PlayerCount>,
+    mut state: ResMut<MatchState>,
+    mut round_over: EventWriter<RoundOver>,
+) {
+    if state.between_rounds() {
+        return;
+    }
+    let mut remaining = (0..players.0).filter(|player| {
+        rockets.iter().any(|rocket| rocket.player == *player)
+            || queue.contains(*player)
+            || lives.0[*player] > 0
+    });
+    let winner = match (remaining.next(), remaining.next()) {
+        (_, Some(_)) => return,
+        (winner, None) => winner,
+    };
+
+    round_over.send(RoundOver {
+        round: state.round,
+        winner,
+    });
+    // A drawn round is replayed under the same number
+    if let Some(winner) = winner {
+        state.wins[winner] += 1;
+        state.round += 1;
+    }
+    state.intermission = Some(Timer::from_seconds(INTERMISSION, TimerMode::Once));
+}
+
+/// Once the pause between rounds is over, either ends the match or clears the
+/// field and puts every rocket back at its start position with fresh lives
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn start_next_round(
+    mut commands: Commands,
+    time: Res<Time>,
+    bindings: Res<KeyBindings>,
+    players: Res<PlayerCount>,
+    layout: Res<SunLayout>,
+    config: Res<PhysicsConfig>,
+    mut state: ResMut<MatchState>,
+    mut lives: ResMut<Lives>,
+    mut queue: ResMut<RespawnQueue>,
+    mut next_state: ResMut<NextState<GameState>>,
+    field: Query<Entity, Or<(With<Rocket>, With<Bullet>)>>,
+) {
+    let Some(timer) = state.intermission.as_mut() else {
+        return;
+    };
+    if !timer.tick(time.delta()).finished() {
+        return;
+    }
+    if state.match_winner().is_some() {
+        next_state.set(GameState::GameOver);
+        return;
+    }
+    state.intermission = None;
+    *lives = Lives::default();
+    queue.clear();
+    for entity in field.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_starting_rockets(&mut commands, &bindings, &players, &layout, &config);
+}
+
+/// Credits every destroyed rocket to whoever shot it last. A rocket that went
+/// down untouched or by its own hand in a duel still counts for the opponent,
+/// with more players nobody is credited for a self-kill.
+pub fn count_kills(
+    mut events: EventReader<RocketDamaged>,
+    players: Res<PlayerCount>,
+    mut state: ResMut<MatchState>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for event in events.read().filter(|event| event.destroyed) {
+        let opponent = (players.0 == 2).then_some(1 - event.player);
+        let attacker = event.attacker.filter(|&attacker| attacker != event.player);
+        let Some(killer) = attacker.or(opponent) else {
+            continue;
+        };
+        state.kills[killer] += 1;
+        if state.kills_to_win.is_some_and(|target| state.kills[killer] >= target) {
+            info!("Player {} reached the kill target", killer + 1);
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+pub fn log_round_over(mut events: EventReader<RoundOver>, state: Res<MatchState>) {
+    for event in events.read() {
+        match event.winner {
+            Some(winner) => {
+                info!("Player {} wins round {}, wins {:?}", winner + 1, event.round, state.wins)
+            }
+            None => info!("Round {} drawn, replaying it, wins {:?}", event.round, state.wins),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+
+use crate::game_state::GameState;
+use crate::rng::{random_seed, GameRng, NextSeed};
+
+#[derive(Component)]
+pub struct SeedText;
+
+/// Text typed (or pasted) by the player while entering a seed
+#[derive(Resource, Default)]
+pub struct SeedInput {
+    pub active: bool,
+    pub buffer: String,
+}
+
+pub fn add_seed_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.,
+                color: Color::srgba(1., 1., 1., 0.7),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+        SeedText,
+    ));
+}
+
+pub fn update_seed_text(
+    rng: Res<GameRng>,
+    input: Res<SeedInput>,
+    mut texts: Query<&mut Text, With<SeedText>>,
+) {
+    if !rng.is_changed() && !input.is_changed() {
+        return;
+    }
+    for mut text in &mut texts {
+        text.sections[0].value = if input.active {
+            format!("Seed: {}_  [Enter apply, Esc cancel]", input.buffer)
+        } else {
+            format!("Seed: {}  [F1 copy, F2 enter]", rng.seed())
+        };
+    }
+}
+
+/// F1 copies the current seed, F2 opens the seed input which accepts digits and Ctrl+V
+pub fn seed_input(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut input: ResMut<SeedInput>,
+    mut next_seed: ResMut<NextSeed>,
+    rng: Res<GameRng>,
+) {
+    if !input.active {
+        if keys.just_pressed(KeyCode::F1) {
+            copy_to_clipboard(&rng.seed().to_string());
+        }
+        if keys.just_pressed(KeyCode::F2) {
+            input.active = true;
+            input.buffer.clear();
+        }
+        return;
+    }
+
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && keys.just_pressed(KeyCode::KeyV) {
+        if let Some(pasted) = paste_from_clipboard() {
+            input.buffer = pasted.trim().to_string();
+        }
+    }
+
+    for key in keys.get_just_pressed() {
+        if let Some(digit) = digit(*key) {
+            input.buffer.push(digit);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Backspace) {
+        input.buffer.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        input.active = false;
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Enter) {
+        let seed = input.buffer.parse::<u64>().unwrap_or_else(|_| {
+            warn!("Invalid seed {:?}, using a random one", input.buffer);
+            random_seed()
+        });
+        input.active = false;
+
+        // Start the match over so all of it is driven by the new seed
+        next_seed.0 = Some(seed);
+        match state.get() {
+            // Setting the state it is already in doesn't run `OnEnter`
+            GameState::Playing => commands.add(|world: &mut World| {
+                world.run_schedule(OnEnter(GameState::Playing));
+            }),
+            _ => next_state.set(GameState::Playing),
+        }
+        info!("Restarted with seed {seed}");
+    }
+}
+
+fn digit(key: KeyCode) -> Option<char> {
+    let digit = match key {
+        KeyCode::Digit0 | KeyCode::Numpad0 => 0,
+        KeyCode::Digit1 | KeyCode::Numpad1 => 1,
+        KeyCode::Digit2 | KeyCode::Numpad2 => 2,
+        KeyCode::Digit3 | KeyCode::Numpad3 => 3,
+        KeyCode::Digit4 | KeyCode::Numpad4 => 4,
+        KeyCode::Digit5 | KeyCode::Numpad5 => 5,
+        KeyCode::Digit6 | KeyCode::Numpad6 => 6,
+        KeyCode::Digit7 | KeyCode::Numpad7 => 7,
+        KeyCode::Digit8 | KeyCode::Numpad8 => 8,
+        KeyCode::Digit9 | KeyCode::Numpad9 => 9,
+        _ => return None,
+    };
+    char::from_digit(digit, 10)
+}
+
+fn copy_to_clipboard(text: &str) {
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)) {
+        Ok(()) => info!("Copied seed {text} to the clipboard"),
+        Err(err) => warn!("Could not copy the seed: {err}"),
+    }
+}
+
+fn paste_from_clipboard() -> Option<String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|err| warn!("Could not read the clipboard: {err}"))
+        .ok()
+}
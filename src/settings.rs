@@ -0,0 +1,209 @@
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::rocket::{player_spec, Rocket};
+use crate::ron_file::{load_ron, save_ron};
+use crate::sun::PhysicsConfig;
+
+const DISPLAY_SETTINGS_PATH: &str = "display.ron";
+const GAME_SETTINGS_PATH: &str = "game.ron";
+
+// Frame caps cycled through with F10, `None` means uncapped
+const FRAME_CAPS: [Option<f32>; 4] = [None, Some(30.), Some(60.), Some(144.)];
+
+pub struct DisplaySettingsPlugin;
+
+impl Plugin for DisplaySettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DisplaySettings::load())
+            .add_systems(
+                Update,
+                (toggle_display_settings, apply_display_settings).chain(),
+            )
+            .add_systems(Last, limit_frame_rate);
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct DisplaySettings {
+    pub vsync: bool,
+    pub frame_cap: Option<f32>,
+    // Missing from files saved before it existed
+    #[serde(default)]
+    pub fullscreen: bool,
+    // Window size in pixels outside fullscreen, `None` opens it at the
+    // arena's size. The window can also be resized by hand.
+    #[serde(default)]
+    pub resolution: Option<(f32, f32)>,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            frame_cap: None,
+            fullscreen: false,
+            resolution: None,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// Reads the settings from disk, falling back to the defaults if the file is missing or invalid
+    pub fn load() -> Self {
+        load_ron(DISPLAY_SETTINGS_PATH).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        save_ron(DISPLAY_SETTINGS_PATH, self);
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        if self.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+}
+
+/// F9 toggles vsync, F10 cycles through the frame caps and F11 toggles
+/// fullscreen. Changes are persisted right away.
+fn toggle_display_settings(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<DisplaySettings>) {
+    let mut changed = false;
+
+    if keys.just_pressed(KeyCode::F9) {
+        settings.vsync = !settings.vsync;
+        changed = true;
+    }
+
+    if keys.just_pressed(KeyCode::F10) {
+        let next = FRAME_CAPS
+            .iter()
+            .position(|cap| *cap == settings.frame_cap)
+            .map_or(0, |index| (index + 1) % FRAME_CAPS.len());
+        settings.frame_cap = FRAME_CAPS[next];
+        changed = true;
+    }
+
+    if keys.just_pressed(KeyCode::F11) {
+        settings.fullscreen = !settings.fullscreen;
+        changed = true;
+    }
+
+    if changed {
+        info!(
+            "Display settings: vsync {}, frame cap {:?}, fullscreen {}",
+            settings.vsync, settings.frame_cap, settings.fullscreen
+        );
+        settings.save();
+    }
+}
+
+/// Pushes the present mode, window mode and resolution to the window live, also
+/// runs once at startup to apply the loaded file. The resolution is only set
+/// again when it changes, so toggling vsync keeps a window resized by hand.
+fn apply_display_settings(
+    settings: Res<DisplaySettings>,
+    mut applied_resolution: Local<Option<(f32, f32)>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let resolution_changed = settings.resolution != *applied_resolution;
+    *applied_resolution = settings.resolution;
+    for mut window in &mut windows {
+        window.present_mode = settings.present_mode();
+        window.mode = settings.window_mode();
+        if let Some((width, height)) = settings.resolution.filter(|_| resolution_changed) {
+            window.resolution.set(width, height);
+        }
+    }
+}
+
+/// Sleeps away whatever is left of the frame budget when a frame cap is set
+fn limit_frame_rate(settings: Res<DisplaySettings>, mut last_frame: Local<Option<Instant>>) {
+    if let (Some(cap), Some(last)) = (settings.frame_cap, *last_frame) {
+        let target = Duration::from_secs_f32(1.0 / cap);
+        let elapsed = last.elapsed();
+        if elapsed < target {
+            std::thread::sleep(target - elapsed);
+        }
+    }
+    *last_frame = Some(Instant::now());
+}
+
+/// Flight tuning and volume read from `game.ron`, next to the keys in `controls.ron`.
+/// Fields missing from the file keep their defaults.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
+pub struct GameSettings {
+    // Strength of the suns' pull, copied into `PhysicsConfig::g_force` every match
+    pub gravity: f64,
+    // Top speed of a standard rocket without the afterburner, faster and
+    // slower ships are scaled along with it
+    pub max_speed: f32,
+    // Loudness of everything from 0 to 1, and of the music on top of that
+    pub volume: f32,
+    pub music_volume: f32,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            gravity: PhysicsConfig::default().g_force,
+            max_speed: 150.,
+            volume: 0.8,
+            music_volume: 0.5,
+        }
+    }
+}
+
+impl GameSettings {
+    /// Reads the settings from disk. A missing file is written with the defaults
+    /// so there is one to edit, an invalid one is left alone.
+    pub fn load() -> Self {
+        load_ron(GAME_SETTINGS_PATH).unwrap_or_else(|| {
+            let settings = Self::default();
+            settings.save();
+            settings
+        })
+    }
+
+    pub fn save(&self) {
+        save_ron(GAME_SETTINGS_PATH, self);
+    }
+}
+
+/// Writes `game.ron` back whenever a setting is changed while the game runs
+pub fn save_game_settings(settings: Res<GameSettings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
+
+/// Scales the top speed every new rocket gets from its `PlayerSpec` by how far
+/// `GameSettings::max_speed` is from the default. Taken from the spec each
+/// time, so a rocket put back by a rollback isn't scaled twice.
+pub fn apply_max_speed_setting(
+    settings: Res<GameSettings>,
+    mut rockets: Query<&mut Rocket, Added<Rocket>>,
+) {
+    let scale = settings.max_speed / GameSettings::default().max_speed;
+    for mut rocket in rockets.iter_mut() {
+        rocket.max_speed = player_spec(rocket.player).max_speed * scale;
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+
+use crate::rocket::{Rocket, RocketInput};
+use crate::time_scale::TimeScale;
+
+pub const SHIELD_BUBBLE_COLOR: Color = Color::srgb(0.4, 0.7, 1.);
+// In world units, just inside the ring of the shield power-up
+const SHIELD_BUBBLE_RADIUS: f32 = 44.;
+// Opacity of the bubble when the shield is empty and when it is full
+const MIN_BUBBLE_ALPHA: f32 = 0.1;
+const MAX_BUBBLE_ALPHA: f32 = 0.45;
+
+/// The translucent circle drawn around its parent rocket while the shield is up
+#[derive(Component)]
+pub struct ShieldBubble;
+
+/// Raises each rocket's shield while its key is held and there is energy left
+pub fn update_shields(
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    mut rockets: Query<(&RocketInput, &mut Rocket)>,
+) {
+    let delta = time_scale.delta(&time);
+    for (input, mut rocket) in rockets.iter_mut() {
+        rocket.update_shield(delta, input.shield);
+    }
+}
+
+/// Gives every new rocket its bubble, hidden until the shield goes up
+pub fn attach_shield_bubbles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    rockets: Query<(Entity, &Rocket), Added<Rocket>>,
+) {
+    for (entity, rocket) in rockets.iter() {
+        // Undo the sprite scale so the radius is in world units
+        let scale = 1.0 / rocket.base_scale;
+        let mesh = meshes.add(Mesh::from(Circle::new(SHIELD_BUBBLE_RADIUS)));
+        let material = materials.add(ColorMaterial::from(SHIELD_BUBBLE_COLOR));
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                MaterialMesh2dBundle {
+                    mesh: mesh.into(),
+                    material,
+                    transform: Transform {
+                        translation: Vec3::new(0., 0., 0.2),
+                        scale: Vec3::splat(scale),
+                        ..default()
+                    },
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                ShieldBubble,
+            ));
+        });
+    }
+}
+
+/// Shows the bubble while the shield is up, fading out as its energy runs low
+pub fn update_shield_bubbles(
+    rockets: Query<&Rocket>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut bubbles: Query<(&Parent, &Handle<ColorMaterial>, &mut Visibility), With<ShieldBubble>>,
+) {
+    for (parent, handle, mut visibility) in bubbles.iter_mut() {
+        let Ok(rocket) = rockets.get(parent.get()) else {
+            continue;
+        };
+        if !rocket.shield_up {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+        let charge = rocket.shield_energy / rocket.max_shield_energy;
+        if let Some(material) = materials.get_mut(handle) {
+            let alpha = MIN_BUBBLE_ALPHA + (MAX_BUBBLE_ALPHA - MIN_BUBBLE_ALPHA) * charge;
+            material.color = SHIELD_BUBBLE_COLOR.with_alpha(alpha);
+        }
+    }
+}
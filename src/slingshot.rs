@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+use crate::rocket::{Rocket, RocketInput};
+use crate::score::MatchState;
+use crate::sun::{kill_distance, nearest_sun, Sun};
+
+/// Tuning for the gravity assist bonus
+#[derive(Resource)]
+pub struct SlingshotSettings {
+    // Width of the band just outside the kill distance that counts as a close pass
+    pub band: f32,
+    // Exit speed needed, as a multiple of the entry speed
+    pub min_gain: f32,
+    // Slower approaches don't count, so spiralling in gently never scores
+    pub min_entry_speed: f32,
+    // A pass that stays in the band longer than this is an orbit, not a slingshot
+    pub max_pass_time: f32,
+}
+
+impl Default for SlingshotSettings {
+    fn default() -> Self {
+        Self {
+            band: 90.,
+            min_gain: 1.25,
+            min_entry_speed: 60.,
+            max_pass_time: 4.,
+        }
+    }
+}
+
+/// Sent when a rocket leaves the sun faster than it came in
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Slingshot {
+    pub player: usize,
+    pub entry_speed: f32,
+    pub exit_speed: f32,
+}
+
+/// Per-rocket record of the current pass around the sun
+#[derive(Component, Default, Clone)]
+pub struct SlingshotTracker {
+    // Speed when the rocket entered the band, `None` while it is outside
+    entry_speed: Option<f32>,
+    time_in_band: f32,
+    thrusted: bool,
+}
+
+/// Records the speed of rockets entering the band around the nearest sun and
+/// awards a bonus to those that thrust through it and leave much faster
+pub fn track_slingshots(
+    time: Res<Time>,
+    settings: Res<SlingshotSettings>,
+    mut state: ResMut<MatchState>,
+    mut slingshots: EventWriter<Slingshot>,
+    suns: Query<(&Sun, &Transform), Without<Rocket>>,
+    mut rockets: Query<(&Rocket, &Transform, &RocketInput, &mut SlingshotTracker)>,
+) {
+    for (rocket, transform, input, mut tracker) in rockets.iter_mut() {
+        let position = transform.translation.truncate();
+        let Some((sun, sun_transform)) = nearest_sun(suns.iter(), position) else {
+            continue;
+        };
+        let distance = position.distance(sun_transform.translation.truncate());
+        let in_band = distance < kill_distance(sun, rocket) + settings.band;
+        let speed = rocket.velocity.length();
+
+        match (tracker.entry_speed, in_band) {
+            (None, true) => {
+                *tracker = SlingshotTracker {
+                    entry_speed: Some(speed),
+                    ..default()
+                };
+            }
+            (Some(_), true) => {
+                tracker.time_in_band += time.delta_seconds();
+                tracker.thrusted |= input.thrust;
+            }
+            (Some(entry_speed), false) => {
+                let assisted = tracker.thrusted
+                    && tracker.time_in_band <= settings.max_pass_time
+                    && entry_speed >= settings.min_entry_speed
+                    && speed >= entry_speed * settings.min_gain;
+                if assisted {
+                    state.bonus[rocket.player] += 1;
+                    slingshots.send(Slingshot {
+                        player: rocket.player,
+                        entry_speed,
+                        exit_speed: speed,
+                    });
+                }
+                tracker.entry_speed = None;
+            }
+            (None, false) => {}
+        }
+    }
+}
+
+pub fn log_slingshots(mut events: EventReader<Slingshot>) {
+    for event in events.read() {
+        info!(
+            "Player {} slingshot from {:.0} to {:.0}",
+            event.player + 1,
+            event.entry_speed,
+            event.exit_speed
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat},
+    },
+    sprite::{Material2d, MaterialMesh2dBundle, Mesh2dHandle},
+    window::WindowResized,
+};
+
+use crate::camera::CameraFollow;
+use crate::rng::{random_seed, GameRng};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH};
+
+// Seconds between two nebulae drifting in
+const NEBULA_INTERVAL: f32 = 25.;
+// Nebulae on screen at once
+const MAX_NEBULAE: usize = 3;
+// Side of the generated nebula texture in pixels
+const NEBULA_TEXTURE_SIZE: u32 = 64;
+// Share of the camera's movement a nebula follows, between the far stars and the arena
+const NEBULA_PARALLAX: f32 = 0.3;
+const NEBULA_COLOURS: [Color; 3] = [
+    Color::srgb(0.45, 0.25, 0.8),
+    Color::srgb(0.2, 0.45, 0.85),
+    Color::srgb(0.8, 0.3, 0.45),
+];
+
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct MovingPatternMaterial {
+    #[uniform(0)]
+    color: LinearRgba,
+    #[uniform(1)]
+    time: f32,
+    // Share of the pixels that hold a star
+    #[uniform(2)]
+    star_density: f32,
+    // How fast the stars pulse, 0 keeps them steady
+    #[uniform(3)]
+    twinkle_speed: f32,
+    // Drift of the starfield in pixels per second
+    #[uniform(4)]
+    scroll_velocity: Vec2,
+    // Stars per pixel along each axis, smaller makes bigger stars
+    #[uniform(5)]
+    star_scale: f32,
+    // Pixels the layer scrolls per world unit the camera moves
+    #[uniform(6)]
+    parallax: f32,
+    // Where the camera is looking, in world units
+    #[uniform(7)]
+    view_offset: Vec2,
+    // Opacity between the stars, only the back layer hides the clear colour
+    #[uniform(8)]
+    backdrop: f32,
+}
+
+impl Material2d for MovingPatternMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/stars_material.wgsl".into()
+    }
+}
+
+/// One layer of the starfield, the further back the denser, dimmer and slower it is
+struct StarLayer {
+    depth: f32,
+    brightness: f32,
+    star_density: f32,
+    star_scale: f32,
+    parallax: f32,
+    scroll_velocity: Vec2,
+    backdrop: f32,
+}
+
+const STAR_LAYERS: [StarLayer; 3] = [
+    StarLayer {
+        depth: -0.3,
+        brightness: 0.5,
+        star_density: 0.006,
+        star_scale: 0.35,
+        parallax: 0.05,
+        scroll_velocity: Vec2::new(1.5, 0.),
+        backdrop: 0.8,
+    },
+    StarLayer {
+        depth: -0.2,
+        brightness: 0.8,
+        star_density: 0.005,
+        star_scale: 0.2,
+        parallax: 0.15,
+        scroll_velocity: Vec2::new(3., 0.),
+        backdrop: 0.,
+    },
+    StarLayer {
+        depth: -0.1,
+        brightness: 1.,
+        star_density: 0.003,
+        star_scale: 0.12,
+        parallax: 0.35,
+        scroll_velocity: Vec2::new(6., 0.),
+        backdrop: 0.,
+    },
+];
+
+/// Marks the screen filling quads the star layers are drawn on
+#[derive(Component)]
+pub struct Background;
+
+/// A faint cloud drifting across the back of the arena
+#[derive(Component)]
+pub struct Nebula {
+    // Position before the parallax is added
+    drift: Vec2,
+    velocity: Vec2,
+    size: f32,
+}
+
+/// Texture shared by the nebulae and when the next one drifts in
+#[derive(Resource)]
+pub struct NebulaSpawner {
+    image: Handle<Image>,
+    timer: Timer,
+    // Kept apart from `GameRng` so the scenery doesn't change the match
+    rng: GameRng,
+}
+
+/// World size of a background that fills a window of the given size
+fn background_size(width: f32, height: f32) -> Vec2 {
+    let scale = (ARENA_WIDTH / width).max(ARENA_HEIGHT / height);
+    Vec2::new(width, height) * scale
+}
+
+/// Soft round blob, white so each nebula can be tinted through its sprite
+fn nebula_image() -> Image {
+    let size = NEBULA_TEXTURE_SIZE;
+    let centre = (size as f32 - 1.) / 2.;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let offset = Vec2::new(x as f32 - centre, y as f32 - centre) / centre;
+            let falloff = (1. - offset.length()).max(0.);
+            let alpha = falloff * falloff * (3. - 2. * falloff);
+            data.extend_from_slice(&[255, 255, 255, (alpha * 255.) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+pub fn add_background(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<MovingPatternMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    window: Query<&Window>,
+) {
+    let window = window.single();
+    let resolution = background_size(window.width(), window.height());
+    let mesh: Mesh2dHandle = meshes
+        .add(Mesh::from(Rectangle::from_size(resolution)))
+        .into();
+    for layer in &STAR_LAYERS {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh.clone(),
+                material: materials.add(MovingPatternMaterial {
+                    color: LinearRgba::rgb(layer.brightness, layer.brightness, layer.brightness),
+                    time: 0.0,
+                    star_density: layer.star_density,
+                    twinkle_speed: 1.5,
+                    scroll_velocity: layer.scroll_velocity,
+                    star_scale: layer.star_scale,
+                    parallax: layer.parallax,
+                    view_offset: Vec2::ZERO,
+                    backdrop: layer.backdrop,
+                }),
+                transform: Transform::from_xyz(0., 0., layer.depth),
+                ..default()
+            },
+            Background,
+        ));
+    }
+
+    commands.insert_resource(NebulaSpawner {
+        image: images.add(nebula_image()),
+        timer: Timer::from_seconds(NEBULA_INTERVAL, TimerMode::Repeating),
+        rng: GameRng::new(random_seed()),
+    });
+}
+
+/// Advances the starfield's clock, it drives the twinkle and the drift, and
+/// tells every layer where the camera is so each scrolls at its own rate
+pub fn animate_background(
+    time: Res<Time>,
+    follow: Res<CameraFollow>,
+    mut materials: ResMut<Assets<MovingPatternMaterial>>,
+) {
+    for (_, material) in materials.iter_mut() {
+        material.time = time.elapsed_seconds();
+        material.view_offset = follow.centre;
+    }
+}
+
+/// Rebuilds the background quads when the window changes size, e.g. when
+/// switching to fullscreen, so the starfield always fills the screen
+pub fn resize_background(
+    mut resized: EventReader<WindowResized>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    backgrounds: Query<&Mesh2dHandle, With<Background>>,
+) {
+    let Some(event) = resized.read().last() else {
+        return;
+    };
+    let size = background_size(event.width, event.height);
+    for handle in backgrounds.iter() {
+        meshes.insert(&handle.0, Mesh::from(Rectangle::from_size(size)));
+    }
+}
+
+/// Every so often sends a nebula in from the left or right edge, slowly
+/// crossing the arena behind the rockets
+pub fn spawn_nebulae(
+    mut commands: Commands,
+    time: Res<Time>,
+    spawner: Option<ResMut<NebulaSpawner>>,
+    nebulae: Query<(), With<Nebula>>,
+) {
+    let Some(mut spawner) = spawner else {
+        return;
+    };
+    if !spawner.timer.tick(time.delta()).just_finished() || nebulae.iter().count() >= MAX_NEBULAE {
+        return;
+    }
+
+    let rng = &mut spawner.rng;
+    let size = rng.range(300., 520.);
+    let direction = if rng.next_f32() < 0.5 { 1. } else { -1. };
+    let drift = Vec2::new(
+        -direction * (ARENA_WIDTH / 2. + size),
+        rng.range(-ARENA_HEIGHT / 2., ARENA_HEIGHT / 2.),
+    );
+    let velocity = Vec2::new(direction * rng.range(6., 12.), rng.range(-2., 2.));
+    let colour = NEBULA_COLOURS[(rng.next_u64() % NEBULA_COLOURS.len() as u64) as usize];
+    let alpha = rng.range(0.1, 0.2);
+    let image = spawner.image.clone();
+
+    commands.spawn((
+        SpriteBundle {
+            texture: image,
+            sprite: Sprite {
+                color: colour.with_alpha(alpha),
+                custom_size: Some(Vec2::splat(size)),
+                ..default()
+            },
+            transform: Transform::from_xyz(drift.x, drift.y, -0.05),
+            ..default()
+        },
+        Nebula {
+            drift,
+            velocity,
+            size,
+        },
+    ));
+}
+
+/// Drifts the nebulae, following part of the camera's movement, and removes
+/// them once they have crossed the arena
+pub fn move_nebulae(
+    mut commands: Commands,
+    time: Res<Time>,
+    follow: Res<CameraFollow>,
+    mut nebulae: Query<(Entity, &mut Nebula, &mut Transform)>,
+) {
+    for (entity, mut nebula, mut transform) in nebulae.iter_mut() {
+        let step = nebula.velocity * time.delta_seconds();
+        nebula.drift += step;
+        if nebula.drift.x.abs() > ARENA_WIDTH / 2. + nebula.size + 1. {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let position = nebula.drift + follow.centre * (1. - NEBULA_PARALLAX);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
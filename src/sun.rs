@@ -0,0 +1,355 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+    sprite::{Material2d, Mesh2dHandle},
+};
+
+use crate::collision::Collider;
+use crate::hitbox::HitboxSettings;
+use crate::rocket::Rocket;
+
+pub const SUN_RADIUS: f32 = 50.;
+pub const SUN_COLOR: Color = Color::srgb(7.0, 7.0, 0.0);
+
+// How far past its surface a sun's collider reaches
+const SUN_KILL_MARGIN: f32 = 5.;
+
+// Debug view: velocity lines show where a rocket is a second from now if
+// nothing pulls it, gravity lines are scaled down and capped to stay on screen
+const VELOCITY_LINE_SECONDS: f32 = 1.;
+const GRAVITY_LINE_SCALE: f32 = 0.05;
+const MAX_GRAVITY_LINE: f32 = 200.;
+const VELOCITY_LINE_COLOR: Color = Color::srgb(0.3, 0.6, 1.);
+const GRAVITY_LINE_COLOR: Color = Color::srgb(1., 0.3, 0.3);
+const SUN_DEBUG_COLOR: Color = Color::srgb(1., 0.8, 0.2);
+// The sun's mesh reaches this far past its radius to leave room for the flares
+const CORONA_SCALE: f32 = 1.6;
+// Brightness pulses per second, in radians
+const SUN_PULSE_SPEED: f32 = 1.5;
+
+/// Corona around the suns. Rockets inside it lose health faster the closer
+/// they get, the kill distance is its inner edge and destroys them outright.
+#[derive(Resource)]
+pub struct SunHeat {
+    // Width of the corona outside the kill distance
+    pub corona: f32,
+    // Health lost per second at the inner edge
+    pub damage_per_second: f32,
+}
+
+impl Default for SunHeat {
+    fn default() -> Self {
+        Self {
+            corona: 90.,
+            damage_per_second: 250.,
+        }
+    }
+}
+
+impl SunHeat {
+    /// Health lost per second `distance` away from a sun whose kill distance
+    /// is `inner`, falling off with the inverse distance to nothing at the
+    /// outer edge of the corona
+    pub fn damage(&self, distance: f32, inner: f32) -> f32 {
+        let outer = inner + self.corona;
+        if self.corona <= 0. || distance >= outer {
+            return 0.;
+        }
+        let distance = distance.max(inner);
+        self.damage_per_second * (1. / distance - 1. / outer) / (1. / inner - 1. / outer)
+    }
+}
+
+#[derive(Component)]
+pub struct Sun {
+    pub color: Color,
+    pub radius: f32,
+    // Radius the mesh was built with, the sun is scaled relative to it
+    mesh_radius: f32,
+}
+
+/// A sun of `radius` as `add_sun` makes it, for tests that place it by hand
+#[cfg(test)]
+pub fn test_sun(radius: f32) -> Sun {
+    Sun {
+        color: SUN_COLOR,
+        radius,
+        mesh_radius: radius,
+    }
+}
+
+/// Tuning for the sun's gravity and size, can be changed live
+#[derive(Resource, Clone, Copy)]
+pub struct PhysicsConfig {
+    pub g_force: f64,
+    // Rockets closer than this to the sun's centre are not pulled any further
+    pub gravity_cutoff: f32,
+    pub sun_radius: f32,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            // A circular orbit 300 units out is flown at 100 units per second
+            g_force: 3000000.0,
+            gravity_cutoff: 65.0,
+            sun_radius: SUN_RADIUS,
+        }
+    }
+}
+
+/// Where suns are placed, e.g. two entries for a binary star
+#[derive(Resource)]
+pub struct SunLayout(pub Vec<Vec2>);
+
+impl Default for SunLayout {
+    fn default() -> Self {
+        Self(vec![Vec2::ZERO])
+    }
+}
+
+pub fn add_sun(mut commands: Commands, config: Res<PhysicsConfig>, layout: Res<SunLayout>) {
+    for position in layout.0.iter() {
+        let sun = Sun {
+            color: SUN_COLOR,
+            radius: config.sun_radius,
+            mesh_radius: config.sun_radius,
+        };
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(3.0))),
+            sun_collider(&sun),
+            sun,
+        ));
+    }
+}
+
+/// Glowing surface and flickering corona of a sun, drawn by
+/// `shaders/sun_material.wgsl`
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct SunMaterial {
+    #[uniform(0)]
+    color: LinearRgba,
+    #[uniform(1)]
+    time: f32,
+    #[uniform(2)]
+    core_radius: f32,
+    #[uniform(3)]
+    pulse_speed: f32,
+}
+
+impl Material2d for SunMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/sun_material.wgsl".into()
+    }
+}
+
+/// Gives every new sun its disc and corona, sized to `Sun::mesh_radius`
+pub fn attach_sun_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<SunMaterial>>,
+    suns: Query<(Entity, &Sun), Added<Sun>>,
+) {
+    for (entity, sun) in suns.iter() {
+        let mesh = Mesh::from(Circle::new(sun.mesh_radius * CORONA_SCALE));
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(mesh)),
+            // Each sun gets its own material so they can change colour independently
+            materials.add(SunMaterial {
+                color: sun.color.into(),
+                time: 0.,
+                core_radius: 1. / CORONA_SCALE,
+                pulse_speed: SUN_PULSE_SPEED,
+            }),
+        ));
+    }
+}
+
+pub fn animate_sun_materials(time: Res<Time>, mut materials: ResMut<Assets<SunMaterial>>) {
+    for (_, material) in materials.iter_mut() {
+        material.time = time.elapsed_seconds();
+    }
+}
+
+/// The sun closest to `position`, the one whose pull dominates there
+pub fn nearest_sun<'a>(
+    suns: impl Iterator<Item = (&'a Sun, &'a Transform)>,
+    position: Vec2,
+) -> Option<(&'a Sun, &'a Transform)> {
+    suns.min_by(|(_, a), (_, b)| {
+        let a = a.translation.truncate().distance_squared(position);
+        let b = b.translation.truncate().distance_squared(position);
+        a.total_cmp(&b)
+    })
+}
+
+pub fn apply_physics_config(config: Res<PhysicsConfig>, mut suns: Query<&mut Sun>) {
+    if !config.is_changed() {
+        return;
+    }
+    for mut sun in &mut suns {
+        sun.radius = config.sun_radius;
+    }
+}
+
+/// Keeps the sun's material and size in sync with `Sun`, so a sun that
+/// changes colour or grows at runtime is also drawn that way.
+pub fn update_sun_material(
+    mut suns: Query<(&Sun, &Handle<SunMaterial>, &mut Transform), Changed<Sun>>,
+    mut materials: ResMut<Assets<SunMaterial>>,
+) {
+    for (sun, handle, mut transform) in suns.iter_mut() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.color = sun.color.into();
+        }
+        let scale = sun.radius / sun.mesh_radius;
+        transform.scale = Vec3::new(scale, scale, 1.);
+    }
+}
+
+/// Advances a body by one step of the suns' pull. Each sun stops pulling
+/// inside its own cutoff, so a body where two pulls cancel out drifts instead
+/// of jittering. Returns the new position and velocity.
+pub fn gravity_step(
+    position: Vec2,
+    velocity: Vec2,
+    suns: &[Vec2],
+    config: &PhysicsConfig,
+    max_speed: f32,
+    delta: f32,
+) -> (Vec2, Vec2) {
+    let acceleration = gravity_acceleration(position, suns, config);
+    let mut velocity = velocity + acceleration * delta;
+    if velocity.length() > max_speed {
+        velocity = velocity.normalize() * max_speed;
+    }
+    (position + velocity * delta, velocity)
+}
+
+/// Combined pull of the suns on a body at `position`
+pub fn gravity_acceleration(position: Vec2, suns: &[Vec2], config: &PhysicsConfig) -> Vec2 {
+    let mut acceleration = Vec2::ZERO;
+    for sun in suns {
+        let direction = *sun - position;
+        let distance = direction.length();
+
+        if distance < config.gravity_cutoff {
+            continue;
+        }
+
+        let force = config.g_force / (distance * distance) as f64;
+
+        acceleration += direction.normalize() * force as f32;
+    }
+    acceleration
+}
+
+/// Draws the suns' radii and each rocket's velocity and the pull on it while
+/// the debug view is on, the gravity is recomputed the same way
+/// `gravitational_pull` does
+pub fn draw_physics_debug(
+    settings: Res<HitboxSettings>,
+    config: Res<PhysicsConfig>,
+    mut gizmos: Gizmos,
+    suns: Query<(&Sun, &Transform)>,
+    rockets: Query<(&Rocket, &Transform)>,
+) {
+    if !settings.debug {
+        return;
+    }
+    let sun_positions: Vec<Vec2> = suns
+        .iter()
+        .map(|(_, transform)| transform.translation.truncate())
+        .collect();
+    for (sun, transform) in suns.iter() {
+        gizmos.circle_2d(transform.translation.truncate(), sun.radius, SUN_DEBUG_COLOR);
+    }
+    for (rocket, transform) in rockets.iter() {
+        let position = transform.translation.truncate();
+        gizmos.line_2d(
+            position,
+            position + rocket.velocity * VELOCITY_LINE_SECONDS,
+            VELOCITY_LINE_COLOR,
+        );
+        let gravity = gravity_acceleration(position, &sun_positions, &config);
+        gizmos.line_2d(
+            position,
+            position + (gravity * GRAVITY_LINE_SCALE).clamp_length_max(MAX_GRAVITY_LINE),
+            GRAVITY_LINE_COLOR,
+        );
+    }
+}
+
+/// A little larger than the sun itself, so rockets burn up before their
+/// sprite disappears into it
+pub fn sun_collider(sun: &Sun) -> Collider {
+    Collider {
+        radius: sun.radius + SUN_KILL_MARGIN,
+    }
+}
+
+/// Grows the colliders along with the suns, e.g. during overtime
+pub fn fit_sun_colliders(mut suns: Query<(&Sun, &mut Collider), Changed<Sun>>) {
+    for (sun, mut collider) in suns.iter_mut() {
+        *collider = sun_collider(sun);
+    }
+}
+
+/// Distance from the sun's centre at which a rocket touches its surface
+pub fn kill_distance(sun: &Sun, rocket: &Rocket) -> f32 {
+    // Half of `radius_collision` belongs to each rocket
+    rocket.radius_collision * 0.5 + sun_collider(sun).radius
+}
+
+/// How much rockets get stretched towards the sun as they fall in
+#[derive(Resource)]
+pub struct TidalStretch {
+    // Scale factor along the radial direction at the kill distance
+    pub max_stretch: f32,
+    // Distance from the sun where the stretching starts
+    pub onset_distance: f32,
+}
+
+impl Default for TidalStretch {
+    fn default() -> Self {
+        Self {
+            max_stretch: 1.35,
+            onset_distance: 220.,
+        }
+    }
+}
+
+/// Stretches rocket sprites along the direction to the sun and squashes them
+/// across it, growing to `max_stretch` right before the rocket burns up
+pub fn tidal_stretch(
+    settings: Res<TidalStretch>,
+    suns: Query<(&Sun, &Transform), Without<Rocket>>,
+    mut rockets: Query<(&Rocket, &mut Transform)>,
+) {
+    for (rocket, mut transform) in rockets.iter_mut() {
+        let Some((sun, sun_transform)) = nearest_sun(suns.iter(), transform.translation.truncate())
+        else {
+            return;
+        };
+        let sun_position = sun_transform.translation.truncate();
+        let offset = sun_position - transform.translation.truncate();
+        let distance = offset.length();
+        let kill = kill_distance(sun, rocket);
+        let onset = settings.onset_distance.max(kill + 1.);
+        let proximity = ((onset - distance) / (onset - kill)).clamp(0., 1.);
+
+        let stretch = 1. + (settings.max_stretch - 1.) * proximity;
+        let squash = 1. / stretch.sqrt();
+
+        // Weight each local axis by how much it lines up with the sun
+        let local = transform.rotation.inverse() * offset.normalize_or_zero().extend(0.);
+        let (weight_x, weight_y) = (local.x * local.x, local.y * local.y);
+        let radial = |weight: f32| weight * stretch + (1. - weight) * squash;
+
+        transform.scale = Vec3::new(
+            rocket.base_scale * radial(weight_x),
+            rocket.base_scale * radial(weight_y),
+            transform.scale.z,
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+
+use crate::rocket::RocketDamaged;
+use crate::score::RoundOver;
+
+/// Multiplies the delta the movement systems integrate with, 0 freezes
+/// everything in place and 1 is normal speed. Bevy's clocks are left alone so
+/// timers, effects and the UI keep running.
+#[derive(Resource, Clone)]
+pub struct TimeScale(pub f32);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl TimeScale {
+    /// This frame's delta after scaling, a negative scale counts as 0
+    pub fn delta(&self, time: &Time) -> f32 {
+        time.delta_seconds() * self.0.max(0.0)
+    }
+}
+
+/// Slow motion played when the kill that decides a round lands
+#[derive(Resource, Clone)]
+pub struct SlowMotion {
+    // Time scale right after the kill
+    pub min_scale: f32,
+    // Seconds it takes to get back to full speed
+    pub duration: f32,
+    remaining: f32,
+}
+
+impl Default for SlowMotion {
+    fn default() -> Self {
+        Self {
+            min_scale: 0.2,
+            duration: 1.5,
+            remaining: 0.0,
+        }
+    }
+}
+
+pub fn slow_motion_on_round_over(
+    mut events: EventReader<RoundOver>,
+    mut slow_motion: ResMut<SlowMotion>,
+    mut scale: ResMut<TimeScale>,
+    time: Res<Time>,
+) {
+    if events.read().count() > 0 {
+        slow_motion.remaining = slow_motion.duration;
+    }
+    // Leaves the scale alone otherwise, so it can be set by hand
+    if slow_motion.remaining <= 0.0 {
+        return;
+    }
+    slow_motion.remaining = (slow_motion.remaining - time.delta_seconds()).max(0.0);
+    let progress = 1.0 - slow_motion.remaining / slow_motion.duration;
+    // Squared so the moment lingers before picking up speed, ends exactly at 1
+    scale.0 = slow_motion.min_scale + (1.0 - slow_motion.min_scale) * progress * progress;
+}
+
+/// Brief near freeze when any rocket is destroyed, so the kill lands with weight
+#[derive(Resource, Clone)]
+pub struct HitStop {
+    // Time scale while the hit-stop lasts
+    pub scale: f32,
+    // Seconds it lasts, 0 turns it off
+    pub duration: f32,
+    remaining: f32,
+}
+
+impl Default for HitStop {
+    fn default() -> Self {
+        Self {
+            scale: 0.05,
+            duration: 0.08,
+            remaining: 0.0,
+        }
+    }
+}
+
+/// Runs after `slow_motion_on_round_over`: overrides its scale while the
+/// hit-stop lasts and hands back to it afterwards, or to full speed if no
+/// slow motion is playing
+pub fn hit_stop_on_destruction(
+    mut events: EventReader<RocketDamaged>,
+    mut hit_stop: ResMut<HitStop>,
+    slow_motion: Res<SlowMotion>,
+    mut scale: ResMut<TimeScale>,
+    time: Res<Time>,
+) {
+    if events.read().any(|event| event.destroyed) {
+        hit_stop.remaining = hit_stop.duration;
+    }
+    if hit_stop.remaining <= 0.0 {
+        return;
+    }
+    hit_stop.remaining = (hit_stop.remaining - time.delta_seconds()).max(0.0);
+    if hit_stop.remaining > 0.0 {
+        scale.0 = hit_stop.scale;
+    } else if slow_motion.remaining <= 0.0 {
+        scale.0 = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    use crate::asteroid::{move_asteroids, Asteroid};
+    use crate::bullet::{handle_bullet_movement, spawn_bullet, test_bullet};
+    use crate::rocket::{test_rocket, Arena, BoundaryMode, Rocket, RocketInput};
+    use crate::sun::PhysicsConfig;
+
+    const VELOCITY: Vec2 = Vec2::new(120., -40.);
+
+    /// How far a bullet and an asteroid travel in a 0.1 second step. There
+    /// are no suns, so both fly in a straight line.
+    fn distances(scale: f32) -> (f32, f32) {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_millis(100));
+        world.insert_resource(time);
+        world.insert_resource(TimeScale(scale));
+        world.init_resource::<PhysicsConfig>();
+        world.init_resource::<Arena>();
+        world.init_resource::<BoundaryMode>();
+        let bullet = world.spawn((Transform::default(), test_bullet(VELOCITY))).id();
+        let asteroid = world
+            .spawn((
+                Transform::default(),
+                Asteroid {
+                    velocity: VELOCITY,
+                    radius: 10.,
+                    health: 1.,
+                },
+            ))
+            .id();
+        world.run_system_once(handle_bullet_movement);
+        world.run_system_once(move_asteroids);
+        let distance = |entity| world.get::<Transform>(entity).unwrap().translation.length();
+        (distance(bullet), distance(asteroid))
+    }
+
+    #[test]
+    fn half_speed_travels_half_as_far() {
+        let (bullet, asteroid) = distances(1.);
+        assert!((bullet - VELOCITY.length() * 0.1).abs() < 1e-4);
+        let (slow_bullet, slow_asteroid) = distances(0.5);
+        assert!((slow_bullet - bullet * 0.5).abs() < 1e-4);
+        assert!((slow_asteroid - asteroid * 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn zero_freezes_everything() {
+        assert_eq!(distances(0.), (0., 0.));
+        assert_eq!(distances(-1.), (0., 0.));
+    }
+
+    /// Shots fired by a rocket holding the trigger for eight 0.125 second
+    /// steps, and how far one more step holding reload gets it
+    fn trigger(scale: f32) -> (u32, f32) {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_millis(125));
+        world.insert_resource(time);
+        world.insert_resource(TimeScale(scale));
+        let mut rocket = test_rocket(0);
+        rocket.fire_cooldown = 0.25;
+        rocket.last_fired = 0.;
+        let input = RocketInput {
+            fire: true,
+            ..default()
+        };
+        let entity = world.spawn((Transform::default(), rocket, input)).id();
+        for _ in 0..8 {
+            world.run_system_once(spawn_bullet);
+        }
+        let rocket = world.get::<Rocket>(entity).unwrap();
+        let fired = rocket.max_ammo - rocket.ammo;
+        world.entity_mut(entity).insert(RocketInput {
+            reload: true,
+            ..default()
+        });
+        world.run_system_once(spawn_bullet);
+        (fired, world.get::<Rocket>(entity).unwrap().reload_progress)
+    }
+
+    #[test]
+    fn half_speed_fires_and_reloads_half_as_fast() {
+        assert_eq!(trigger(1.), (4, 0.125));
+        assert_eq!(trigger(0.5), (2, 0.0625));
+        assert_eq!(trigger(0.), (0, 0.));
+    }
+}
@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+
+use crate::rocket::Rocket;
+use crate::sun::{gravity_step, kill_distance, PhysicsConfig, Sun};
+
+const PATH_COLOR: Color = Color::srgba(1., 1., 1., 0.35);
+
+/// Predicted path of each rocket if it coasted from here, toggled with F8
+#[derive(Resource)]
+pub struct TrajectorySettings {
+    pub enabled: bool,
+    // More steps reach further ahead but cost more per frame
+    pub steps: usize,
+    // Seconds simulated per step
+    pub step: f32,
+}
+
+impl Default for TrajectorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            steps: 120,
+            step: 1. / 30.,
+        }
+    }
+}
+
+pub fn toggle_trajectories(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<TrajectorySettings>,
+) {
+    if keys.just_pressed(KeyCode::F8) {
+        settings.enabled = !settings.enabled;
+    }
+}
+
+/// Runs the same gravity integration as `gravitational_pull` ahead of time,
+/// stopping where the path would burn up in a sun
+pub fn draw_trajectories(
+    mut gizmos: Gizmos,
+    settings: Res<TrajectorySettings>,
+    config: Res<PhysicsConfig>,
+    rockets: Query<(&Rocket, &Transform)>,
+    suns: Query<(&Sun, &Transform), Without<Rocket>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    let sun_positions: Vec<Vec2> = suns
+        .iter()
+        .map(|(_, transform)| transform.translation.truncate())
+        .collect();
+
+    for (rocket, transform) in rockets.iter() {
+        let mut position = transform.translation.truncate();
+        let mut velocity = rocket.velocity;
+        let mut path = vec![position];
+
+        for _ in 0..settings.steps {
+            (position, velocity) = gravity_step(
+                position,
+                velocity,
+                &sun_positions,
+                &config,
+                rocket.top_speed(),
+                settings.step,
+            );
+            path.push(position);
+
+            let burning = suns.iter().any(|(sun, sun_transform)| {
+                sun_transform.translation.truncate().distance(position)
+                    < kill_distance(sun, rocket)
+            });
+            if burning {
+                break;
+            }
+        }
+        gizmos.linestrip_2d(path, PATH_COLOR);
+    }
+}
@@ -0,0 +1,109 @@
+/// What a rocket fires, cycled with its weapon key
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum WeaponKind {
+    #[default]
+    Single,
+    // Three bullets fanned out around the facing direction
+    Spread,
+    // Faster, weaker bullets on a shorter cooldown
+    Rapid,
+    // Bullets that bank off the arena edges a few times
+    Bounce,
+    // A slow shot that steers towards the closest enemy rocket
+    Missile,
+}
+
+impl WeaponKind {
+    pub fn next(self) -> Self {
+        match self {
+            WeaponKind::Single => WeaponKind::Spread,
+            WeaponKind::Spread => WeaponKind::Rapid,
+            WeaponKind::Rapid => WeaponKind::Bounce,
+            WeaponKind::Bounce => WeaponKind::Missile,
+            WeaponKind::Missile => WeaponKind::Single,
+        }
+    }
+
+    /// Angle in degrees from the facing direction of every bullet in one shot
+    pub fn angles(self) -> &'static [f32] {
+        match self {
+            WeaponKind::Spread => &[-15., 0., 15.],
+            WeaponKind::Single | WeaponKind::Rapid | WeaponKind::Bounce | WeaponKind::Missile => {
+                &[0.]
+            }
+        }
+    }
+
+    pub fn bullet_speed(self) -> f32 {
+        match self {
+            WeaponKind::Single => 300.,
+            WeaponKind::Spread => 260.,
+            WeaponKind::Rapid => 380.,
+            WeaponKind::Bounce => 320.,
+            WeaponKind::Missile => 220.,
+        }
+    }
+
+    pub fn damage(self) -> f32 {
+        match self {
+            WeaponKind::Single => 25.,
+            WeaponKind::Spread => 15.,
+            WeaponKind::Rapid => 10.,
+            WeaponKind::Bounce => 20.,
+            WeaponKind::Missile => 35.,
+        }
+    }
+
+    /// Seconds a bullet flies before it is removed, faster bullets fade sooner
+    pub fn bullet_lifetime(self) -> f32 {
+        match self {
+            WeaponKind::Single => 4.,
+            WeaponKind::Spread => 2.5,
+            WeaponKind::Rapid => 1.5,
+            WeaponKind::Bounce => 6.,
+            WeaponKind::Missile => 5.,
+        }
+    }
+
+    /// How many times a bullet reflects off the arena edges before it is removed
+    pub fn bounces(self) -> u32 {
+        match self {
+            WeaponKind::Bounce => 3,
+            WeaponKind::Single
+            | WeaponKind::Spread
+            | WeaponKind::Rapid
+            | WeaponKind::Missile => 0,
+        }
+    }
+
+    /// Distance over which a bullet's damage falls off to `BulletImpact::falloff_floor`
+    /// of the full amount, `None` for bullets that hit equally hard at any range
+    pub fn falloff_range(self) -> Option<f32> {
+        match self {
+            WeaponKind::Spread => Some(350.),
+            WeaponKind::Single | WeaponKind::Rapid | WeaponKind::Bounce | WeaponKind::Missile => {
+                None
+            }
+        }
+    }
+
+    /// Turn rate in radians per second of a shot that homes in on enemy rockets
+    pub fn homing_turn_rate(self) -> Option<f32> {
+        match self {
+            WeaponKind::Missile => Some(100_f32.to_radians()),
+            WeaponKind::Single | WeaponKind::Spread | WeaponKind::Rapid | WeaponKind::Bounce => {
+                None
+            }
+        }
+    }
+
+    /// Multiplier applied to the rocket's own fire cooldown
+    pub fn cooldown_scale(self) -> f32 {
+        match self {
+            WeaponKind::Single | WeaponKind::Spread => 1.,
+            WeaponKind::Rapid => 0.4,
+            WeaponKind::Bounce => 1.2,
+            WeaponKind::Missile => 2.,
+        }
+    }
+}